@@ -1,4 +1,4 @@
-use ed25519_zebra::{SigningKey, VerificationKey};
+use ed25519_zebra::{batch, Signature, SigningKey, VerificationKey, VerificationKeyBytes};
 use rand::thread_rng;
 use thiserror::Error;
 
@@ -22,6 +22,20 @@ pub fn verify_signature(data: &[u8], public_key: &[u8], data_signature: &[u8]) -
     Ok(())
 }
 
+/// Verify the signatures of multiple pieces of data at once
+///
+/// Items are tuples of (data, public_key, signature). This is faster than verifying each signature individually,
+/// but only tells if all signatures are valid, not which one is invalid.
+pub fn verify_signatures(items: &[(&[u8], &[u8], &[u8])]) -> Result<(), Ed25519Error> {
+    let mut verifier = batch::Verifier::new();
+    for (data, public_key, data_signature) in items {
+        let key_bytes: [u8; 32] = (*public_key).try_into().map_err(|_| Ed25519Error::VerificationError)?;
+        let sig_bytes: [u8; 64] = (*data_signature).try_into().map_err(|_| Ed25519Error::VerificationError)?;
+        verifier.queue((VerificationKeyBytes::from(key_bytes), Signature::from(sig_bytes), *data));
+    }
+    verifier.verify(thread_rng()).map_err(|_| Ed25519Error::VerificationError)
+}
+
 /// create signature from data
 pub fn create_signature(data: &[u8], private_key: &[u8]) -> Result<[u8; 64], Ed25519Error> {
     let signing_key = SigningKey::try_from(private_key).map_err(|_| Ed25519Error::VerificationError)?;
@@ -1,6 +1,6 @@
 use super::shared::Command;
 use anyhow::{Context, Result};
-use pog_proto::api::SignedBlock;
+use pog_proto::api::{AccountID, RawBlock, SignedBlock};
 use tokio::sync::{mpsc, oneshot};
 
 #[derive(Debug, Clone)]
@@ -24,20 +24,40 @@ impl BlockpoolClient {
         resp_rx.await?
     }
 
-    pub async fn process_final_vote(&self, block: pog_proto::api::RawBlock, _vote: u64) -> Result<()> {
-        let block: SignedBlock = block.try_into()?;
-
+    /// Adds the final vote of `voter` for a block
+    pub async fn process_final_vote(&self, block: RawBlock, voter: AccountID) -> Result<()> {
         self.send_command(|resp| Command::ProcessFinalVote {
             block,
+            voter,
             resp,
         })
         .await
     }
 
-    pub async fn process_vote_proposal(&self, block: pog_proto::api::RawBlock, _vote: u64) -> Result<()> {
-        let block: SignedBlock = block.try_into()?;
+    /// Starts a vote on a block submitted to this node
+    pub async fn submit_block(&self, block: RawBlock) -> Result<()> {
+        self.send_command(|resp| Command::SubmitBlock {
+            block,
+            resp,
+        })
+        .await
+    }
 
+    /// Adds the vote of `voter` for a block proposed by the network
+    ///
+    /// Proposals without a voter only start a vote on the block.
+    pub async fn process_vote_proposal(&self, block: RawBlock, voter: Option<AccountID>) -> Result<()> {
         self.send_command(|resp| Command::ProcessVoteProposal {
+            block,
+            voter,
+            resp,
+        })
+        .await
+    }
+
+    /// Appends a block fetched from a peer to the chain, unless a block at its height is being voted on
+    pub async fn add_synced_block(&self, block: SignedBlock) -> Result<()> {
+        self.send_command(|resp| Command::AddSyncedBlock {
             block,
             resp,
         })
@@ -51,9 +71,11 @@ impl BlockpoolClient {
         .await
     }
 
-    /// Gets the total voting power in the network
-    pub fn get_total_network_power(&self) -> f64 {
-        //TODO: Get all voting power of all prime delegates combined
-        100_000_000_f64
+    /// Blocks that are waiting for a vote, oldest first
+    pub async fn get_pending_blocks(&self) -> Result<Vec<SignedBlock>> {
+        self.send_command(|resp| Command::GetPendingBlocks {
+            resp,
+        })
+        .await
     }
 }
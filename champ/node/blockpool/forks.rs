@@ -0,0 +1,90 @@
+use super::votes::Votes;
+use pog_proto::api::{AccountID, BlockID};
+use std::collections::HashMap;
+
+/// Pending blocks that compete for the same height of an account chain
+#[derive(Debug, Default)]
+pub struct Forks {
+    candidates: HashMap<(AccountID, u64), Vec<BlockID>>,
+}
+
+impl Forks {
+    /// Adds a candidate for a height. Returns `true` if another block already competes for it.
+    pub fn add(&mut self, account: AccountID, height: u64, block_id: BlockID) -> bool {
+        let candidates = self.candidates.entry((account, height)).or_default();
+        if !candidates.contains(&block_id) {
+            candidates.push(block_id);
+        }
+        candidates.len() > 1
+    }
+
+    pub fn candidates(&self, account: &AccountID, height: u64) -> Vec<BlockID> {
+        self.candidates.get(&(*account, height)).cloned().unwrap_or_default()
+    }
+
+    /// Removes a single candidate that can no longer be added to the chain
+    pub fn discard(&mut self, account: &AccountID, height: u64, block_id: &BlockID) {
+        if let Some(candidates) = self.candidates.get_mut(&(*account, height)) {
+            candidates.retain(|candidate| candidate != block_id);
+            if candidates.is_empty() {
+                self.candidates.remove(&(*account, height));
+            }
+        }
+    }
+
+    /// Removes all candidates for a height once one of them was added to the chain
+    pub fn remove(&mut self, account: &AccountID, height: u64) -> Vec<BlockID> {
+        self.candidates.remove(&(*account, height)).unwrap_or_default()
+    }
+}
+
+/// The candidate with the most voting power
+///
+/// Ties go to the lowest block ID, so every node that has seen the same votes picks the same block.
+pub fn choose(candidates: &[BlockID], votes: &Votes) -> Option<BlockID> {
+    candidates.iter().copied().max_by(|a, b| votes.power(a).cmp(&votes.power(b)).then_with(|| b.cmp(a)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forks() {
+        let mut forks = Forks::default();
+        assert!(!forks.add([1; 24], 5, [1; 32]));
+        assert!(!forks.add([1; 24], 5, [1; 32]));
+        assert!(!forks.add([1; 24], 6, [2; 32]));
+        assert!(!forks.add([2; 24], 5, [3; 32]));
+        assert!(forks.add([1; 24], 5, [4; 32]));
+
+        assert_eq!(vec![[1; 32], [4; 32]], forks.candidates(&[1; 24], 5));
+        assert_eq!(vec![[1; 32], [4; 32]], forks.remove(&[1; 24], 5));
+        assert!(forks.candidates(&[1; 24], 5).is_empty());
+
+        forks.add([1; 24], 6, [5; 32]);
+        forks.discard(&[1; 24], 6, &[2; 32]);
+        assert_eq!(vec![[5; 32]], forks.candidates(&[1; 24], 6));
+        forks.discard(&[1; 24], 6, &[5; 32]);
+        assert!(forks.candidates(&[1; 24], 6).is_empty());
+    }
+
+    #[test]
+    fn test_choose() {
+        let mut votes = Votes::default();
+        let candidates = [[3; 32], [1; 32], [2; 32]];
+        assert_eq!(None, choose(&[], &votes));
+
+        // without votes, the lowest block ID wins
+        assert_eq!(Some([1; 32]), choose(&candidates, &votes));
+
+        votes.add_vote([2; 32], [1; 24], 100);
+        votes.add_vote([3; 32], [2; 24], 50);
+        votes.add_vote([3; 32], [3; 24], 60);
+        assert_eq!(Some([3; 32]), choose(&candidates, &votes));
+
+        // ties are broken by the block ID
+        votes.add_vote([2; 32], [4; 24], 10);
+        assert_eq!(Some([2; 32]), choose(&candidates, &votes));
+    }
+}
@@ -1,6 +1,12 @@
 mod client;
+mod forks;
+mod orphans;
+mod recent;
 mod server;
 mod shared;
+mod status;
+mod votes;
 
 pub use client::BlockpoolClient;
 pub use server::Blockpool;
+pub use status::{get_status, set_status, BlockStatus, BlockStatuses};
@@ -0,0 +1,160 @@
+use pog_proto::api::{AccountID, BlockID, RawBlock};
+use std::collections::HashMap;
+
+/// A block whose previous block is unknown, together with the votes that arrived for it
+#[derive(Debug, Clone)]
+pub struct Orphan {
+    pub block_id: BlockID,
+    pub raw_block: RawBlock,
+    pub votes: Vec<AccountID>,
+    pub final_votes: Vec<AccountID>,
+    received: u64,
+    // orders orphans that were received in the same second
+    sequence: u64,
+}
+
+/// Blocks that arrived before their previous block, keyed by the ID of the missing block
+#[derive(Debug, Default)]
+pub struct Orphans {
+    blocks: HashMap<BlockID, Vec<Orphan>>,
+    next_sequence: u64,
+}
+
+impl Orphans {
+    /// Holds a block until its previous block is added to the chain
+    ///
+    /// If the pool is full, the oldest orphans are dropped and their IDs returned.
+    pub fn add(
+        &mut self,
+        parent: BlockID,
+        block_id: BlockID,
+        raw_block: RawBlock,
+        now: u64,
+        max_orphans: usize,
+    ) -> Vec<BlockID> {
+        if self.contains(&block_id) {
+            return vec![];
+        }
+
+        self.blocks.entry(parent).or_default().push(Orphan {
+            block_id,
+            raw_block,
+            votes: vec![],
+            final_votes: vec![],
+            received: now,
+            sequence: self.next_sequence,
+        });
+        self.next_sequence += 1;
+
+        let mut dropped = vec![];
+        while self.len() > max_orphans {
+            let oldest =
+                self.iter().min_by_key(|orphan| (orphan.received, orphan.sequence)).map(|orphan| orphan.block_id);
+            match oldest {
+                Some(oldest) => dropped.extend(self.remove_where(|orphan| orphan.block_id == oldest)),
+                None => break,
+            }
+        }
+        dropped
+    }
+
+    /// Remembers a vote for an orphan, so it can be counted once the orphan is validated
+    pub fn add_vote(&mut self, block_id: &BlockID, voter: AccountID, final_vote: bool) {
+        let orphan = self.blocks.values_mut().flatten().find(|orphan| &orphan.block_id == block_id);
+        if let Some(orphan) = orphan {
+            let votes = match final_vote {
+                true => &mut orphan.final_votes,
+                false => &mut orphan.votes,
+            };
+            if !votes.contains(&voter) {
+                votes.push(voter);
+            }
+        }
+    }
+
+    /// Removes and returns the orphans waiting for a block
+    pub fn take(&mut self, parent: &BlockID) -> Vec<Orphan> {
+        self.blocks.remove(parent).unwrap_or_default()
+    }
+
+    /// Drops orphans that waited longer than `expiry` seconds for their previous block and returns their IDs
+    pub fn expire(&mut self, now: u64, expiry: u64) -> Vec<BlockID> {
+        self.remove_where(|orphan| now.saturating_sub(orphan.received) > expiry)
+    }
+
+    pub fn contains(&self, block_id: &BlockID) -> bool {
+        self.iter().any(|orphan| &orphan.block_id == block_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Orphan> {
+        self.blocks.values().flatten()
+    }
+
+    fn remove_where(&mut self, predicate: impl Fn(&Orphan) -> bool) -> Vec<BlockID> {
+        let mut removed = vec![];
+        for orphans in self.blocks.values_mut() {
+            removed.extend(orphans.iter().filter(|orphan| predicate(orphan)).map(|orphan| orphan.block_id));
+            orphans.retain(|orphan| !predicate(orphan));
+        }
+        self.blocks.retain(|_, orphans| !orphans.is_empty());
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_block() -> RawBlock {
+        RawBlock {
+            header: None,
+            data: vec![],
+        }
+    }
+
+    #[test]
+    fn test_orphans() {
+        let mut orphans = Orphans::default();
+        assert!(orphans.add([1; 32], [2; 32], raw_block(), 0, 10).is_empty());
+        assert!(orphans.add([1; 32], [3; 32], raw_block(), 0, 10).is_empty());
+        assert!(orphans.add([1; 32], [3; 32], raw_block(), 0, 10).is_empty());
+        assert!(orphans.add([4; 32], [5; 32], raw_block(), 0, 10).is_empty());
+        assert_eq!(3, orphans.len());
+        assert!(orphans.contains(&[3; 32]));
+
+        orphans.add_vote(&[2; 32], [1; 24], false);
+        orphans.add_vote(&[2; 32], [1; 24], false);
+        orphans.add_vote(&[2; 32], [1; 24], true);
+        orphans.add_vote(&[9; 32], [1; 24], true);
+
+        let released = orphans.take(&[1; 32]);
+        assert_eq!(vec![[2; 32], [3; 32]], released.iter().map(|orphan| orphan.block_id).collect::<Vec<_>>());
+        assert_eq!(vec![[1; 24]], released[0].votes);
+        assert_eq!(vec![[1; 24]], released[0].final_votes);
+        assert!(orphans.take(&[1; 32]).is_empty());
+        assert_eq!(1, orphans.len());
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut orphans = Orphans::default();
+        orphans.add([1; 32], [2; 32], raw_block(), 10, 2);
+        orphans.add([6; 32], [7; 32], raw_block(), 10, 2);
+        assert_eq!(vec![[2; 32]], orphans.add([1; 32], [3; 32], raw_block(), 20, 2));
+        assert_eq!(vec![[7; 32]], orphans.add([4; 32], [5; 32], raw_block(), 30, 2));
+        assert_eq!(2, orphans.len());
+
+        assert!(orphans.expire(40, 20).is_empty());
+        assert_eq!(vec![[3; 32]], orphans.expire(41, 20));
+        assert_eq!(vec![[5; 32]], orphans.expire(100, 20));
+        assert!(orphans.is_empty());
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::{HashSet, VecDeque};
+
+use pog_proto::api::BlockID;
+
+/// Block IDs that are remembered until `max` newer ones were added, so they can't exhaust memory
+#[derive(Debug)]
+pub struct RecentBlocks {
+    ids: HashSet<BlockID>,
+    order: VecDeque<BlockID>,
+    max: usize,
+}
+
+impl RecentBlocks {
+    pub fn new(max: usize) -> Self {
+        Self {
+            ids: HashSet::new(),
+            order: VecDeque::new(),
+            max,
+        }
+    }
+
+    pub fn contains(&self, block_id: &BlockID) -> bool {
+        self.ids.contains(block_id)
+    }
+
+    pub fn insert(&mut self, block_id: BlockID) {
+        if !self.ids.insert(block_id) {
+            return;
+        }
+
+        self.order.push_back(block_id);
+        while self.order.len() > self.max {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, block_id: &BlockID) {
+        if self.ids.remove(block_id) {
+            self.order.retain(|id| id != block_id);
+        }
+    }
+}
+
+impl Extend<BlockID> for RecentBlocks {
+    fn extend<T: IntoIterator<Item = BlockID>>(&mut self, block_ids: T) {
+        block_ids.into_iter().for_each(|block_id| self.insert(block_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_blocks() {
+        let mut recent = RecentBlocks::new(2);
+        recent.extend([[1; 32], [2; 32], [1; 32]]);
+        assert!(recent.contains(&[1; 32]));
+
+        // the oldest blocks are forgotten
+        recent.insert([3; 32]);
+        assert!(!recent.contains(&[1; 32]));
+        assert!(recent.contains(&[2; 32]) && recent.contains(&[3; 32]));
+
+        recent.remove(&[2; 32]);
+        recent.insert([4; 32]);
+        assert!(recent.contains(&[3; 32]) && recent.contains(&[4; 32]));
+        assert_eq!(2, recent.order.len());
+    }
+}
@@ -1,18 +1,48 @@
 #![allow(dead_code)]
 
 use super::client;
+use super::forks::{self, Forks};
+use super::orphans::{Orphan, Orphans};
+use super::recent::RecentBlocks;
 use super::shared::Command;
+use super::status::{set_status, BlockStatus};
+use super::votes::Votes;
+use crate::consensus::{epoch_power, voting_power};
+use crate::p2p::server::timestamp;
+use crate::p2p::types::{request_body, RequestBodyData};
 use crate::state::ChampStateArc;
-use anyhow::Result;
-use pog_proto::api::{BlockID, SignedBlock};
-use std::collections::{HashMap, HashSet, VecDeque};
+use crate::storage::{Database, DatabaseError};
+use crate::validation::block::{validate, validate_fork, BlockValidationError, Validation};
+use crate::validation::spam;
+use anyhow::{anyhow, Result};
+use encoding::account::{generate_account_address, parse_account_address_string};
+use pog_proto::api::{transaction, AccountID, BlockID, RawBlock, SignedBlock, Transaction};
+use pog_proto::rpc::node_admin::Mode;
+use std::collections::{HashSet, VecDeque};
 use tokio::sync::mpsc::{self, Receiver, Sender};
 
-use tracing::info;
+use tracing::{debug, info, warn};
+
+// rejected blocks that are remembered, so late votes for them are ignored
+const MAX_REJECTED: usize = 10_000;
+// blocks that are remembered to have gotten this node's final vote, far more than are usually pending at once
+const MAX_SENT_VOTES: usize = 10_000;
 
 #[derive(Debug)]
 struct QueueItem {
-    block: pog_proto::api::SignedBlock,
+    block: SignedBlock,
+    raw_block: RawBlock,
+    account: AccountID,
+}
+
+/// What happened to a block that was proposed or voted for
+enum Added {
+    /// the block is waiting for votes
+    Pending(BlockID),
+    /// the block is waiting for its previous block
+    Orphan(BlockID),
+    /// the block was already added to the chain or lost the vote against a competing block
+    Ignored,
 }
 
 #[derive(Debug)]
@@ -21,8 +51,16 @@ pub struct Blockpool {
     rx: Receiver<Command>,
     block_queue: VecDeque<QueueItem>,
     state: Option<ChampStateArc>,
-    block_votes: HashMap<BlockID, Vec<u64>>,
-    sent_votes: HashSet<BlockID>,
+    votes: Votes,
+    final_votes: Votes,
+    // blocks this node sent its final vote for
+    sent_votes: RecentBlocks,
+    forks: Forks,
+    // blocks that lost a vote against a competing block
+    rejected: RecentBlocks,
+    orphans: Orphans,
+    // orphans whose previous block was added, to be proposed after the current command
+    released: VecDeque<Orphan>,
 }
 
 impl Default for Blockpool {
@@ -40,8 +78,13 @@ impl Blockpool {
             rx,
             block_queue: VecDeque::with_capacity(10_000),
             state: None,
-            block_votes: HashMap::new(),
-            sent_votes: HashSet::new(),
+            votes: Votes::default(),
+            final_votes: Votes::default(),
+            sent_votes: RecentBlocks::new(MAX_SENT_VOTES),
+            forks: Forks::default(),
+            rejected: RecentBlocks::new(MAX_REJECTED),
+            orphans: Orphans::default(),
+            released: VecDeque::new(),
         }
     }
 
@@ -61,118 +104,923 @@ impl Blockpool {
         info!("blockpool started listening to incoming commands");
         while let Some(cmd) = self.rx.recv().await {
             match cmd {
+                Command::SubmitBlock {
+                    block,
+                    resp,
+                } => {
+                    let result = self.process_proposal(block, None).await;
+                    self.process_orphans().await;
+                    let _ = resp.send(result);
+                }
                 Command::ProcessVoteProposal {
-                    block: _,
-                    resp: _,
+                    block,
+                    voter,
+                    resp,
                 } => {
-                    // let result = block::validate(&block, &state).await;
-                    // //TODO: Fix this with different quorum!
-                    // let quorum = self.calculate_quorum(&block);
-
-                    // // Quorum setting in Consensus module - currently 60%
-                    // if quorum > consensus::voting_power::VOTE_PERCENTAGE_NEEDED {
-                    //     match result {
-                    //         Ok(_) => {
-                    //             self.block_queue.push_back(QueueItem {
-                    //                 block: block.clone(),
-                    //             });
-                    //             self.sent_votes.remove(&block.get_id());
-                    //             let _ = resp.send(Ok(()));
-                    //         } //TODO: Send a final vote back
-                    //         Err(block::BlockValidationError::Invalid(_)) => {
-                    //             let _ = resp.send(Ok(()));
-                    //         } //TODO: maybe retry or handle errors and then Start a vote
-                    //         Err(block::BlockValidationError::Error(err)) => {
-                    //             let _ = resp.send(Err(anyhow!("error {err}")));
-                    //         }
-                    //     }
-                    // }
+                    let result = self.process_proposal(block, voter).await;
+                    self.process_orphans().await;
+                    let _ = resp.send(result);
                 }
                 Command::ProcessFinalVote {
-                    block: _,
-                    resp: _,
+                    block,
+                    voter,
+                    resp,
                 } => {
-                    // //TODO: Fix this
-                    // // here a vote is received
-                    // // if prime delegate, add vote and send back
-                    // // if quorum is reached, send final vote
-                    // let block_id = block.get_id();
-                    // if self.sent_votes.contains(&block_id) {
-                    //     let _ = resp.send(Err(anyhow!("block already processed")));
-                    //     continue;
-                    // }
-                    // self.sent_votes.insert(block_id);
-
-                    // let wallet_manager = state.wallet_manager.read().await;
-                    // let wallet =
-                    //     wallet_manager.primary_wallet().await.ok_or_else(|| anyhow!("no primary wallet found"));
-                    // if wallet.is_err() {
-                    //     continue;
-                    // }
-                    // let this_account_address = wallet.unwrap().account_address_bytes;
-
-                    // let result = voting_power::get_active_power(
-                    //     self.state.as_ref().expect("how did this happen?"),
-                    //     this_account_address,
-                    // )
-                    // .await;
-                    // if result.is_err() {
-                    //     continue;
-                    // }
-                    // let this_voting_power = result.unwrap();
-
-                    // // TODO: only add own voting if this is delegate
-                    // // TODO: check somewhere that the same sender cant vote twice
-                    // self.save_vote(vote, &block_id);
-                    // self.save_vote(this_voting_power, &block_id);
-
-                    // // Add own vote to quorum if prime delegate
-                    // let _quorum = self.calculate_quorum(&block);
-
-                    // let result = block::validate(&block, &state).await;
-                    // match result {
-                    //     Ok(_) => {
-                    //         let _ = resp.send(Ok(()));
-                    //     }
-                    //     Err(_) => {
-                    //         let _ = resp.send(Err(anyhow!("not implemented")));
-                    //     }
-                    // }
+                    let result = self.process_final_vote(block, voter).await;
+                    self.process_orphans().await;
+                    let _ = resp.send(result);
+                }
+                Command::AddSyncedBlock {
+                    block,
+                    resp,
+                } => {
+                    let result = self.add_synced_block(block).await;
+                    self.process_orphans().await;
+                    let _ = resp.send(result);
                 }
                 Command::GetQueueSize {
                     resp,
                 } => {
                     let _ = resp.send(Ok(self.block_queue.len() as u64));
                 }
+                Command::GetPendingBlocks {
+                    resp,
+                } => {
+                    let _ = resp.send(Ok(self.block_queue.iter().map(|item| item.block.clone()).collect()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a vote for a block and starts the final vote once it has reached quorum
+    ///
+    /// Blocks are validated the first time they are seen. If this node is a prime delegate, it votes for them
+    /// and sends its vote to the network.
+    async fn process_proposal(&mut self, raw_block: RawBlock, voter: Option<AccountID>) -> Result<()> {
+        let state = self.state.clone().expect("state was checked");
+        let block_id = match self.add_pending(&state, raw_block).await? {
+            Added::Pending(block_id) => block_id,
+            Added::Orphan(block_id) => {
+                if let Some(voter) = voter {
+                    self.orphans.add_vote(&block_id, voter, false);
+                }
+                return Ok(());
+            }
+            Added::Ignored => return Ok(()),
+        };
+
+        // votes can arrive more than once through different peers and only prime delegates can vote
+        self.remove_excluded(&state);
+        let voter = voter
+            .filter(|voter| !self.votes.has_voted(&block_id, voter))
+            .filter(|voter| !state.equivocations.is_excluded(voter, timestamp()));
+        if let Some(voter) = voter.filter(|voter| state.prime_delegates.contains(voter)) {
+            let power = epoch_power::get_vote_power(&state, &voter)?;
+            self.votes.add_vote(block_id, voter, power);
+        }
+
+        // votes for all blocks competing for the same height count towards the quorum
+        let candidates = self.candidates(&block_id);
+        let online_power = state.network_power.online_power();
+        let quorum: f64 = candidates.iter().map(|candidate| self.votes.quorum(candidate, online_power)).sum();
+        debug!("block {block_id:?} has a quorum of {quorum}");
+
+        // Quorum setting in Consensus module - currently 60%
+        if quorum >= voting_power::VOTE_PERCENTAGE_NEEDED {
+            if let Some(leader) = forks::choose(&candidates, &self.votes) {
+                self.send_final_vote(&state, &leader).await?;
             }
         }
+
+        for candidate in candidates {
+            self.update_status(&state, &candidate).await?;
+            self.check_final_quorum(&state, &candidate).await?;
+        }
         Ok(())
     }
 
-    fn calculate_quorum(&self, block: &SignedBlock) -> f64 {
-        // count the final votes received based on a blockID and once 60% of the online voting has been reached, add the block to the chain
+    /// Adds a final vote for a block and appends the block to the chain once it has reached quorum
+    async fn process_final_vote(&mut self, raw_block: RawBlock, voter: AccountID) -> Result<()> {
+        let state = self.state.clone().expect("state was checked");
+        let block_id = match self.add_pending(&state, raw_block).await? {
+            Added::Pending(block_id) => block_id,
+            Added::Orphan(block_id) => {
+                self.orphans.add_vote(&block_id, voter, true);
+                return Ok(());
+            }
+            Added::Ignored => return Ok(()),
+        };
+
+        if !state.prime_delegates.contains(&voter) {
+            return Err(anyhow!("final vote from an account that is not a prime delegate"));
+        }
+
+        if self.final_votes.has_voted(&block_id, &voter) {
+            return Err(anyhow!("duplicate final vote"));
+        }
+
+        self.remove_excluded(&state);
+        if state.equivocations.is_excluded(&voter, timestamp()) {
+            return Err(anyhow!("final vote from an account that signed votes for competing blocks"));
+        }
+
+        let power = epoch_power::get_vote_power(&state, &voter)?;
+        self.final_votes.add_vote(block_id, voter, power);
+
+        self.update_status(&state, &block_id).await?;
+        self.check_final_quorum(&state, &block_id).await
+    }
+
+    /// Validates a block and adds it to the queue, if it has not been seen before
+    ///
+    /// Blocks for a height that is already taken by another block are forks. Prime delegates only vote for the
+    /// first block they see at each height, and never for a fork of a block that is already in their chain.
+    ///
+    /// Blocks that build on a block this node does not have yet are held as orphans until it is added.
+    async fn add_pending(&mut self, state: &ChampStateArc, raw_block: RawBlock) -> Result<Added> {
+        let block: SignedBlock = raw_block.clone().try_into()?;
+        let block_id = block.get_id();
+
+        if self.is_pending(&block_id) {
+            return Ok(Added::Pending(block_id));
+        }
+
+        // late votes for blocks that were already added or rejected
+        if self.rejected.contains(&block_id) {
+            return Ok(Added::Ignored);
+        }
+
+        if self.orphans.contains(&block_id) {
+            return Ok(Added::Orphan(block_id));
+        }
+
+        set_status(state, block_id, BlockStatus::Received).await?;
+        if let Err(e) = spam::check_spam_index(&block, state).await {
+            set_status(state, block_id, BlockStatus::rejected(&e)).await?;
+            return Err(e.into());
+        }
+
+        set_status(state, block_id, BlockStatus::Validating).await?;
+        let forks_chain = match validate(&block, state).await {
+            Ok(_) => false,
+            Err(BlockValidationError::Invalid(Validation::BlockDuplicate)) => {
+                set_status(state, block_id, BlockStatus::Confirmed).await?;
+                return Ok(Added::Ignored);
+            }
+            Err(BlockValidationError::Invalid(Validation::BlockHeightError | Validation::PreviousBlockError)) => {
+                if let Some(parent) = self.missing_parent(state, &block).await? {
+                    self.add_orphan(state, parent, block_id, raw_block).await?;
+                    // the previous block might never be proposed again, so it is fetched from a peer
+                    state.sync_client.sync_account(generate_account_address(block.header.public_key.clone())?);
+                    return Ok(Added::Orphan(block_id));
+                }
+
+                if let Err(e) = validate_fork(&block, state).await {
+                    set_status(state, block_id, BlockStatus::rejected(&e)).await?;
+                    return Err(e.into());
+                }
+                true
+            }
+            Err(e) => {
+                set_status(state, block_id, BlockStatus::rejected(&e)).await?;
+                return Err(e.into());
+            }
+        };
+
+        let account = generate_account_address(block.header.public_key.clone())?;
+        let height = block.data.height;
+        if self.forks.add(account, height, block_id) || forks_chain {
+            warn!("block {block_id:?} competes with another block at height {height} of {account:?}");
+        }
+
+        let own_vote = match forks_chain {
+            true => None,
+            false => self.own_vote(state).await?,
+        };
+        let own_vote = own_vote.filter(|(voter, _)| {
+            !self.forks.candidates(&account, height).iter().any(|candidate| self.votes.has_voted(candidate, voter))
+        });
+        if let Some((account, power)) = own_vote {
+            self.votes.add_vote(block_id, account, power);
+        }
+
+        let proposal = request_body::VoteProposal {
+            block: Some(raw_block.clone()),
+            vote: own_vote.map_or(0, |(_, power)| power),
+        };
+        if let Err(e) = state.p2p_client.broadcast(RequestBodyData::VoteProposal(proposal)) {
+            tracing::error!("could not send vote proposal: {e}");
+        }
+
+        self.block_queue.push_back(QueueItem {
+            block,
+            raw_block,
+            account,
+        });
+        self.update_status(state, &block_id).await?;
+        Ok(Added::Pending(block_id))
+    }
+
+    /// Appends a block fetched from a peer to the chain
+    ///
+    /// Blocks at a height that has pending blocks are left to the vote. A synced block is replaced like any other
+    /// block in the chain if a competing block reaches final quorum later.
+    async fn add_synced_block(&mut self, block: SignedBlock) -> Result<()> {
+        let state = self.state.clone().expect("state was checked");
         let block_id = block.get_id();
+        let account = generate_account_address(block.header.public_key.clone())?;
+
+        if self.rejected.contains(&block_id) {
+            return Err(anyhow!("block {block_id:?} was rejected"));
+        }
+        if !self.forks.candidates(&account, block.data.height).is_empty() {
+            return Err(anyhow!("a block at height {} of {account:?} is being voted on", block.data.height));
+        }
+
+        validate(&block, &state).await?;
+        state.db.lock().await.add_block(block.clone()).await?;
+        state.voting_power_cache.invalidate(&block);
+        epoch_power::block_synced(&state, &block).await;
+        set_status(&state, block_id, BlockStatus::Confirmed).await?;
+
+        // blocks that arrived before this one can be voted on now
+        self.released.extend(self.orphans.take(&block_id));
+        Ok(())
+    }
+
+    /// The ID of the block a block builds on, if it is neither in the database nor rejected
+    async fn missing_parent(&self, state: &ChampStateArc, block: &SignedBlock) -> Result<Option<BlockID>> {
+        if block.data.height == 0 {
+            return Ok(None);
+        }
+
+        let parent: BlockID = match block.data.previous.clone().try_into() {
+            Ok(parent) => parent,
+            Err(_) => return Ok(None),
+        };
+        if self.rejected.contains(&parent) {
+            return Ok(None);
+        }
+
+        match state.db.lock().await.get_block_by_id(parent).await {
+            Ok(_) => Ok(None),
+            Err(DatabaseError::BlockNotFound) => Ok(Some(parent)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn add_orphan(
+        &mut self,
+        state: &ChampStateArc,
+        parent: BlockID,
+        block_id: BlockID,
+        raw_block: RawBlock,
+    ) -> Result<()> {
+        debug!("block {block_id:?} waits for its previous block {parent:?}");
+        let max_orphans = state.config.read().await.consensus.max_orphans;
+
+        for dropped in self.orphans.add(parent, block_id, raw_block, timestamp(), max_orphans) {
+            let status = BlockStatus::rejected("too many blocks are waiting for their previous block");
+            set_status(state, dropped, status).await?;
+        }
+        if self.orphans.contains(&block_id) {
+            set_status(state, block_id, BlockStatus::Orphaned).await?;
+        }
+        Ok(())
+    }
+
+    /// Proposes the orphans whose previous block was added, together with the votes that arrived for them,
+    /// and drops orphans that waited too long
+    async fn process_orphans(&mut self) {
+        let state = self.state.clone().expect("state was checked");
+
+        while let Some(orphan) = self.released.pop_front() {
+            let Orphan {
+                block_id,
+                raw_block,
+                votes,
+                final_votes,
+                ..
+            } = orphan;
+            debug!("previous block of orphan {block_id:?} was added");
+
+            if let Err(e) = self.process_proposal(raw_block.clone(), None).await {
+                warn!("could not add orphan {block_id:?}: {e}");
+                continue;
+            }
+            for voter in votes {
+                if let Err(e) = self.process_proposal(raw_block.clone(), Some(voter)).await {
+                    warn!("could not add vote for orphan {block_id:?}: {e}");
+                }
+            }
+            for voter in final_votes {
+                if let Err(e) = self.process_final_vote(raw_block.clone(), voter).await {
+                    warn!("could not add final vote for orphan {block_id:?}: {e}");
+                }
+            }
+        }
+
+        let expiry = state.config.read().await.consensus.orphan_expiry;
+        for expired in self.orphans.expire(timestamp(), expiry) {
+            let status = BlockStatus::rejected("previous block did not arrive in time");
+            if let Err(e) = set_status(&state, expired, status).await {
+                warn!("could not update status of orphan {expired:?}: {e}");
+            }
+        }
+    }
+
+    /// Casts our final vote for a block that reached quorum, if this node is a prime delegate
+    ///
+    /// Only one block at each height gets our final vote.
+    async fn send_final_vote(&mut self, state: &ChampStateArc, block_id: &BlockID) -> Result<()> {
+        if self.candidates(block_id).iter().any(|candidate| self.sent_votes.contains(candidate)) {
+            return Ok(());
+        }
+        self.sent_votes.insert(*block_id);
+
+        let (account, power) = match self.own_vote(state).await? {
+            Some(vote) => vote,
+            None => return Ok(()),
+        };
+        self.final_votes.add_vote(*block_id, account, power);
+
+        let raw_block = match self.block_queue.iter().find(|item| &item.block.get_id() == block_id) {
+            Some(item) => item.raw_block.clone(),
+            None => return Ok(()),
+        };
+
+        let final_vote = request_body::FinalVote {
+            block: Some(raw_block),
+            vote: power,
+        };
+        if let Err(e) = state.p2p_client.broadcast(RequestBodyData::FinalVote(final_vote)) {
+            tracing::error!("could not send final vote: {e}");
+        }
+
+        Ok(())
+    }
+
+    async fn check_final_quorum(&mut self, state: &ChampStateArc, block_id: &BlockID) -> Result<()> {
+        let quorum = self.final_votes.quorum(block_id, state.network_power.online_power());
+        debug!("block {block_id:?} has a final quorum of {quorum}");
+
+        if quorum >= voting_power::VOTE_PERCENTAGE_NEEDED {
+            self.confirm_block(state, block_id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Our own account and its power in the current epoch, if this node runs as a prime delegate and its account was elected
+    async fn own_vote(&self, state: &ChampStateArc) -> Result<Option<(AccountID, u64)>> {
+        let account = {
+            let config = state.config.read().await;
+            match (&config.consensus.mode, &config.consensus.primary_wallet) {
+                (Mode::Prime, Some(primary_wallet)) => parse_account_address_string(primary_wallet)?,
+                _ => return Ok(None),
+            }
+        };
+
+        if !state.prime_delegates.contains(&account) {
+            return Ok(None);
+        }
+
+        let power = epoch_power::get_vote_power(state, &account)?;
+        Ok(Some((account, power)))
+    }
+
+    /// Publishes how close a pending block is to being confirmed
+    async fn update_status(&self, state: &ChampStateArc, block_id: &BlockID) -> Result<()> {
+        let online_power = state.network_power.online_power();
+        let status = BlockStatus::Voting {
+            quorum: self.votes.quorum(block_id, online_power),
+            final_quorum: self.final_votes.quorum(block_id, online_power),
+        };
+        set_status(state, *block_id, status).await
+    }
+
+    /// Removes the votes of accounts that signed votes for competing blocks, so they no longer count towards quorum
+    fn remove_excluded(&mut self, state: &ChampStateArc) {
+        for voter in state.equivocations.excluded(timestamp()) {
+            self.votes.remove_voter(&voter);
+            self.final_votes.remove_voter(&voter);
+        }
+    }
+
+    fn is_pending(&self, block_id: &BlockID) -> bool {
+        self.block_queue.iter().any(|item| &item.block.get_id() == block_id)
+    }
+
+    /// All pending blocks at the height of a pending block, including itself
+    fn candidates(&self, block_id: &BlockID) -> Vec<BlockID> {
+        match self.block_queue.iter().find(|item| &item.block.get_id() == block_id) {
+            Some(item) => self.forks.candidates(&item.account, item.block.data.height),
+            None => vec![],
+        }
+    }
+
+    /// Removes a block from the queue and forgets its votes
+    fn remove_pending(&mut self, block_id: &BlockID) -> Option<QueueItem> {
+        let position = self.block_queue.iter().position(|item| &item.block.get_id() == block_id)?;
+        self.votes.remove(block_id);
+        self.final_votes.remove(block_id);
+        self.sent_votes.remove(block_id);
+        self.block_queue.remove(position)
+    }
+
+    /// Removes a block from the queue and appends it to the chain
+    ///
+    /// Competing blocks at the same height are rejected. If the chain already has another block at this height,
+    /// that block and all blocks after it are rolled back, together with pending blocks that build on them. Chains of
+    /// other accounts are rolled back from the block that claimed a send in one of them.
+    async fn confirm_block(&mut self, state: &ChampStateArc, block_id: &BlockID) -> Result<()> {
+        let item = match self.remove_pending(block_id) {
+            Some(item) => item,
+            None => return Ok(()),
+        };
+        let height = item.block.data.height;
+
+        let mut invalid: HashSet<BlockID> = HashSet::new();
+        for candidate in self.forks.remove(&item.account, height) {
+            if &candidate != block_id && self.remove_pending(&candidate).is_some() {
+                set_status(state, candidate, BlockStatus::rejected("a competing block was confirmed")).await?;
+                invalid.insert(candidate);
+            }
+        }
+
+        info!("block {block_id:?} reached final quorum");
+        let mut rolled_back = vec![];
+        let mut rolled_back_claims = vec![];
+        {
+            let mut db = state.db.lock().await;
+            let replaced = db.get_block_by_height(item.account, &height).await?;
+            if replaced.map_or(false, |replaced| &replaced.get_id() != block_id) {
+                // claims of a rolled back send would spend it a second time once the send is made again,
+                // so the chains that claimed it are rolled back from the claim as well
+                let mut chains = vec![(item.account, height)];
+                while let Some((account, from)) = chains.pop() {
+                    let is_claim = (account, from) != (item.account, height);
+                    for block in db.remove_blocks_from(account, from).await? {
+                        warn!("rolled back block {:?} at height {}", block.get_id(), block.data.height);
+                        state.voting_power_cache.invalidate(&block);
+                        chains.extend(claiming_chains(&**db, &block).await?);
+                        if is_claim {
+                            rolled_back_claims.push(block.get_id());
+                        } else {
+                            rolled_back.push(block.get_id());
+                        }
+                    }
+                }
+            }
+            db.add_block(item.block.clone()).await?;
+        }
+        state.voting_power_cache.invalidate(&item.block);
+        set_status(state, *block_id, BlockStatus::Confirmed).await?;
+        self.released.extend(self.orphans.take(block_id));
+
+        for rolled_back in rolled_back {
+            set_status(state, rolled_back, BlockStatus::rejected("replaced by a competing block")).await?;
+            invalid.insert(rolled_back);
+        }
+        for rolled_back in rolled_back_claims {
+            set_status(state, rolled_back, BlockStatus::rejected("claims a send that was rolled back")).await?;
+            invalid.insert(rolled_back);
+        }
 
-        let all_votes = self.block_votes.get(&block_id);
+        // pending blocks that build on a rejected or rolled back block can never be added
+        loop {
+            let descendants: Vec<(BlockID, AccountID, u64)> = self
+                .block_queue
+                .iter()
+                .filter(|pending| invalid.iter().any(|id| pending.block.data.previous == id.to_vec()))
+                .map(|pending| (pending.block.get_id(), pending.account, pending.block.data.height))
+                .collect();
+            if descendants.is_empty() {
+                break;
+            }
 
-        let total_votes = all_votes.unwrap().iter().sum::<u64>() as f64;
+            for (descendant, account, height) in descendants {
+                self.remove_pending(&descendant);
+                self.forks.discard(&account, height, &descendant);
+                set_status(state, descendant, BlockStatus::rejected("builds on a rejected block")).await?;
+                invalid.insert(descendant);
+            }
+        }
 
-        let total_network_power = self.state.as_ref().unwrap().blockpool_client.get_total_network_power();
-        total_votes / total_network_power
+        // as are orphans waiting for one of them
+        let mut parents: Vec<BlockID> = invalid.iter().copied().collect();
+        while let Some(parent) = parents.pop() {
+            for orphan in self.orphans.take(&parent) {
+                set_status(state, orphan.block_id, BlockStatus::rejected("builds on a rejected block")).await?;
+                parents.push(orphan.block_id);
+                invalid.insert(orphan.block_id);
+            }
+        }
+
+        self.rejected.extend(invalid);
+        Ok(())
     }
+}
+
+/// The accounts that claimed a send of a block, with the height of the block that claimed it
+async fn claiming_chains(db: &dyn Database, block: &SignedBlock) -> Result<Vec<(AccountID, u64)>> {
+    let block_id = block.get_id();
+    let mut chains = vec![];
+    for (index, tx) in block.data.transactions.iter().enumerate() {
+        if !matches!(tx.data, Some(transaction::Data::TxSend(_))) {
+            continue;
+        }
+
+        let send_id = Transaction::get_id(block_id, index as u32).map_err(|_| anyhow!("invalid transaction id"))?;
+        if let Some(claim_id) = db.get_send_recipient(send_id).await? {
+            let (_, claim_block_id, account) = db.get_transaction_by_id(claim_id).await?;
+            chains.push((account, db.get_block_by_id(claim_block_id).await?.data.height));
+        }
+    }
+    Ok(chains)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blockpool::{get_status, BlockStatus};
+    use crate::fixtures::{
+        account, child_block, genesis_block, mock_network, raw_block, with_transactions, MockNode, DELEGATE_BALANCE,
+    };
+    use crate::p2p::types::{Command, RequestBodyData};
+    use anyhow::Result;
+    use crypto::signatures::ed25519::generate_private_key;
+    use encoding::account::generate_account_address;
+    use pog_proto::api::transaction::{Data, TxClaim, TxSend};
+    use pog_proto::api::{SignedBlock, Transaction};
+    use pog_proto::rpc::node_admin::Mode;
+
+    // two of the delegates stay below 60% of the network power, three reach it
+    const NETWORK_POWER: u64 = 100_000_000;
 
-    fn save_vote(&mut self, vote: u64, block_id: &[u8; 32]) {
-        let all_votes = match self.block_votes.get(block_id) {
-            Some(v) => {
-                v.to_owned().push(vote);
-                v.to_owned()
+    async fn network(modes: &[Mode]) -> Result<Vec<MockNode>> {
+        mock_network(modes, 0, NETWORK_POWER).await
+    }
+
+    /// Delivers the requests of each node to all other nodes until no node has anything left to send
+    async fn relay(nodes: &mut [MockNode]) -> Result<()> {
+        loop {
+            let mut requests = vec![];
+            for node in nodes.iter_mut() {
+                while let Ok(Command::Broadcast {
+                    request,
+                }) = node.p2p_rx.try_recv()
+                {
+                    requests.push((node.account, request));
+                }
+            }
+
+            if requests.is_empty() {
+                return Ok(());
             }
-            None => vec![vote],
+
+            for (sender, request) in requests {
+                for node in nodes.iter().filter(|node| node.account != sender) {
+                    let client = &node.state.blockpool_client;
+                    match request.clone() {
+                        RequestBodyData::VoteProposal(proposal) => {
+                            let voter = match proposal.vote {
+                                0 => None,
+                                _ => Some(sender),
+                            };
+                            client.process_vote_proposal(proposal.block.expect("block should be set"), voter).await?
+                        }
+                        RequestBodyData::FinalVote(vote) => {
+                            client.process_final_vote(vote.block.expect("block should be set"), sender).await?
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_voting_rounds() -> Result<()> {
+        let mut nodes = network(&[Mode::Prime, Mode::Prime, Mode::Prime, Mode::Validating]).await?;
+        let block = genesis_block(&generate_private_key()?, 100);
+
+        nodes[3].state.blockpool_client.submit_block(raw_block(&block)).await?;
+        relay(&mut nodes).await?;
+
+        for node in &nodes {
+            node.state.db.lock().await.get_block_by_id(block.get_id()).await.expect("block should be confirmed");
+            assert_eq!(0, node.state.blockpool_client.get_queue_size().await?);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_voting_without_quorum() -> Result<()> {
+        let mut nodes = network(&[Mode::Prime, Mode::Prime, Mode::Validating]).await?;
+        let block = genesis_block(&generate_private_key()?, 100);
+
+        nodes[0].state.blockpool_client.submit_block(raw_block(&block)).await?;
+        relay(&mut nodes).await?;
+
+        for node in &nodes {
+            node.state.db.lock().await.get_block_by_id(block.get_id()).await.expect_err("block should be pending");
+            assert_eq!(1, node.state.blockpool_client.get_queue_size().await?);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_final_vote() -> Result<()> {
+        let nodes = network(&[Mode::Prime, Mode::Prime, Mode::Prime]).await?;
+        let block = raw_block(&genesis_block(&generate_private_key()?, 100));
+        let client = &nodes[0].state.blockpool_client;
+
+        client.submit_block(block.clone()).await?;
+        client.process_final_vote(block.clone(), nodes[1].account).await?;
+        client.process_final_vote(block, nodes[1].account).await.expect_err("second vote should be rejected");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_votes_of_non_delegates() -> Result<()> {
+        let mut nodes = network(&[Mode::Prime, Mode::Prime, Mode::Validating]).await?;
+        let block = raw_block(&genesis_block(&generate_private_key()?, 100));
+        let not_elected = nodes[0].account;
+        for node in &nodes {
+            node.state.prime_delegates.set(0, vec![(nodes[1].account, DELEGATE_BALANCE)]);
+        }
+
+        // a node that runs as a prime delegate does not vote unless its account was elected
+        nodes[0].state.blockpool_client.submit_block(block.clone()).await?;
+        match nodes[0].p2p_rx.try_recv() {
+            Ok(Command::Broadcast {
+                request: RequestBodyData::VoteProposal(proposal),
+            }) => assert_eq!(0, proposal.vote),
+            other => panic!("expected a vote proposal, got {other:?}"),
+        }
+
+        let client = &nodes[2].state.blockpool_client;
+        client.process_vote_proposal(block.clone(), Some(not_elected)).await?;
+        client.process_final_vote(block, not_elected).await.expect_err("final vote should be rejected");
+        assert_eq!(1, client.get_queue_size().await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fork_choice() -> Result<()> {
+        let mut nodes = network(&[Mode::Prime, Mode::Prime, Mode::Prime]).await?;
+        let private_key = generate_private_key()?;
+        let (first, second) = (genesis_block(&private_key, 100), genesis_block(&private_key, 200));
+
+        // the first node's block reaches the third node first and gets two of the three votes
+        nodes[0].state.blockpool_client.submit_block(raw_block(&first)).await?;
+        nodes[1].state.blockpool_client.submit_block(raw_block(&second)).await?;
+        relay(&mut nodes).await?;
+
+        let account_id = account(&private_key)?;
+        for node in &nodes {
+            let db = node.state.db.lock().await;
+            assert_eq!(Some(first.clone()), db.get_block_by_height(account_id, &0).await?);
+            db.get_block_by_id(second.get_id()).await.expect_err("fork should be rejected");
+            drop(db);
+            assert_eq!(0, node.state.blockpool_client.get_queue_size().await?);
+            assert_eq!(
+                Some(BlockStatus::rejected("a competing block was confirmed")),
+                get_status(&node.state, second.get_id()).await?
+            );
+        }
+
+        // late votes for the rejected block are ignored
+        let client = &nodes[0].state.blockpool_client;
+        client.process_vote_proposal(raw_block(&second), Some(nodes[1].account)).await?;
+        assert_eq!(0, client.get_queue_size().await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fork_replaces_confirmed_block() -> Result<()> {
+        let mut nodes = network(&[Mode::Validating, Mode::Prime, Mode::Prime, Mode::Prime]).await?;
+        let private_key = generate_private_key()?;
+        let account_id = account(&private_key)?;
+
+        // the first node added a block the rest of the network never saw, and a block after it
+        let (stale, winner) = (genesis_block(&private_key, 100), genesis_block(&private_key, 200));
+        let descendant = child_block(&private_key, &stale);
+        {
+            let mut db = nodes[0].state.db.lock().await;
+            db.add_block(stale.clone()).await?;
+            db.add_block(descendant.clone()).await?;
+        }
+
+        nodes[1].state.blockpool_client.submit_block(raw_block(&winner)).await?;
+        relay(&mut nodes).await?;
+
+        for node in &nodes {
+            let db = node.state.db.lock().await;
+            assert_eq!(winner, db.get_latest_block_by_account(account_id).await?);
+            db.get_block_by_id(stale.get_id()).await.expect_err("block should be rolled back");
+            db.get_block_by_id(descendant.get_id()).await.expect_err("descendant should be rolled back");
+            assert_eq!(0, node.state.blockpool_client.get_queue_size().await?);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rollback_of_claimed_send() -> Result<()> {
+        let mut nodes = network(&[Mode::Validating, Mode::Prime, Mode::Prime, Mode::Prime]).await?;
+        let (sender_key, receiver_key) = (generate_private_key()?, generate_private_key()?);
+        let receiver = account(&receiver_key)?;
+
+        // the first node added a send the rest of the network never saw, and the receiver already claimed it
+        let send = Transaction {
+            data: Some(Data::TxSend(TxSend {
+                receiver: receiver.to_vec(),
+                amount: 50,
+                data: vec![],
+            })),
+        };
+        let stale = with_transactions(&sender_key, &genesis_block(&sender_key, 50), vec![send]);
+        let send_id = Transaction::get_id(stale.get_id(), 0).expect("should create transaction id");
+        let claim = Transaction {
+            data: Some(Data::TxClaim(TxClaim {
+                send_transaction_id: send_id.to_vec(),
+            })),
         };
+        let claiming = with_transactions(&receiver_key, &genesis_block(&receiver_key, 50), vec![claim]);
+        {
+            let mut db = nodes[0].state.db.lock().await;
+            db.add_block(stale.clone()).await?;
+            db.add_block(claiming.clone()).await?;
+        }
+
+        let winner = genesis_block(&sender_key, 100);
+        nodes[1].state.blockpool_client.submit_block(raw_block(&winner)).await?;
+        relay(&mut nodes).await?;
+
+        let state = nodes[0].state.clone();
+        {
+            let db = state.db.lock().await;
+            db.get_block_by_id(stale.get_id()).await.expect_err("send should be rolled back");
+            db.get_block_by_id(claiming.get_id()).await.expect_err("claim should be rolled back");
+            assert_eq!(None, db.get_send_recipient(send_id).await?);
+        }
+        assert!(matches!(get_status(&state, claiming.get_id()).await?, Some(BlockStatus::Rejected { .. })));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_excluded_voter() -> Result<()> {
+        let mut nodes = network(&[Mode::Prime, Mode::Prime, Mode::Prime]).await?;
+        let block = raw_block(&genesis_block(&generate_private_key()?, 100));
+        let client = &nodes[0].state.blockpool_client;
+
+        client.process_final_vote(block.clone(), nodes[1].account).await?;
+        nodes[0].state.equivocations.exclude(nodes[2].account, u64::MAX);
+        client.process_final_vote(block.clone(), nodes[2].account).await.expect_err("vote should be rejected");
+
+        // once the second voter is excluded as well, the votes of all three nodes are not enough for a final vote
+        nodes[0].state.equivocations.exclude(nodes[1].account, u64::MAX);
+        client.process_vote_proposal(block.clone(), Some(nodes[1].account)).await?;
+        client.process_vote_proposal(block, Some(nodes[2].account)).await?;
+        assert_eq!(1, client.get_queue_size().await?);
 
-        let result = &self.block_votes.insert(*block_id, all_votes);
-        if result.is_none() {
-            panic!("something went wrong")
+        while let Ok(Command::Broadcast {
+            request,
+        }) = nodes[0].p2p_rx.try_recv()
+        {
+            assert!(!matches!(request, RequestBodyData::FinalVote(_)), "no final vote should be sent");
         }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_block_status() -> Result<()> {
+        let mut nodes = network(&[Mode::Prime, Mode::Prime, Mode::Prime, Mode::Validating]).await?;
+        let block = genesis_block(&generate_private_key()?, 100);
+        let state = nodes[3].state.clone();
+        let client = &state.blockpool_client;
+
+        client.submit_block(raw_block(&block)).await?;
+        let status = get_status(&state, block.get_id()).await?;
+        assert_eq!(
+            Some(BlockStatus::Voting {
+                quorum: 0.0,
+                final_quorum: 0.0
+            }),
+            status
+        );
+        assert_eq!(vec![block.clone()], client.get_pending_blocks().await?);
+
+        // the vote of one of three prime delegates
+        client.process_vote_proposal(raw_block(&block), Some(nodes[0].account)).await?;
+        match get_status(&state, block.get_id()).await? {
+            Some(BlockStatus::Voting {
+                quorum,
+                final_quorum,
+            }) => {
+                assert!(quorum > 0.0 && quorum < 1.0, "{quorum}");
+                assert_eq!(0.0, final_quorum);
+            }
+            other => panic!("expected a pending block, got {other:?}"),
+        }
+
+        relay(&mut nodes).await?;
+        assert_eq!(Some(BlockStatus::Confirmed), get_status(&state, block.get_id()).await?);
+        assert!(client.get_pending_blocks().await?.is_empty());
+
+        // blocks with an invalid signature never reach the queue
+        let mut invalid = raw_block(&genesis_block(&generate_private_key()?, 100));
+        invalid.header.as_mut().expect("header should be set").signature = vec![0; 64];
+        let invalid_id = SignedBlock::try_from(invalid.clone())?.get_id();
+        client.submit_block(invalid).await.expect_err("block should be invalid");
+        assert!(matches!(get_status(&state, invalid_id).await?, Some(BlockStatus::Rejected { .. })));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_orphan() -> Result<()> {
+        let mut nodes = network(&[Mode::Prime, Mode::Prime, Mode::Prime, Mode::Validating]).await?;
+        let private_key = generate_private_key()?;
+        let parent = genesis_block(&private_key, 100);
+        let child = child_block(&private_key, &parent);
+        let state = nodes[3].state.clone();
+
+        // the child arrives first, together with a vote for it
+        state.blockpool_client.submit_block(raw_block(&child)).await?;
+        state.blockpool_client.process_vote_proposal(raw_block(&child), Some(nodes[0].account)).await?;
+        assert_eq!(Some(BlockStatus::Orphaned), get_status(&state, child.get_id()).await?);
+        assert_eq!(0, state.blockpool_client.get_queue_size().await?);
+        relay(&mut nodes).await?;
+
+        state.blockpool_client.submit_block(raw_block(&parent)).await?;
+        relay(&mut nodes).await?;
+
+        for node in &nodes {
+            let db = node.state.db.lock().await;
+            assert_eq!(
+                child,
+                db.get_latest_block_by_account(generate_account_address(child.header.public_key.clone())?).await?
+            );
+            drop(db);
+            assert_eq!(Some(BlockStatus::Confirmed), get_status(&node.state, child.get_id()).await?);
+            assert_eq!(0, node.state.blockpool_client.get_queue_size().await?);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_synced_block() -> Result<()> {
+        let nodes = network(&[Mode::Validating]).await?;
+        let state = nodes[0].state.clone();
+        let private_key = generate_private_key()?;
+        let (parent, competing) = (genesis_block(&private_key, 100), genesis_block(&private_key, 200));
+        let child = child_block(&private_key, &parent);
+
+        // a synced block does not replace a block that is being voted on
+        state.blockpool_client.submit_block(raw_block(&competing)).await?;
+        state.blockpool_client.add_synced_block(parent.clone()).await.expect_err("height is being voted on");
+
+        let nodes = network(&[Mode::Validating]).await?;
+        let state = nodes[0].state.clone();
+        state.blockpool_client.submit_block(raw_block(&child)).await?;
+        assert_eq!(Some(BlockStatus::Orphaned), get_status(&state, child.get_id()).await?);
+
+        // orphans waiting for the synced block are voted on
+        state.blockpool_client.add_synced_block(parent.clone()).await?;
+        assert_eq!(Some(BlockStatus::Confirmed), get_status(&state, parent.get_id()).await?);
+        assert_eq!(1, state.blockpool_client.get_queue_size().await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_orphan_limit() -> Result<()> {
+        let nodes = network(&[Mode::Prime]).await?;
+        let state = &nodes[0].state;
+        state.config.write().await.consensus.max_orphans = 1;
+
+        let private_key = generate_private_key()?;
+        let parent = genesis_block(&private_key, 100);
+        let first = child_block(&private_key, &parent);
+        let second = child_block(&private_key, &first);
+        state.blockpool_client.submit_block(raw_block(&second)).await?;
+        state.blockpool_client.submit_block(raw_block(&first)).await?;
+
+        // the pool only holds one orphan, so the oldest one is dropped
+        assert!(matches!(get_status(state, second.get_id()).await?, Some(BlockStatus::Rejected { .. })));
+        assert_eq!(Some(BlockStatus::Orphaned), get_status(state, first.get_id()).await?);
+
+        Ok(())
     }
 }
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use pog_proto::api::{AccountID, RawBlock, SignedBlock};
 use tokio::sync::oneshot;
 
 type Responder<T> = oneshot::Sender<Result<T>>;
@@ -6,12 +7,23 @@ type Responder<T> = oneshot::Sender<Result<T>>;
 #[derive(Debug)]
 pub enum Command {
     // input
+    SubmitBlock {
+        block: RawBlock,
+        resp: Responder<()>,
+    },
     ProcessVoteProposal {
-        block: pog_proto::api::SignedBlock,
+        block: RawBlock,
+        voter: Option<AccountID>,
         resp: Responder<()>,
     },
     ProcessFinalVote {
-        block: pog_proto::api::SignedBlock,
+        block: RawBlock,
+        voter: AccountID,
+        resp: Responder<()>,
+    },
+    /// a block fetched from a peer by syncing
+    AddSyncedBlock {
+        block: SignedBlock,
         resp: Responder<()>,
     },
 
@@ -19,4 +31,7 @@ pub enum Command {
     GetQueueSize {
         resp: Responder<u64>,
     },
+    GetPendingBlocks {
+        resp: Responder<Vec<SignedBlock>>,
+    },
 }
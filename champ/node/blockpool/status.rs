@@ -0,0 +1,119 @@
+use anyhow::Result;
+use pog_proto::api::BlockID;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::state::ChampStateArc;
+
+/// Where a block is in its lifecycle, as seen by this node
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum BlockStatus {
+    /// submitted or proposed, but not looked at yet
+    Received,
+    Validating,
+    /// waiting for its previous block
+    Orphaned,
+    /// share of the online voting power that voted for the block and that cast a final vote for it
+    Voting {
+        quorum: f64,
+        final_quorum: f64,
+    },
+    Confirmed,
+    Rejected {
+        reason: String,
+    },
+}
+
+impl BlockStatus {
+    pub fn rejected(reason: impl ToString) -> Self {
+        BlockStatus::Rejected {
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Confirmed and rejected blocks do not change their status anymore, unless they are proposed again
+    pub fn is_final(&self) -> bool {
+        matches!(self, BlockStatus::Confirmed | BlockStatus::Rejected { .. })
+    }
+}
+
+/// Status of the blocks that are not confirmed or rejected yet
+///
+/// This lives outside of the blockpool, so the status of a block can be looked up while it is being validated.
+#[derive(Debug, Default)]
+pub struct BlockStatuses {
+    pending: RwLock<HashMap<BlockID, BlockStatus>>,
+}
+
+impl BlockStatuses {
+    pub fn get(&self, block_id: &BlockID) -> Option<BlockStatus> {
+        self.pending.read().expect("block status lock is poisoned").get(block_id).cloned()
+    }
+
+    fn set(&self, block_id: BlockID, status: BlockStatus) {
+        self.pending.write().expect("block status lock is poisoned").insert(block_id, status);
+    }
+
+    fn remove(&self, block_id: &BlockID) {
+        self.pending.write().expect("block status lock is poisoned").remove(block_id);
+    }
+}
+
+/// Updates the status of a block. Confirmed and rejected blocks are stored in the database.
+pub async fn set_status(state: &ChampStateArc, block_id: BlockID, status: BlockStatus) -> Result<()> {
+    if !status.is_final() {
+        state.block_statuses.set(block_id, status);
+        return Ok(());
+    }
+
+    state.db.lock().await.set_block_status(block_id, serde_json::to_vec(&status)?).await?;
+    state.block_statuses.remove(&block_id);
+    Ok(())
+}
+
+/// Looks up the status of a block
+///
+/// Blocks in the database that have no stored status were added without a vote, e.g. when the node synced them.
+pub async fn get_status(state: &ChampStateArc, block_id: BlockID) -> Result<Option<BlockStatus>> {
+    if let Some(status) = state.block_statuses.get(&block_id) {
+        return Ok(Some(status));
+    }
+
+    let db = state.db.lock().await;
+    if let Some(status) = db.get_block_status(block_id).await? {
+        return Ok(Some(serde_json::from_slice(&status)?));
+    }
+
+    match db.get_block_by_id(block_id).await {
+        Ok(_) => Ok(Some(BlockStatus::Confirmed)),
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ChampState;
+
+    #[tokio::test]
+    async fn test_status() -> Result<()> {
+        let state = ChampState::mock().await;
+        let block_id = [1; 32];
+        assert_eq!(None, get_status(&state, block_id).await?);
+
+        set_status(&state, block_id, BlockStatus::Validating).await?;
+        assert_eq!(Some(BlockStatus::Validating), get_status(&state, block_id).await?);
+        assert_eq!(None, state.db.lock().await.get_block_status(block_id).await?);
+
+        let rejected = BlockStatus::rejected("invalid block height");
+        set_status(&state, block_id, rejected.clone()).await?;
+        assert_eq!(None, state.block_statuses.get(&block_id));
+        assert_eq!(Some(rejected), get_status(&state, block_id).await?);
+
+        // proposed again
+        set_status(&state, block_id, BlockStatus::Received).await?;
+        assert_eq!(Some(BlockStatus::Received), get_status(&state, block_id).await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use pog_proto::api::{AccountID, BlockID};
+use std::collections::HashMap;
+
+/// Voting power each account voted with, grouped by block
+#[derive(Debug, Default)]
+pub struct Votes {
+    votes: HashMap<BlockID, HashMap<AccountID, u64>>,
+}
+
+impl Votes {
+    /// Records the vote of an account. A later vote of the same account replaces the earlier one.
+    pub fn add_vote(&mut self, block_id: BlockID, voter: AccountID, power: u64) {
+        self.votes.entry(block_id).or_default().insert(voter, power);
+    }
+
+    pub fn has_voted(&self, block_id: &BlockID, voter: &AccountID) -> bool {
+        self.votes.get(block_id).map_or(false, |votes| votes.contains_key(voter))
+    }
+
+    /// Sum of the voting power of all votes for a block
+    pub fn power(&self, block_id: &BlockID) -> u64 {
+        self.votes.get(block_id).map_or(0, |votes| votes.values().sum())
+    }
+
+    /// Share of the online voting power that voted for a block
+    pub fn quorum(&self, block_id: &BlockID, online_power: f64) -> f64 {
+        if online_power <= 0.0 {
+            return 0.0;
+        }
+        self.power(block_id) as f64 / online_power
+    }
+
+    pub fn remove(&mut self, block_id: &BlockID) {
+        self.votes.remove(block_id);
+    }
+
+    /// Removes all votes of an account
+    pub fn remove_voter(&mut self, voter: &AccountID) {
+        for votes in self.votes.values_mut() {
+            votes.remove(voter);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_votes() {
+        let mut votes = Votes::default();
+        let block_id = [1; 32];
+
+        assert_eq!(0, votes.power(&block_id));
+        assert_eq!(0.0, votes.quorum(&block_id, 1000.0));
+
+        votes.add_vote(block_id, [1; 24], 300);
+        votes.add_vote(block_id, [2; 24], 200);
+        votes.add_vote([2; 32], [3; 24], 1000);
+        assert_eq!(500, votes.power(&block_id));
+        assert!(votes.has_voted(&block_id, &[1; 24]));
+        assert!(!votes.has_voted(&block_id, &[3; 24]));
+        assert_eq!(0.5, votes.quorum(&block_id, 1000.0));
+
+        // voting again replaces the earlier vote
+        votes.add_vote(block_id, [2; 24], 400);
+        assert_eq!(700, votes.power(&block_id));
+        assert_eq!(0.0, votes.quorum(&block_id, 0.0));
+
+        votes.remove(&block_id);
+        assert_eq!(0, votes.power(&block_id));
+        assert_eq!(1000, votes.power(&[2; 32]));
+
+        votes.add_vote(block_id, [3; 24], 100);
+        votes.remove_voter(&[3; 24]);
+        assert_eq!(0, votes.power(&block_id));
+        assert_eq!(0, votes.power(&[2; 32]));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::config::read_file;
+use crate::consensus::parameters::ConsensusParameters;
+use anyhow::{anyhow, Context, Result};
+use encoding::account::{parse_account_address_string, validate_account_address};
+use path_absolutize::Absolutize;
+use pog_proto::api::AccountID;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const DEV_CHAIN_SPEC: &str = include_str!("chains/dev.toml");
+
+fn default_epoch_length() -> u64 {
+    60 * 60
+}
+
+/// Parameters of a chain that have to be the same on every node of the network
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChainSpec {
+    pub chain_id: String,
+
+    /// Accounts that start with a balance. If empty, new accounts can open their chain with any balance.
+    #[serde(default)]
+    pub genesis: Vec<GenesisAccount>,
+
+    #[serde(default)]
+    pub limits: ChainLimits,
+
+    #[serde(default)]
+    pub consensus: ConsensusParameters,
+
+    /// length of an epoch in seconds
+    #[serde(default = "default_epoch_length")]
+    pub epoch_length: u64,
+
+    /// Multiaddrs of peers a node connects to in addition to `consensus.initial_peers`
+    #[serde(default)]
+    pub bootstrap_peers: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GenesisAccount {
+    pub address: String,
+    pub balance: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ChainLimits {
+    /// maximum number of transactions in a block
+    pub max_transactions: usize,
+    /// maximum size of the encoded block data in bytes
+    pub max_block_size: usize,
+}
+
+impl Default for ChainLimits {
+    fn default() -> Self {
+        Self {
+            max_transactions: 255,
+            max_block_size: 65536,
+        }
+    }
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        ChainSpec::from_toml(DEV_CHAIN_SPEC).expect("built-in dev chain spec is invalid")
+    }
+}
+
+impl ChainSpec {
+    /// Loads the chain spec named by `chain`.
+    ///
+    /// `dev` is built into the node, every other chain is read from a TOML file.
+    /// Relative paths are resolved from `base_path` (the directory of the config file).
+    pub fn load(chain: &str, base_path: &Path) -> Result<Self> {
+        if chain == "dev" {
+            return ChainSpec::from_toml(DEV_CHAIN_SPEC);
+        }
+
+        let path = chain.parse::<PathBuf>()?;
+        let path = path.absolutize_from(base_path)?.to_path_buf();
+        let spec = read_file(path.clone()).with_context(|| format!("failed to read chain spec {path:?}"))?;
+        ChainSpec::from_toml(&spec).with_context(|| format!("failed to parse chain spec {path:?}"))
+    }
+
+    pub fn from_toml(spec: &str) -> Result<Self> {
+        let spec = toml::from_str::<ChainSpec>(spec)?;
+
+        if spec.epoch_length == 0 {
+            return Err(anyhow!("epoch length has to be greater than 0"));
+        }
+
+        for account in &spec.genesis {
+            parse_account_address_string(&account.address)
+                .ok()
+                .filter(|address| validate_account_address(address).is_ok())
+                .ok_or_else(|| anyhow!("invalid genesis account address: {}", account.address))?;
+        }
+
+        Ok(spec)
+    }
+
+    /// Returns the epoch a timestamp belongs to
+    pub fn epoch(&self, timestamp: u64) -> u64 {
+        timestamp / self.epoch_length
+    }
+
+    /// Returns the balance an account starts with.
+    ///
+    /// `None` means there are no genesis accounts and any starting balance is accepted.
+    pub fn genesis_balance(&self, account_id: &AccountID) -> Option<u64> {
+        if self.genesis.is_empty() {
+            return None;
+        }
+
+        let balance = self
+            .genesis
+            .iter()
+            .find(|account| parse_account_address_string(&account.address).ok().as_ref() == Some(account_id))
+            .map_or(0, |account| account.balance);
+
+        Some(balance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus::parameters::GraphParameters;
+    use encoding::{account::generate_account_address, zbase32::ToZbase};
+
+    #[test]
+    fn test_dev_chain_spec() {
+        let spec = ChainSpec::default();
+        assert_eq!("dev", spec.chain_id);
+        assert_eq!(ChainLimits::default(), spec.limits);
+        assert_eq!(ConsensusParameters::default(), spec.consensus);
+        assert_eq!(default_epoch_length(), spec.epoch_length);
+        assert_eq!(None, spec.genesis_balance(&[0; 24]));
+    }
+
+    #[test]
+    fn test_genesis_balance() {
+        let account_id = generate_account_address(b"key".to_vec()).unwrap();
+        let spec = ChainSpec::from_toml(&format!(
+            r#"
+            chain_id = "test"
+
+            [[genesis]]
+            address = "pog-{}"
+            balance = 1000
+
+            [limits]
+            max_transactions = 10
+            "#,
+            account_id.encode_zbase().unwrap()
+        ))
+        .unwrap();
+
+        assert_eq!(Some(1000), spec.genesis_balance(&account_id));
+        assert_eq!(Some(0), spec.genesis_balance(&generate_account_address(b"other".to_vec()).unwrap()));
+        assert_eq!(10, spec.limits.max_transactions);
+        assert_eq!(default_epoch_length(), spec.epoch_length);
+        assert_eq!(2, spec.epoch(2 * 60 * 60 + 1));
+        assert_eq!(ChainLimits::default().max_block_size, spec.limits.max_block_size);
+    }
+
+    #[test]
+    fn test_consensus_parameters() {
+        let spec = ChainSpec::from_toml(
+            r#"
+            chain_id = "test"
+
+            [consensus]
+            block_weight = 2.0
+
+            [consensus.graphs]
+            plateau_size = 100.0
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(2.0, spec.consensus.block_weight);
+        assert_eq!(100.0, spec.consensus.graphs.plateau_size);
+        assert_eq!(ConsensusParameters::default().balance_weight, spec.consensus.balance_weight);
+        assert_eq!(GraphParameters::default().tx_curve_max, spec.consensus.graphs.tx_curve_max);
+    }
+
+    #[test]
+    fn test_invalid_genesis_account() {
+        let spec = ChainSpec::from_toml(
+            r#"
+            chain_id = "test"
+
+            [[genesis]]
+            address = "pog-invalid"
+            balance = 1000
+            "#,
+        );
+        assert!(spec.is_err());
+    }
+}
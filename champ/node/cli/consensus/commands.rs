@@ -0,0 +1,81 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    cli::error::CLIError,
+    config::read_file,
+    consensus::simulation::{self, Scenario, SimulatedPower},
+    state::ChampStateArc,
+};
+
+use clap::ArgMatches;
+use encoding::{account::parse_account_address_string, zbase32::ToZbase};
+use tracing::debug;
+
+pub async fn run(matches: &ArgMatches, state: &ChampStateArc) -> Result<(), CLIError> {
+    debug!("check cli arguments");
+    if let Some(matches) = matches.subcommand_matches("simulate") {
+        debug!("simulating voting power");
+
+        let account = match matches.value_of("account") {
+            Some(account) => Some(
+                parse_account_address_string(account)
+                    .map_err(|e| CLIError::Unknown(format!("invalid account: {e}")))?,
+            ),
+            None => None,
+        };
+
+        let timestamp = match matches.value_of("timestamp") {
+            Some(timestamp) => timestamp.parse().map_err(|e| CLIError::Unknown(format!("invalid timestamp: {e}")))?,
+            None => SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs(),
+        };
+
+        let scenario = match matches.value_of("scenario") {
+            Some(path) => {
+                let scenario = read_file(path.into())
+                    .map_err(|e| CLIError::Unknown(format!("failed to read scenario {path}: {e}")))?;
+                Scenario::from_toml(&scenario)
+                    .map_err(|e| CLIError::Unknown(format!("failed to parse scenario {path}: {e}")))?
+            }
+            None => Scenario::default(),
+        };
+
+        let results = simulation::simulate(state, account, timestamp, &scenario)
+            .await
+            .map_err(|e| CLIError::Unknown(format!("simulation failed: {e}")))?;
+
+        if results.is_empty() {
+            return Err(CLIError::Unknown(format!("no accounts with blocks before {timestamp}")));
+        }
+
+        print_results(&results)?;
+        return Ok(());
+    }
+
+    Err(CLIError::UnknownCommand)
+}
+
+fn print_results(results: &[SimulatedPower]) -> Result<(), CLIError> {
+    println!(
+        "{:<44} {:>22} {:>22} {:>14} {:>14} {:>22} {:>20} {:>20} {:>20}",
+        "account", "balance", "cashflow", "block", "age", "inactive tax", "actual", "delegated", "active"
+    );
+
+    for result in results {
+        let account = result.account.encode_zbase().map_err(|e| CLIError::Unknown(e.to_string()))?;
+        let components = &result.components;
+        println!(
+            "{:<44} {:>22} {:>22} {:>14} {:>14} {:>22} {:>20} {:>20} {:>20}",
+            format!("pog-{account}"),
+            components.balance.to_string(),
+            components.cashflow.to_string(),
+            components.block.to_string(),
+            components.age.to_string(),
+            components.inactive_tax.to_string(),
+            result.actual_power,
+            result.delegated_power,
+            result.active_power
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,2 @@
+mod commands;
+pub use commands::*;
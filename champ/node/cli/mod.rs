@@ -1,4 +1,5 @@
 pub mod admin;
+pub mod consensus;
 pub mod error;
 pub mod parser;
 pub mod wallet;
@@ -80,5 +80,35 @@ pub fn new() -> clap::ArgMatches {
                 )
                 .subcommand(clap::Command::new("generate-key").about("generates a node private key used for JWTs")),
         )
+        .subcommand(
+            clap::Command::new("consensus").about("inspect the consensus of the local database").subcommand(
+                clap::Command::new("simulate")
+                    .about("calculates the voting power of accounts and each graph it is made of")
+                    .arg(
+                        Arg::new("account")
+                            .short('a')
+                            .long("account")
+                            .help("Account to calculate the power of, all accounts if not set")
+                            .takes_value(true)
+                            .value_name("ACCOUNT"),
+                    )
+                    .arg(
+                        Arg::new("timestamp")
+                            .short('t')
+                            .long("timestamp")
+                            .help("Unix timestamp to calculate the power at, defaults to now")
+                            .takes_value(true)
+                            .value_name("TIMESTAMP"),
+                    )
+                    .arg(
+                        Arg::new("scenario")
+                            .short('s')
+                            .long("scenario")
+                            .help("TOML file with hypothetical blocks and delegations")
+                            .takes_value(true)
+                            .value_name("FILE"),
+                    ),
+            ),
+        )
         .get_matches()
 }
@@ -1,3 +1,4 @@
+use crate::chain::ChainSpec;
 use crate::storage::{DatabaseConfig, Databases};
 use anyhow::Result;
 use anyhow::{anyhow, Context};
@@ -45,6 +46,30 @@ fn default_consensus() -> ConsensusSettings {
     }
 }
 
+fn default_max_spam_index() -> f64 {
+    2.0
+}
+
+fn default_max_orphans() -> usize {
+    1000
+}
+
+fn default_orphan_expiry() -> u64 {
+    600
+}
+
+fn default_target_peers() -> usize {
+    25
+}
+
+fn default_ban_duration() -> u64 {
+    60 * 60 * 24
+}
+
+fn default_request_window() -> u64 {
+    60
+}
+
 fn default_node_name() -> String {
     "PogNetwork Node".to_string()
 }
@@ -90,6 +115,10 @@ pub struct Config {
 
     #[serde(skip)]
     pub internal: InternalConfig,
+
+    /// chain spec of `consensus.chain`, loaded on `read`
+    #[serde(skip)]
+    pub chain_spec: ChainSpec,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -97,7 +126,8 @@ pub struct WalletManagerConfig {}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ConsensusSettings {
-    pub chain: String, // currently only `dev` is supported
+    /// `dev` or the path to a chain spec file (relative to the config file)
+    pub chain: String,
 
     pub initial_peers: HashSet<String>,
 
@@ -105,6 +135,30 @@ pub struct ConsensusSettings {
 
     #[serde(with = "ModeDef")]
     pub mode: Mode,
+
+    /// blocks with a higher spam index are rejected (see `validation::spam`)
+    #[serde(default = "default_max_spam_index")]
+    pub max_spam_index: f64,
+
+    /// blocks that are held until their previous block arrives (see `blockpool::orphans`)
+    #[serde(default = "default_max_orphans")]
+    pub max_orphans: usize,
+
+    /// seconds an orphan waits for its previous block before it is dropped
+    #[serde(default = "default_orphan_expiry")]
+    pub orphan_expiry: u64,
+
+    /// peers learned from other peers are dialed until the node is connected to this many peers
+    #[serde(default = "default_target_peers")]
+    pub target_peers: usize,
+
+    /// seconds a peer is banned for once its score drops too low (see `p2p::reputation`)
+    #[serde(default = "default_ban_duration")]
+    pub ban_duration: u64,
+
+    /// requests signed more than this many seconds before or after the current time are rejected (see `p2p::replay`)
+    #[serde(default = "default_request_window")]
+    pub request_window: u64,
 }
 
 impl Default for ConsensusSettings {
@@ -114,6 +168,12 @@ impl Default for ConsensusSettings {
             mode: Mode::Validating,
             primary_wallet: None,
             initial_peers: HashSet::new(),
+            max_spam_index: default_max_spam_index(),
+            max_orphans: default_max_orphans(),
+            orphan_expiry: default_orphan_expiry(),
+            target_peers: default_target_peers(),
+            ban_duration: default_ban_duration(),
+            request_window: default_request_window(),
         }
     }
 }
@@ -181,6 +241,13 @@ impl Config {
         self.admin = config.admin;
         self.node_users = config.node_users;
         self.consensus.primary_wallet = config.consensus.primary_wallet;
+        self.consensus.chain = config.consensus.chain;
+        self.consensus.max_spam_index = config.consensus.max_spam_index;
+        self.consensus.max_orphans = config.consensus.max_orphans;
+        self.consensus.orphan_expiry = config.consensus.orphan_expiry;
+        self.consensus.target_peers = config.consensus.target_peers;
+        self.consensus.ban_duration = config.consensus.ban_duration;
+        self.consensus.request_window = config.consensus.request_window;
 
         self.data_path = if let Some(path) = config.database.path {
             let path = path.parse::<PathBuf>()?;
@@ -194,6 +261,9 @@ impl Config {
             Config::get_default_data_path()?.to_str().map(|p| p.to_string())
         };
         self.database.data_path = self.data_path.clone();
+
+        let config_dir = config_path.parent().ok_or_else(|| anyhow!("unknown config directory"))?;
+        self.chain_spec = ChainSpec::load(&self.consensus.chain, config_dir)?;
         Ok(())
     }
 
@@ -228,3 +298,39 @@ pub fn read_file(path: PathBuf) -> Result<String, std::io::Error> {
     drop(file);
     Ok(file_content)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_consensus_settings() {
+        let dir = std::env::temp_dir().join(format!("champ-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut config = Config {
+            config_path_override: dir.join("champ.toml").to_str().map(|p| p.to_string()),
+            ..Default::default()
+        };
+        config.database.path = Some("data".to_string());
+        config.consensus.max_orphans = 5;
+        config.consensus.orphan_expiry = 10;
+        config.consensus.target_peers = 15;
+        config.consensus.ban_duration = 20;
+        config.consensus.request_window = 25;
+        config.write().unwrap();
+
+        let mut read = Config {
+            config_path_override: config.config_path_override.clone(),
+            ..Default::default()
+        };
+        read.read().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(5, read.consensus.max_orphans);
+        assert_eq!(10, read.consensus.orphan_expiry);
+        assert_eq!(15, read.consensus.target_peers);
+        assert_eq!(20, read.consensus.ban_duration);
+        assert_eq!(25, read.consensus.request_window);
+    }
+}
@@ -0,0 +1,113 @@
+use dashmap::DashMap;
+use encoding::account::generate_account_address;
+use pog_proto::api::{transaction::Data, AccountID, SignedBlock};
+
+use super::metrics;
+
+/// Caches the voting power of accounts until a block changes it
+#[derive(Debug, Default)]
+pub struct VotingPowerCache {
+    actual_power: DashMap<AccountID, u64>,
+    // delegated power of an account and the delegators it was calculated from
+    delegated_power: DashMap<AccountID, (u64, Vec<AccountID>)>,
+}
+
+impl VotingPowerCache {
+    pub fn get_actual_power(&self, account_id: &AccountID) -> Option<u64> {
+        record(self.actual_power.get(account_id).map(|power| *power))
+    }
+
+    pub fn set_actual_power(&self, account_id: AccountID, power: u64) {
+        self.actual_power.insert(account_id, power);
+    }
+
+    pub fn get_delegated_power(&self, account_id: &AccountID) -> Option<u64> {
+        record(self.delegated_power.get(account_id).map(|entry| entry.0))
+    }
+
+    pub fn set_delegated_power(&self, account_id: AccountID, power: u64, delegators: Vec<AccountID>) {
+        self.delegated_power.insert(account_id, (power, delegators));
+    }
+
+    /// Removes all voting power that depends on the account of a newly added block
+    pub fn invalidate(&self, block: &SignedBlock) {
+        let account_id = match generate_account_address(block.header.public_key.to_vec()) {
+            Ok(account_id) => account_id,
+            Err(_) => return,
+        };
+
+        self.actual_power.remove(&account_id);
+        self.delegated_power.remove(&account_id);
+        self.delegated_power.retain(|_, (_, delegators)| !delegators.contains(&account_id));
+
+        // the new representative gains a delegator
+        for transaction in &block.data.transactions {
+            if let Some(Data::TxDelegate(tx)) = &transaction.data {
+                if let Ok(representative) = AccountID::try_from(tx.representative.as_slice()) {
+                    self.delegated_power.remove(&representative);
+                }
+            }
+        }
+    }
+}
+
+fn record(power: Option<u64>) -> Option<u64> {
+    match power {
+        Some(_) => metrics::cache_hit(),
+        None => metrics::cache_miss(),
+    }
+    power
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pog_proto::api::{BlockData, BlockHeader};
+
+    fn block(public_key: &[u8]) -> SignedBlock {
+        SignedBlock::new(
+            BlockHeader {
+                signature: b"signature".to_vec(),
+                public_key: public_key.to_vec(),
+                timestamp: 1,
+            },
+            BlockData {
+                version: 0,
+                signature_type: 0,
+                balance: 0,
+                height: 0,
+                previous: b"previous".to_vec(),
+                transactions: vec![],
+            },
+        )
+    }
+
+    #[test]
+    fn test_voting_power_cache() {
+        let cache = VotingPowerCache::default();
+        let representative = generate_account_address(b"representative".to_vec()).unwrap();
+        let delegator = generate_account_address(b"delegator".to_vec()).unwrap();
+
+        assert_eq!(None, cache.get_actual_power(&representative));
+
+        cache.set_actual_power(representative, 100);
+        cache.set_actual_power(delegator, 50);
+        cache.set_delegated_power(representative, 50, vec![delegator]);
+        assert_eq!(Some(100), cache.get_actual_power(&representative));
+        assert_eq!(Some(50), cache.get_delegated_power(&representative));
+
+        // blocks of unrelated accounts don't change anything
+        cache.invalidate(&block(b"other"));
+        assert_eq!(Some(100), cache.get_actual_power(&representative));
+        assert_eq!(Some(50), cache.get_delegated_power(&representative));
+
+        // a block of a delegator changes the delegated power of its representative
+        cache.invalidate(&block(b"delegator"));
+        assert_eq!(None, cache.get_actual_power(&delegator));
+        assert_eq!(None, cache.get_delegated_power(&representative));
+        assert_eq!(Some(100), cache.get_actual_power(&representative));
+
+        cache.invalidate(&block(b"representative"));
+        assert_eq!(None, cache.get_actual_power(&representative));
+    }
+}
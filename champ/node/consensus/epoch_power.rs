@@ -0,0 +1,197 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use anyhow::{anyhow, Result};
+use pog_proto::api::{AccountID, SignedBlock};
+
+use super::simulation::{simulate, Scenario};
+use crate::state::ChampStateArc;
+
+/// The active power of every account at the start of an epoch, ordered by account
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PowerSnapshot {
+    pub epoch: u64,
+    pub powers: Vec<(AccountID, u64)>,
+}
+
+impl PowerSnapshot {
+    pub fn new(epoch: u64, mut powers: Vec<(AccountID, u64)>) -> Self {
+        powers.sort();
+        Self {
+            epoch,
+            powers,
+        }
+    }
+
+    /// Accounts without a block before the epoch started have no power
+    pub fn power(&self, account_id: &AccountID) -> u64 {
+        match self.powers.binary_search_by(|(account, _)| account.cmp(account_id)) {
+            Ok(index) => self.powers[index].1,
+            Err(_) => 0,
+        }
+    }
+}
+
+/// The voting power snapshot of the current epoch, which all votes during the epoch are weighted with
+#[derive(Debug, Default)]
+pub struct EpochPower {
+    snapshot: RwLock<Option<PowerSnapshot>>,
+    // set when blocks from before the epoch were synced after the snapshot was taken
+    outdated: AtomicBool,
+}
+
+impl EpochPower {
+    pub fn get(&self) -> Option<PowerSnapshot> {
+        self.snapshot.read().expect("epoch power lock is poisoned").clone()
+    }
+
+    pub fn set(&self, snapshot: PowerSnapshot) {
+        *self.snapshot.write().expect("epoch power lock is poisoned") = Some(snapshot);
+    }
+
+    pub fn epoch(&self) -> Option<u64> {
+        self.snapshot.read().expect("epoch power lock is poisoned").as_ref().map(|snapshot| snapshot.epoch)
+    }
+
+    pub fn power(&self, account_id: &AccountID) -> Option<u64> {
+        self.snapshot.read().expect("epoch power lock is poisoned").as_ref().map(|snapshot| snapshot.power(account_id))
+    }
+
+    /// Whether the snapshot has to be taken again
+    pub fn is_outdated(&self) -> bool {
+        self.outdated.load(Ordering::SeqCst)
+    }
+}
+
+/// Marks the snapshot as outdated if a synced block is from before its epoch
+///
+/// A node that is still syncing receives old blocks after it took the snapshot, and would otherwise weight votes with
+/// the power it knew of until the epoch ends.
+pub async fn block_synced(state: &ChampStateArc, block: &SignedBlock) {
+    let epoch = match state.epoch_power.epoch() {
+        Some(epoch) => epoch,
+        None => return,
+    };
+
+    let epoch_start = epoch.saturating_mul(state.config.read().await.chain_spec.epoch_length);
+    if block.header.timestamp <= epoch_start {
+        state.epoch_power.outdated.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Snapshots the active power of every account at the start of an epoch and persists it.
+///
+/// Only blocks from before the epoch count, so every node calculates the same snapshot no matter when it does so.
+/// Snapshots that were already taken are loaded from the database instead, unless they are outdated.
+pub async fn snapshot(state: &ChampStateArc, epoch: u64) -> Result<PowerSnapshot> {
+    // blocks that change while the snapshot is taken mark it as outdated again
+    let outdated = state.epoch_power.outdated.swap(false, Ordering::SeqCst);
+    if !outdated {
+        if let Some(powers) = state.db.lock().await.get_power_snapshot(epoch).await? {
+            let snapshot = PowerSnapshot::new(epoch, powers);
+            state.epoch_power.set(snapshot.clone());
+            return Ok(snapshot);
+        }
+    }
+
+    let epoch_start = epoch.saturating_mul(state.config.read().await.chain_spec.epoch_length);
+    let powers = simulate(state, None, epoch_start, &Scenario::default())
+        .await?
+        .into_iter()
+        .map(|power| (power.account, power.active_power))
+        .collect();
+
+    let snapshot = PowerSnapshot::new(epoch, powers);
+    tracing::debug!("took a snapshot of the voting power of {} accounts for epoch {epoch}", snapshot.powers.len());
+
+    state.db.lock().await.set_power_snapshot(epoch, snapshot.powers.clone()).await?;
+    state.epoch_power.set(snapshot.clone());
+    Ok(snapshot)
+}
+
+/// The power an account votes with during the current epoch
+pub fn get_vote_power(state: &ChampStateArc, account_id: &AccountID) -> Result<u64> {
+    state.epoch_power.power(account_id).ok_or_else(|| anyhow!("no voting power snapshot for the current epoch"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{account, genesis_block_at, DELEGATE_BALANCE};
+    use crate::state::ChampState;
+    use crypto::signatures::ed25519::generate_private_key;
+
+    #[test]
+    fn test_power_snapshot() {
+        let snapshot = PowerSnapshot::new(2, vec![([3; 24], 30), ([1; 24], 10), ([2; 24], 20)]);
+        assert_eq!(vec![([1; 24], 10), ([2; 24], 20), ([3; 24], 30)], snapshot.powers);
+        assert_eq!(20, snapshot.power(&[2; 24]));
+        assert_eq!(0, snapshot.power(&[4; 24]));
+
+        let epoch_power = EpochPower::default();
+        assert_eq!(None, epoch_power.power(&[1; 24]));
+        epoch_power.set(snapshot);
+        assert_eq!(Some(2), epoch_power.epoch());
+        assert_eq!(Some(10), epoch_power.power(&[1; 24]));
+        assert_eq!(Some(0), epoch_power.power(&[4; 24]));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot() -> Result<()> {
+        let state = ChampState::mock().await;
+        let epoch_length = state.config.read().await.chain_spec.epoch_length;
+        let (early, late) = (generate_private_key()?, generate_private_key()?);
+        let early_account = account(&early)?;
+        let late_account = account(&late)?;
+        get_vote_power(&state, &early_account).expect_err("there is no snapshot yet");
+
+        {
+            let mut db = state.db.lock().await;
+            db.add_block(genesis_block_at(&early, DELEGATE_BALANCE, 10 * epoch_length - 1)).await?;
+            db.add_block(genesis_block_at(&late, DELEGATE_BALANCE, 10 * epoch_length + 1)).await?;
+        }
+
+        // blocks added during the epoch do not change its snapshot
+        let taken = snapshot(&state, 10).await?;
+        assert!(taken.power(&early_account) > 0);
+        assert_eq!(0, taken.power(&late_account));
+        assert_eq!(taken.power(&early_account), get_vote_power(&state, &early_account)?);
+        assert_eq!(Some(taken.powers.clone()), state.db.lock().await.get_power_snapshot(10).await?);
+
+        let next = snapshot(&state, 11).await?;
+        assert!(next.power(&late_account) > 0);
+        assert_eq!(Some(11), state.epoch_power.epoch());
+
+        // a restarted node loads the snapshot it already took
+        assert_eq!(taken, snapshot(&state, 10).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_outdated_snapshot() -> Result<()> {
+        let state = ChampState::mock().await;
+        let epoch_length = state.config.read().await.chain_spec.epoch_length;
+        let (synced, late) = (generate_private_key()?, generate_private_key()?);
+        let synced_account = account(&synced)?;
+        let taken = snapshot(&state, 10).await?;
+        assert_eq!(0, taken.power(&synced_account));
+
+        // blocks signed during the epoch don't change the snapshot
+        let block = genesis_block_at(&late, DELEGATE_BALANCE, 10 * epoch_length + 1);
+        state.db.lock().await.add_block(block.clone()).await?;
+        block_synced(&state, &block).await;
+        assert!(!state.epoch_power.is_outdated());
+
+        // a node that was behind takes the snapshot again once it synced an older block
+        let block = genesis_block_at(&synced, DELEGATE_BALANCE, 10 * epoch_length - 1);
+        state.db.lock().await.add_block(block.clone()).await?;
+        block_synced(&state, &block).await;
+        assert!(state.epoch_power.is_outdated());
+
+        let retaken = snapshot(&state, 10).await?;
+        assert!(retaken.power(&synced_account) > 0);
+        assert!(!state.epoch_power.is_outdated());
+        assert_eq!(Some(retaken.powers), state.db.lock().await.get_power_snapshot(10).await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use anyhow::{anyhow, Result};
+use crypto::signatures::ed25519::verify_signature;
+use encoding::account::generate_account_address;
+use pog_proto::api::{AccountID, BlockID, SignedBlock};
+use pog_proto::p2p::{request_body, RequestBody, RequestHeader};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+
+use crate::p2p::server::timestamp;
+use crate::state::ChampStateArc;
+
+// votes are kept this long to compare them with later votes of the same voter (a day)
+const VOTE_RETENTION: u64 = 60 * 60 * 24;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VoteKind {
+    Proposal,
+    Final,
+}
+
+/// A vote request exactly as the voter signed it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SignedVote {
+    /// the encoded `RequestBody`
+    pub data: Vec<u8>,
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+/// The block a signed vote is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoteTarget {
+    pub voter: AccountID,
+    pub kind: VoteKind,
+    pub account: AccountID,
+    pub height: u64,
+    pub block_id: BlockID,
+}
+
+impl SignedVote {
+    pub fn from_request(data: &[u8], header: &RequestHeader) -> Self {
+        Self {
+            data: data.to_vec(),
+            public_key: header.public_key.clone(),
+            signature: header.signature.clone(),
+        }
+    }
+
+    /// Verifies the signature and reads the block the vote is for
+    ///
+    /// Proposals from nodes that are not prime delegates do not vote for the block and return `None`.
+    pub fn target(&self) -> Result<Option<VoteTarget>> {
+        verify_signature(&self.data, &self.public_key, &self.signature)
+            .map_err(|_| anyhow!("invalid vote signature"))?;
+
+        let (kind, raw_block) = match RequestBody::decode(&*self.data)?.data {
+            Some(request_body::Data::VoteProposal(proposal)) if proposal.vote > 0 => {
+                (VoteKind::Proposal, proposal.block)
+            }
+            Some(request_body::Data::VoteProposal(_)) => return Ok(None),
+            Some(request_body::Data::FinalVote(vote)) => (VoteKind::Final, vote.block),
+            _ => return Err(anyhow!("request is not a vote")),
+        };
+        let block: SignedBlock = raw_block.ok_or_else(|| anyhow!("block was none"))?.try_into()?;
+
+        Ok(Some(VoteTarget {
+            voter: generate_account_address(self.public_key.clone())?,
+            kind,
+            account: generate_account_address(block.header.public_key.clone())?,
+            height: block.data.height,
+            block_id: block.get_id(),
+        }))
+    }
+}
+
+/// Proof that a voter signed two votes of the same kind for different blocks at the same height
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Evidence {
+    pub voter: AccountID,
+    pub kind: VoteKind,
+    pub account: AccountID,
+    pub height: u64,
+    pub first: SignedVote,
+    pub second: SignedVote,
+    /// when the evidence was found, in seconds since the unix epoch
+    pub detected_at: u64,
+}
+
+impl Evidence {
+    /// Checks both signatures and that the votes are for different blocks at the height of the evidence
+    pub fn verify(&self) -> Result<()> {
+        let first = self.first.target()?.ok_or_else(|| anyhow!("first request is not a vote"))?;
+        let second = self.second.target()?.ok_or_else(|| anyhow!("second request is not a vote"))?;
+
+        for target in [first, second] {
+            if (target.voter, target.kind, target.account, target.height)
+                != (self.voter, self.kind, self.account, self.height)
+            {
+                return Err(anyhow!("vote does not match the evidence"));
+            }
+        }
+
+        if first.block_id == second.block_id {
+            return Err(anyhow!("votes are for the same block"));
+        }
+        Ok(())
+    }
+
+    /// Evidence for the same voter and height is only stored once
+    fn key(&self) -> Vec<u8> {
+        let mut key = self.voter.to_vec();
+        key.extend_from_slice(&self.account);
+        key.extend_from_slice(&self.height.to_be_bytes());
+        key.push(self.kind as u8);
+        key
+    }
+}
+
+/// Votes received from the network and the voters that were caught signing competing votes
+#[derive(Debug, Default)]
+pub struct Equivocations {
+    // the first vote of each voter for each height, with the time it was received
+    votes: RwLock<HashMap<(AccountID, VoteKind, AccountID, u64), (BlockID, SignedVote, u64)>>,
+    // voters whose power is excluded from quorum until a timestamp
+    excluded: RwLock<HashMap<AccountID, u64>>,
+}
+
+impl Equivocations {
+    /// Compares a vote with the earlier votes of its voter and returns evidence if it is for a different block
+    pub fn check(&self, vote: SignedVote, now: u64) -> Result<Option<Evidence>> {
+        let target = match vote.target()? {
+            Some(target) => target,
+            None => return Ok(None),
+        };
+
+        let mut votes = self.votes.write().expect("equivocations lock is poisoned");
+        let key = (target.voter, target.kind, target.account, target.height);
+        let (block_id, first) = match votes.get(&key) {
+            Some((block_id, first, received)) if received.saturating_add(VOTE_RETENTION) > now => (block_id, first),
+            _ => {
+                votes.insert(key, (target.block_id, vote, now));
+                return Ok(None);
+            }
+        };
+
+        if *block_id == target.block_id {
+            return Ok(None);
+        }
+
+        Ok(Some(Evidence {
+            voter: target.voter,
+            kind: target.kind,
+            account: target.account,
+            height: target.height,
+            first: first.clone(),
+            second: vote,
+            detected_at: now,
+        }))
+    }
+
+    /// Forgets the votes that were received more than a day ago
+    pub fn prune(&self, now: u64) {
+        let mut votes = self.votes.write().expect("equivocations lock is poisoned");
+        votes.retain(|_, (_, _, received)| received.saturating_add(VOTE_RETENTION) > now);
+    }
+
+    pub fn exclude(&self, voter: AccountID, until: u64) {
+        let mut excluded = self.excluded.write().expect("equivocations lock is poisoned");
+        let until = excluded.get(&voter).map_or(until, |earlier| until.max(*earlier));
+        excluded.insert(voter, until);
+    }
+
+    pub fn is_excluded(&self, voter: &AccountID, now: u64) -> bool {
+        self.excluded.read().expect("equivocations lock is poisoned").get(voter).map_or(false, |until| *until > now)
+    }
+
+    /// Voters that are currently excluded from quorum
+    pub fn excluded(&self, now: u64) -> Vec<AccountID> {
+        let excluded = self.excluded.read().expect("equivocations lock is poisoned");
+        excluded.iter().filter(|(_, until)| **until > now).map(|(voter, _)| *voter).collect()
+    }
+
+    /// When the exclusion of a voter ends, if it is excluded
+    pub fn excluded_until(&self, voter: &AccountID) -> Option<u64> {
+        self.excluded.read().expect("equivocations lock is poisoned").get(voter).copied()
+    }
+}
+
+/// Records a signed vote received from the network
+///
+/// If the voter already signed a vote for another block at the same height, the evidence is stored and the voter's
+/// power is excluded from quorum for `equivocation_exclusion_period` seconds. Only the votes of prime delegates are
+/// recorded, since anyone can sign votes with new keys.
+pub async fn record(state: &ChampStateArc, vote: SignedVote) -> Result<Option<Evidence>> {
+    if !state.prime_delegates.contains(&generate_account_address(vote.public_key.clone())?) {
+        return Ok(None);
+    }
+
+    let evidence = match state.equivocations.check(vote, timestamp())? {
+        Some(evidence) => evidence,
+        None => return Ok(None),
+    };
+
+    tracing::warn!(
+        "{:?} signed votes for two blocks at height {} of {:?}",
+        evidence.voter,
+        evidence.height,
+        evidence.account
+    );
+    state.db.lock().await.add_evidence(evidence.key(), serde_json::to_vec(&evidence)?).await?;
+    exclude(state, &evidence).await;
+    Ok(Some(evidence))
+}
+
+/// Excludes the voters of all stored evidence again, e.g. after a restart
+pub async fn restore(state: &ChampStateArc) -> Result<()> {
+    for evidence in get_evidence(state).await? {
+        exclude(state, &evidence).await;
+    }
+    Ok(())
+}
+
+/// All evidence this node has found
+pub async fn get_evidence(state: &ChampStateArc) -> Result<Vec<Evidence>> {
+    let evidence = state.db.lock().await.get_evidence().await?;
+    Ok(evidence.iter().map(|evidence| serde_json::from_slice(evidence)).collect::<Result<_, _>>()?)
+}
+
+async fn exclude(state: &ChampStateArc, evidence: &Evidence) {
+    let period = state.config.read().await.chain_spec.consensus.equivocation_exclusion_period;
+    state.equivocations.exclude(evidence.voter, evidence.detected_at.saturating_add(period));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::state::ChampState;
+    use crypto::signatures::ed25519::{create_public_key, create_signature, generate_private_key};
+    use pog_proto::api::RawBlock;
+
+    fn raw_block(private_key: &[u8], balance: u64) -> RawBlock {
+        fixtures::raw_block(&fixtures::genesis_block(private_key, balance))
+    }
+
+    fn signed_vote(voter: &[u8], request: request_body::Data) -> SignedVote {
+        let data = RequestBody {
+            data: Some(request),
+            signature_type: 0,
+            timestamp: 1_650_000_000,
+        }
+        .encode_to_vec();
+
+        SignedVote {
+            signature: create_signature(&data, voter).expect("should sign").to_vec(),
+            public_key: create_public_key(voter).expect("should create public key").to_vec(),
+            data,
+        }
+    }
+
+    fn proposal(voter: &[u8], block: RawBlock, vote: u64) -> SignedVote {
+        signed_vote(
+            voter,
+            request_body::Data::VoteProposal(request_body::VoteProposal {
+                block: Some(block),
+                vote,
+            }),
+        )
+    }
+
+    fn final_vote(voter: &[u8], block: RawBlock) -> SignedVote {
+        signed_vote(
+            voter,
+            request_body::Data::FinalVote(request_body::FinalVote {
+                block: Some(block),
+                vote: 1,
+            }),
+        )
+    }
+
+    #[test]
+    fn test_check() -> Result<()> {
+        let (voter, wallet) = (generate_private_key()?, generate_private_key()?);
+        let (first, second) = (raw_block(&wallet, 100), raw_block(&wallet, 200));
+        let equivocations = Equivocations::default();
+
+        assert_eq!(None, equivocations.check(proposal(&voter, first.clone(), 10), 0)?);
+        assert_eq!(None, equivocations.check(proposal(&voter, first.clone(), 10), 0)?);
+        // proposals without a vote and final votes for the block the network chose are allowed
+        assert_eq!(None, equivocations.check(proposal(&voter, second.clone(), 0), 0)?);
+        assert_eq!(None, equivocations.check(final_vote(&voter, second.clone()), 0)?);
+
+        let evidence = equivocations.check(proposal(&voter, second.clone(), 10), 5)?.expect("should find evidence");
+        assert_eq!(fixtures::account(&voter)?, evidence.voter);
+        assert_eq!(VoteKind::Proposal, evidence.kind);
+        assert_eq!(5, evidence.detected_at);
+        evidence.verify()?;
+
+        // votes are forgotten after a day
+        let equivocations = Equivocations::default();
+        equivocations.check(final_vote(&voter, first.clone()), 0)?;
+        assert_eq!(None, equivocations.check(final_vote(&voter, second.clone()), VOTE_RETENTION)?);
+
+        equivocations.check(proposal(&voter, first, 10), VOTE_RETENTION)?;
+        equivocations.prune(2 * VOTE_RETENTION);
+        assert!(equivocations.votes.read().unwrap().is_empty());
+        assert_eq!(None, equivocations.check(proposal(&voter, second, 10), 2 * VOTE_RETENTION)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify() -> Result<()> {
+        let (voter, other_voter, wallet) = (generate_private_key()?, generate_private_key()?, generate_private_key()?);
+        let equivocations = Equivocations::default();
+        equivocations.check(final_vote(&voter, raw_block(&wallet, 100)), 0)?;
+        let evidence =
+            equivocations.check(final_vote(&voter, raw_block(&wallet, 200)), 0)?.expect("should find evidence");
+
+        let mut forged = evidence.clone();
+        forged.second.signature = forged.first.signature.clone();
+        forged.verify().expect_err("signature should be invalid");
+
+        let mut same_block = evidence.clone();
+        same_block.second = same_block.first.clone();
+        same_block.verify().expect_err("votes should be for different blocks");
+
+        let mut other = evidence;
+        other.second = final_vote(&other_voter, raw_block(&wallet, 200));
+        other.verify().expect_err("votes should be signed by the same voter");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_record() -> Result<()> {
+        let state = ChampState::mock().await;
+        let (voter, other_voter, wallet) = (generate_private_key()?, generate_private_key()?, generate_private_key()?);
+        let voter_id = fixtures::account(&voter)?;
+        state.prime_delegates.set(0, vec![(voter_id, 100)]);
+
+        // votes of accounts that are not prime delegates are not recorded
+        assert_eq!(None, record(&state, final_vote(&other_voter, raw_block(&wallet, 100))).await?);
+        assert_eq!(None, record(&state, final_vote(&other_voter, raw_block(&wallet, 200))).await?);
+        assert!(state.equivocations.votes.read().unwrap().is_empty());
+
+        assert_eq!(None, record(&state, final_vote(&voter, raw_block(&wallet, 100))).await?);
+        assert!(!state.equivocations.is_excluded(&voter_id, timestamp()));
+
+        let evidence =
+            record(&state, final_vote(&voter, raw_block(&wallet, 200))).await?.expect("should find evidence");
+        assert!(state.equivocations.is_excluded(&voter_id, timestamp()));
+        assert_eq!(vec![voter_id], state.equivocations.excluded(timestamp()));
+
+        let period = state.config.read().await.chain_spec.consensus.equivocation_exclusion_period;
+        assert!(!state.equivocations.is_excluded(&voter_id, evidence.detected_at + period));
+        assert_eq!(vec![evidence], get_evidence(&state).await?);
+        Ok(())
+    }
+}
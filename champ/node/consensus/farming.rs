@@ -0,0 +1,301 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use pog_proto::api::{
+    transaction::{Data, TxSend},
+    AccountID, SignedBlock, Transaction, TransactionID,
+};
+
+use super::{fixed::Fixed, parameters::FarmingParameters};
+use crate::state::ChampStateArc;
+
+// blocks of an account that are analyzed, starting at its latest block
+const MAX_ANALYZED_BLOCKS: u32 = 100;
+
+/// How much an account looks like an importance farming wallet (see `docs/developers/consensus/importance_farming.md`).
+///
+/// Each signal is between 0 (normal) and 1 (farming). The scores use `Fixed`, as they can change the voting power.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FarmingScore {
+    /// balance changes with very even amounts and intervals
+    pub uniform_transactions: Fixed,
+    /// delegated while the account was young
+    pub young_delegation: Fixed,
+    /// share of the received funds that came from a single account
+    pub single_funding_source: Fixed,
+    /// delegated without ever changing the representative
+    pub unchanged_delegate: Fixed,
+}
+
+impl FarmingScore {
+    /// The average of all signals
+    pub fn total(&self) -> Fixed {
+        (self.uniform_transactions + self.young_delegation + self.single_funding_source + self.unchanged_delegate)
+            / Fixed::from(4)
+    }
+
+    pub fn is_flagged(&self, params: &FarmingParameters) -> bool {
+        self.total() >= Fixed::from_f64(params.flag_threshold)
+    }
+}
+
+/// Scores an account from its latest blocks and the accounts that sent the funds it claimed
+pub async fn score(state: &ChampStateArc, account_id: AccountID) -> Result<FarmingScore> {
+    let params = state.config.read().await.chain_spec.consensus.farming.clone();
+    let db = state.db.lock().await;
+
+    let mut blocks = db.get_blocks(true, MAX_ANALYZED_BLOCKS, 0, Some(account_id)).await?;
+    blocks.reverse();
+
+    let mut funding = vec![];
+    for send_id in claimed_sends(&blocks) {
+        // claims of unknown transactions can not be attributed to a sender
+        if let Ok((send, _, sender)) = db.get_transaction_by_id(send_id).await {
+            if let Some(Data::TxSend(TxSend {
+                amount,
+                ..
+            })) = send.data
+            {
+                funding.push((sender, amount));
+            }
+        }
+    }
+
+    Ok(score_blocks(&blocks, &funding, &params))
+}
+
+/// Scores an account from its blocks until `timestamp`, like `score` does from the database
+///
+/// `chain` is ordered from the first to the latest block and `sends` are the sends of all accounts (see `sends`).
+pub fn score_chain(
+    chain: &[SignedBlock],
+    sends: &HashMap<TransactionID, (AccountID, u64)>,
+    timestamp: u64,
+    params: &FarmingParameters,
+) -> FarmingScore {
+    let blocks: Vec<SignedBlock> = chain.iter().filter(|block| block.header.timestamp <= timestamp).cloned().collect();
+    let blocks = &blocks[blocks.len().saturating_sub(MAX_ANALYZED_BLOCKS as usize)..];
+
+    let funding: Vec<(AccountID, u64)> =
+        claimed_sends(blocks).filter_map(|send_id| sends.get(&send_id).copied()).collect();
+    score_blocks(blocks, &funding, params)
+}
+
+/// The sender and amount of every send in the chains until `timestamp`, by transaction id
+pub fn sends(
+    chains: &BTreeMap<AccountID, Vec<SignedBlock>>,
+    timestamp: u64,
+) -> HashMap<TransactionID, (AccountID, u64)> {
+    let mut sends = HashMap::new();
+    for (account_id, chain) in chains {
+        for block in chain.iter().filter(|block| block.header.timestamp <= timestamp) {
+            let block_id = block.get_id();
+            for (index, transaction) in block.data.transactions.iter().enumerate() {
+                if let Some(Data::TxSend(TxSend {
+                    amount,
+                    ..
+                })) = transaction.data
+                {
+                    if let Ok(transaction_id) = Transaction::get_id(block_id, index as u32) {
+                        sends.insert(transaction_id, (*account_id, amount));
+                    }
+                }
+            }
+        }
+    }
+    sends
+}
+
+/// Scores the blocks of an account, ordered from oldest to newest, and the funds it received from each sender
+pub fn score_blocks(blocks: &[SignedBlock], funding: &[(AccountID, u64)], params: &FarmingParameters) -> FarmingScore {
+    FarmingScore {
+        uniform_transactions: uniform_transactions(blocks, params),
+        young_delegation: young_delegation(blocks, params),
+        single_funding_source: single_funding_source(funding),
+        unchanged_delegate: unchanged_delegate(blocks),
+    }
+}
+
+/// Reduces the power an account delegates if it is flagged as a farming wallet
+pub fn discount(power: u64, score: &FarmingScore, params: &FarmingParameters) -> u64 {
+    if !score.is_flagged(params) {
+        return power;
+    }
+
+    let remaining = (Fixed::ONE - Fixed::from_f64(params.discount)).max(Fixed::ZERO);
+    (Fixed::from(power) * remaining).to_u64()
+}
+
+fn uniform_transactions(blocks: &[SignedBlock], params: &FarmingParameters) -> Fixed {
+    let mut amounts = vec![];
+    let mut intervals = vec![];
+    for pair in blocks.windows(2) {
+        let (previous, block) = (&pair[0], &pair[1]);
+        amounts.push(Fixed::from(block.data.balance.abs_diff(previous.data.balance)));
+        intervals.push(Fixed::from(block.header.timestamp.saturating_sub(previous.header.timestamp)));
+    }
+
+    if amounts.len() < params.min_transactions.max(1) {
+        return Fixed::ZERO;
+    }
+
+    let variation = (coefficient_of_variation(&amounts) + coefficient_of_variation(&intervals)) / Fixed::from(2);
+    (Fixed::ONE - variation).max(Fixed::ZERO)
+}
+
+fn young_delegation(blocks: &[SignedBlock], params: &FarmingParameters) -> Fixed {
+    let first_block = match blocks.first() {
+        Some(block) => block,
+        None => return Fixed::ZERO,
+    };
+
+    let delegation = blocks.iter().find(|block| delegations(block) > 0);
+    match delegation {
+        Some(block) if params.young_delegation_age > 0 => {
+            let age = Fixed::from(block.header.timestamp.saturating_sub(first_block.header.timestamp));
+            (Fixed::ONE - age / Fixed::from(params.young_delegation_age)).max(Fixed::ZERO)
+        }
+        _ => Fixed::ZERO,
+    }
+}
+
+fn single_funding_source(funding: &[(AccountID, u64)]) -> Fixed {
+    let mut by_sender: HashMap<AccountID, u64> = HashMap::new();
+    for (sender, amount) in funding {
+        let total = by_sender.entry(*sender).or_default();
+        *total = total.saturating_add(*amount);
+    }
+
+    let total = by_sender.values().fold(0u64, |sum, amount| sum.saturating_add(*amount));
+    match by_sender.values().max() {
+        Some(largest) if total > 0 => Fixed::from(*largest) / Fixed::from(total),
+        _ => Fixed::ZERO,
+    }
+}
+
+fn unchanged_delegate(blocks: &[SignedBlock]) -> Fixed {
+    match blocks.iter().map(delegations).sum::<u64>() {
+        0 => Fixed::ZERO,
+        changes => Fixed::ONE / Fixed::from(changes),
+    }
+}
+
+fn claimed_sends(blocks: &[SignedBlock]) -> impl Iterator<Item = TransactionID> + '_ {
+    blocks.iter().flat_map(|block| block.data.transactions.iter()).filter_map(|transaction| match &transaction.data {
+        Some(Data::TxClaim(claim)) => claim.send_transaction_id.clone().try_into().ok(),
+        _ => None,
+    })
+}
+
+fn delegations(block: &SignedBlock) -> u64 {
+    block.data.transactions.iter().filter(|tx| matches!(tx.data, Some(Data::TxDelegate(_)))).count() as u64
+}
+
+/// Standard deviation relative to the mean. Values are divided by the mean first, so large amounts can not overflow.
+fn coefficient_of_variation(values: &[Fixed]) -> Fixed {
+    let count = Fixed::from(values.len() as u64);
+    let mean = values.iter().fold(Fixed::ZERO, |sum, value| sum + *value) / count;
+    if mean <= Fixed::ZERO {
+        return Fixed::ZERO;
+    }
+
+    let variance = values
+        .iter()
+        .map(|value| *value / mean - Fixed::ONE)
+        .fold(Fixed::ZERO, |sum, deviation| sum + deviation * deviation)
+        / count;
+    variance.sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pog_proto::api::{transaction::TxDelegate, BlockData, BlockHeader, Transaction};
+
+    const DAY: u64 = 60 * 60 * 24;
+
+    fn block(timestamp: u64, height: u64, balance: u64, delegate: bool) -> SignedBlock {
+        let transactions = match delegate {
+            true => vec![Transaction {
+                data: Some(Data::TxDelegate(TxDelegate {
+                    representative: vec![1; 24],
+                })),
+            }],
+            false => vec![],
+        };
+
+        SignedBlock::new(
+            BlockHeader {
+                signature: vec![],
+                public_key: vec![],
+                timestamp,
+            },
+            BlockData {
+                version: 0,
+                signature_type: 0,
+                balance,
+                height,
+                previous: vec![],
+                transactions,
+            },
+        )
+    }
+
+    #[test]
+    fn test_farming_wallet() {
+        let params = FarmingParameters::default();
+        // delegates on the first day and then receives the same amount every day
+        let mut blocks = vec![block(0, 0, 0, false), block(DAY, 1, 0, true)];
+        for height in 2..10 {
+            blocks.push(block(height * DAY, height, height * 100, false));
+        }
+        let funding = vec![([2; 24], 500), ([2; 24], 300)];
+
+        let score = score_blocks(&blocks, &funding, &params);
+        assert!(score.uniform_transactions > Fixed::from_f64(0.5), "{score:?}");
+        assert!(score.young_delegation > Fixed::from_f64(0.9), "{score:?}");
+        assert_eq!(Fixed::ONE, score.single_funding_source);
+        assert_eq!(Fixed::ONE, score.unchanged_delegate);
+        assert!(score.is_flagged(&params));
+        assert_eq!(500, discount(1000, &score, &params));
+    }
+
+    #[test]
+    fn test_normal_wallet() {
+        let params = FarmingParameters::default();
+        let blocks = vec![
+            block(0, 0, 1000, false),
+            block(3 * DAY, 1, 50, false),
+            block(4 * DAY, 2, 7000, false),
+            block(40 * DAY, 3, 6990, true),
+            block(90 * DAY, 4, 100, false),
+            block(91 * DAY, 5, 30_000, true),
+        ];
+        let funding = vec![([2; 24], 500), ([3; 24], 6000), ([4; 24], 30_000)];
+
+        let score = score_blocks(&blocks, &funding, &params);
+        assert_eq!(Fixed::ZERO, score.young_delegation);
+        assert_eq!(Fixed::from_f64(0.5), score.unchanged_delegate);
+        assert!(score.single_funding_source < Fixed::ONE);
+        assert!(!score.is_flagged(&params));
+        assert_eq!(1000, discount(1000, &score, &params));
+    }
+
+    #[test]
+    fn test_without_history() {
+        let params = FarmingParameters::default();
+        assert_eq!(FarmingScore::default(), score_blocks(&[], &[], &params));
+        assert_eq!(
+            Fixed::ZERO,
+            score_blocks(&[block(0, 0, 10, false), block(DAY, 1, 20, false)], &[], &params).uniform_transactions
+        );
+    }
+
+    #[test]
+    fn test_coefficient_of_variation() {
+        assert_eq!(Fixed::ZERO, coefficient_of_variation(&[Fixed::from(u64::MAX); 3]));
+        assert_eq!(Fixed::ZERO, coefficient_of_variation(&[Fixed::ZERO; 3]));
+        // mean 2, relative deviations of -0.5 and 0.5
+        assert_eq!(Fixed::from_f64(0.5), coefficient_of_variation(&[Fixed::from(1), Fixed::from(3)]));
+    }
+}
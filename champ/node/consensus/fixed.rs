@@ -0,0 +1,306 @@
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+const FRACTION_DIGITS: usize = 9;
+const SCALE: i128 = 1_000_000_000;
+// log10(2) rounded to 9 digits
+const LOG10_2: Fixed = Fixed(301_029_996);
+
+/// A signed decimal fixed-point number with 9 fractional digits, used to calculate voting power.
+///
+/// Floating point results of `log10`, `sqrt` and `powi` can differ in the last bit between CPUs and compilers,
+/// which would let nodes disagree about a quorum. All operations on `Fixed` only use integer arithmetic.
+///
+/// Rounding is the same for every operation: its result is truncated towards zero to 9 digits, and
+/// results that do not fit are saturated at `Fixed::MAX` / `Fixed::MIN` instead of overflowing.
+/// Since every step of a calculation is truncated, a composed result is not truncated as a whole and can be
+/// below the exact value, e.g. `sqrt(3) - 4` is `-2.267949193` instead of `-2.2679491924...`.
+/// Dividing by zero saturates in the direction of the dividend, like a float division would go to infinity.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(i128);
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed(0);
+    pub const ONE: Fixed = Fixed(SCALE);
+    pub const MAX: Fixed = Fixed(i128::MAX);
+    pub const MIN: Fixed = Fixed(i128::MIN);
+
+    /// Creates a number from its scaled representation, where 1 is `1_000_000_000`
+    pub const fn from_raw(raw: i128) -> Self {
+        Self(raw)
+    }
+
+    pub const fn raw(&self) -> i128 {
+        self.0
+    }
+
+    pub fn from_int(value: i128) -> Self {
+        Self(value.saturating_mul(SCALE))
+    }
+
+    /// Converts a float to the nearest fixed-point number.
+    ///
+    /// This is only used for parameters from the chain spec. A multiplication and rounding are exactly
+    /// specified by IEEE 754, so the conversion is the same on every node.
+    pub fn from_f64(value: f64) -> Self {
+        // `as` saturates and maps NaN to 0
+        Self((value * SCALE as f64).round() as i128)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        (self.0 / SCALE) as f64 + (self.0 % SCALE) as f64 / SCALE as f64
+    }
+
+    /// The integer part of the number
+    pub fn trunc(self) -> i128 {
+        self.0 / SCALE
+    }
+
+    /// The integer part of the number, clamped to the range of `u64`
+    pub fn to_u64(self) -> u64 {
+        self.trunc().clamp(0, u64::MAX as i128) as u64
+    }
+
+    /// Raises the number to an integer power by repeated squaring
+    pub fn powi(self, exponent: u32) -> Self {
+        let mut result = Fixed::ONE;
+        let mut base = self;
+        let mut exponent = exponent;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result * base;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = base * base;
+            }
+        }
+        result
+    }
+
+    /// Square root, truncated. Negative numbers have a root of 0.
+    pub fn sqrt(self) -> Self {
+        if self.0 <= 0 {
+            return Fixed::ZERO;
+        }
+
+        match self.0.checked_mul(SCALE) {
+            Some(scaled) => Self(isqrt(scaled as u128) as i128),
+            // numbers above 10^29 lose the fraction of their root
+            None => Self(isqrt((self.0 / SCALE) as u128) as i128 * SCALE),
+        }
+    }
+
+    /// Logarithm to base 10. Numbers that are not positive saturate at `Fixed::MIN`.
+    ///
+    /// The binary logarithm is calculated bit by bit, which is exact to about 8 digits.
+    pub fn log10(self) -> Self {
+        if self.0 <= 0 {
+            return Fixed::MIN;
+        }
+
+        // move the number into [1, 2) and count the integer part of the binary logarithm
+        let mut x = self.0;
+        let mut log2 = 0i128;
+        while x >= 2 * SCALE {
+            x /= 2;
+            log2 += 1;
+        }
+        while x < SCALE {
+            x *= 2;
+            log2 -= 1;
+        }
+
+        // every squaring of a number in [1, 2) shifts out one bit of its logarithm
+        let mut result = Fixed::from_int(log2);
+        let mut bit = SCALE / 2;
+        while bit > 0 {
+            x = x * x / SCALE;
+            if x >= 2 * SCALE {
+                x /= 2;
+                result.0 += bit;
+            }
+            bit /= 2;
+        }
+
+        result * LOG10_2
+    }
+}
+
+/// Integer square root, rounded down
+fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+
+    // Newton's method, starting above the root so it decreases monotonically
+    let mut x = 1u128 << ((128 - n.leading_zeros()) / 2 + 1);
+    loop {
+        let y = (x + n / x) / 2;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+impl From<u64> for Fixed {
+    fn from(value: u64) -> Self {
+        Self(value as i128 * SCALE)
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+    fn add(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+    fn sub(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+    fn neg(self) -> Fixed {
+        Fixed(self.0.saturating_neg())
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Fixed;
+    fn mul(self, rhs: Fixed) -> Fixed {
+        let (a, b) = (self.0, rhs.0);
+        // a * b / SCALE = q * b + r * b / SCALE, where only the second part has to be truncated
+        let (q, r) = (a / SCALE, a % SCALE);
+        let fraction = match r.checked_mul(b) {
+            Some(product) => product / SCALE,
+            None => (b / SCALE) * r + (b % SCALE) * r / SCALE,
+        };
+        Fixed(q.saturating_mul(b).saturating_add(fraction))
+    }
+}
+
+impl Div for Fixed {
+    type Output = Fixed;
+    fn div(self, rhs: Fixed) -> Fixed {
+        let (a, b) = (self.0, rhs.0);
+        if b == 0 {
+            return match a.signum() {
+                1 => Fixed::MAX,
+                -1 => Fixed::MIN,
+                _ => Fixed::ZERO,
+            };
+        }
+
+        // a * SCALE / b = q * SCALE + r * SCALE / b, where only the second part has to be truncated
+        let (q, r) = (a / b, a % b);
+        let fraction = match r.checked_mul(SCALE) {
+            Some(product) => product / b,
+            // divisors above 10^20 lose precision
+            None => r / (b / SCALE),
+        };
+        Fixed(q.saturating_mul(SCALE).saturating_add(fraction))
+    }
+}
+
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 {
+            "-"
+        } else {
+            ""
+        };
+        let integer = (self.0 / SCALE).unsigned_abs();
+        let fraction = (self.0 % SCALE).unsigned_abs();
+
+        if fraction == 0 {
+            return write!(f, "{sign}{integer}");
+        }
+
+        let fraction = format!("{fraction:0width$}", width = FRACTION_DIGITS);
+        write!(f, "{sign}{integer}.{}", fraction.trim_end_matches('0'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arithmetic() {
+        let a = Fixed::from_f64(2.5);
+        let b = Fixed::from(4);
+        assert_eq!(Fixed::from_f64(6.5), a + b);
+        assert_eq!(Fixed::from_f64(-1.5), a - b);
+        assert_eq!(Fixed::from(10), a * b);
+        assert_eq!(Fixed::from_f64(0.625), a / b);
+        assert_eq!(Fixed::from_raw(-2_500_000_000), -a);
+        assert_eq!(Fixed::from(16), b.powi(2));
+        assert_eq!(Fixed::ONE, a.powi(0));
+    }
+
+    #[test]
+    fn test_rounding() {
+        // results are truncated towards zero
+        let third = Fixed::ONE / Fixed::from(3);
+        assert_eq!(Fixed::from_raw(333_333_333), third);
+        assert_eq!(Fixed::from_raw(-333_333_333), -Fixed::ONE / Fixed::from(3));
+        assert_eq!(Fixed::from_raw(999_999_999), third * Fixed::from(3));
+        assert_eq!(Fixed::from_raw(-1), Fixed::from_raw(-1_999_999_999) * Fixed::from_raw(1));
+        assert_eq!(0, Fixed::from_raw(-999_999_999).trunc());
+        assert_eq!(0, Fixed::from_f64(-5.0).to_u64());
+        assert_eq!(5, Fixed::from_f64(5.999).to_u64());
+
+        // only each step is truncated, so a negative composed result can be further from zero than the exact one
+        let composed = Fixed::from(3).sqrt() - Fixed::from(4);
+        assert_eq!(Fixed::from_raw(-2_267_949_193), composed);
+        assert!(composed.to_f64() < 3f64.sqrt() - 4.0);
+    }
+
+    #[test]
+    fn test_saturation() {
+        assert_eq!(Fixed::MAX, Fixed::ONE / Fixed::ZERO);
+        assert_eq!(Fixed::MIN, -Fixed::ONE / Fixed::ZERO);
+        assert_eq!(Fixed::ZERO, Fixed::ZERO / Fixed::ZERO);
+        assert_eq!(Fixed::MAX, Fixed::MAX + Fixed::ONE);
+        assert_eq!(Fixed::MAX, Fixed::from(u64::MAX).powi(3));
+        assert_eq!(u64::MAX, Fixed::MAX.to_u64());
+
+        // large numbers are multiplied without overflowing the intermediate result
+        let large = Fixed::from_raw(10i128.pow(37));
+        assert_eq!(Fixed::from_raw(15 * 10i128.pow(36)), Fixed::from_f64(1.5) * large);
+        assert_eq!(Fixed::from(1 << 61), Fixed::from(1 << 63) / Fixed::from(4));
+    }
+
+    #[test]
+    fn test_sqrt() {
+        assert_eq!(Fixed::from(3), Fixed::from(9).sqrt());
+        assert_eq!(Fixed::from_raw(1_414_213_562), Fixed::from(2).sqrt());
+        assert_eq!(Fixed::ZERO, Fixed::from_f64(-4.0).sqrt());
+        assert_eq!(Fixed::from(316_227_766_016_837), Fixed::from_raw(10i128.pow(38)).sqrt());
+    }
+
+    #[test]
+    fn test_log10() {
+        assert_eq!(Fixed::ZERO, Fixed::ONE.log10());
+        assert_eq!(Fixed::MIN, Fixed::ZERO.log10());
+        for (value, expected) in [(10.0, 1.0), (1000.0, 3.0), (2.0, 0.30103), (0.5, -0.30103), (123456.0, 5.09151)] {
+            let log = Fixed::from_f64(value).log10().to_f64();
+            assert!((log - expected).abs() < 0.00001, "log10({value}) = {log}");
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("1000", Fixed::from(1000).to_string());
+        assert_eq!("0", Fixed::ZERO.to_string());
+        assert_eq!("-2.5", Fixed::from_f64(-2.5).to_string());
+        assert_eq!("0.000000001", Fixed::from_raw(1).to_string());
+        assert_eq!("-0.1", Fixed::from_f64(-0.1).to_string());
+    }
+}
@@ -1,33 +1,32 @@
-use pog_proto::api::SignedBlock;
-
-// so we can normalize the curves
-const TX_CURVE_MAX: i32 = 15;
-const PLATEAU_SIZE: f64 = 175.0;
-// 0.1 = 10%
-const INACTIVE_TAX_PERCENTAGE: f64 = 0.1;
-const INACTIVE_TAX_BOUND: f64 = 0.1;
+//! The graphs an account's importance is made of.
+//!
+//! Every node has to calculate the same voting power, so the graphs use the deterministic `Fixed` type
+//! instead of floats. Parameters from the chain spec are converted with `Fixed::from_f64`.
 
-const NORMALIZE_BALANCE: f64 = 1.0;
-const NORMALIZE_CASHFLOW: f64 = 1.0;
-const NORMALIZE_INACTIVE_TAX: f64 = 1.0;
-const NORMALIZE_BLOCK: f64 = 10.0;
-const NORMALIZE_AGE: f64 = 1.0;
+use super::fixed::Fixed;
+use super::parameters::GraphParameters;
+use pog_proto::api::SignedBlock;
 
-const WEEK_IN_SECONDS: f64 = 60.0 * 60.0 * 24.0 * 7.0;
+const WEEK_IN_SECONDS: u64 = 60 * 60 * 24 * 7;
 
-pub fn balance_graph(balance: u64) -> f64 {
-    balance as f64 / NORMALIZE_BALANCE
+pub fn balance_graph(balance: u64, params: &GraphParameters) -> Fixed {
+    Fixed::from(balance) / Fixed::from_f64(params.normalize_balance)
 }
 
-pub fn cashflow_graph(new_block_balance: u64, old_block_balance: u64) -> f64 {
+pub fn cashflow_graph(new_block_balance: u64, old_block_balance: u64, params: &GraphParameters) -> Fixed {
     let cashflow = new_block_balance as i128 - old_block_balance as i128;
 
-    -cashflow as f64 / NORMALIZE_CASHFLOW
+    -Fixed::from_int(cashflow) / Fixed::from_f64(params.normalize_cashflow)
 }
 
-pub fn inactive_tax_graph(new_block_balance: u64, old_block_balance: u64, net_importance: f64) -> f64 {
+pub fn inactive_tax_graph(
+    new_block_balance: u64,
+    old_block_balance: u64,
+    net_importance: Fixed,
+    params: &GraphParameters,
+) -> Fixed {
     let cashflow = new_block_balance as i128 - old_block_balance as i128;
-    let percentage_buffer = (new_block_balance as f64 * INACTIVE_TAX_BOUND) as i128;
+    let percentage_buffer = (Fixed::from(new_block_balance) * Fixed::from_f64(params.inactive_tax_bound)).trunc();
 
     let upperbound = percentage_buffer;
     let lowerbound = -percentage_buffer;
@@ -35,43 +34,49 @@ pub fn inactive_tax_graph(new_block_balance: u64, old_block_balance: u64, net_im
     tracing::trace!("real cashflow={}", cashflow);
     // Inactive Tax
     if cashflow < upperbound && cashflow > lowerbound && new_block_balance > 0 {
-        return -(net_importance * INACTIVE_TAX_PERCENTAGE) * NORMALIZE_INACTIVE_TAX;
+        return -(net_importance * Fixed::from_f64(params.inactive_tax_percentage))
+            * Fixed::from_f64(params.normalize_inactive_tax);
     }
-    0.0
+    Fixed::ZERO
 }
 
-pub fn block_graph(block_height: u64, new_block: &SignedBlock, old_block: Option<&SignedBlock>) -> f64 {
+pub fn block_graph(
+    block_height: u64,
+    new_block: &SignedBlock,
+    old_block: Option<&SignedBlock>,
+    params: &GraphParameters,
+) -> Fixed {
     let old_block_time = match old_block {
         Some(b) => b.header.timestamp,
         None => new_block.header.timestamp,
     };
     // to get the time between the first and most recent block
     // we need the minimum to not give too high power from the start
-    let time = if new_block.header.timestamp - old_block_time < WEEK_IN_SECONDS as u64 {
-        WEEK_IN_SECONDS
-    } else {
-        (new_block.header.timestamp - old_block_time) as f64
-    };
+    let time = new_block.header.timestamp.saturating_sub(old_block_time).max(WEEK_IN_SECONDS);
 
     // x is the nr of tx based on the account life in weeks
     // https://www.geogebra.org/calculator/ymkv5ew6
-    let blocks_per_week = (time / block_height as f64) / WEEK_IN_SECONDS;
+    let blocks_per_week = Fixed::from(time) / (Fixed::from(block_height) * Fixed::from(WEEK_IN_SECONDS));
+    let x = blocks_per_week / Fixed::from_f64(params.plateau_size) - Fixed::ONE;
     // this is between 0 and 1 where plateau starts at 0.5
-    let graph_result = 1.0 / (blocks_per_week / PLATEAU_SIZE - 1.0).powi(2 * TX_CURVE_MAX) + 1.0;
+    // 1 / x^n is calculated as (1 / x)^n, so it saturates instead of losing all precision for small x
+    let graph_result = (Fixed::ONE / x).powi(2 * params.tx_curve_max) + Fixed::ONE;
     // to normalize tx graph and balance graph
-    graph_result * NORMALIZE_BLOCK
+    graph_result * Fixed::from_f64(params.normalize_block)
 }
 
-pub fn age_graph(account_age: u64) -> f64 {
+pub fn age_graph(account_age: u64, params: &GraphParameters) -> Fixed {
     // x is the account age in weeks
     // starts with negative power but increases at around 1 month
     // slowly increases steadily
     // x + 1 to avoid log0
     // 0.1x + 3 to allow the graph to go through 31 (month ish)
     // - 4 to shift the start
-    let account_age_weeks = (account_age as f64 / WEEK_IN_SECONDS).floor();
-    let graph_result = (account_age_weeks + 1.0).log10() + (0.1 * account_age_weeks + 3.0).sqrt() - 4.0;
-    graph_result * NORMALIZE_AGE
+    let account_age_weeks = Fixed::from(account_age / WEEK_IN_SECONDS);
+    let graph_result = (account_age_weeks + Fixed::ONE).log10()
+        + (account_age_weeks / Fixed::from(10) + Fixed::from(3)).sqrt()
+        - Fixed::from(4);
+    graph_result * Fixed::from_f64(params.normalize_age)
 }
 
 #[cfg(test)]
@@ -79,91 +84,196 @@ mod tests {
     use insta::assert_yaml_snapshot;
     use pog_proto::api::{BlockData, BlockHeader, SignedBlock};
 
+    use crate::consensus::fixed::Fixed;
     use crate::consensus::graphs::{age_graph, balance_graph, block_graph, cashflow_graph, inactive_tax_graph};
+    use crate::consensus::parameters::GraphParameters;
 
-    #[test]
-    fn test_balance_graph() {
-        assert_eq!(1000.0, balance_graph(1000));
-        assert_eq!(5.0, balance_graph(5));
-    }
-    #[test]
-    fn test_cashflow_graph() {
-        assert_eq!(500.0, cashflow_graph(500, 1000));
-        assert_eq!(-500.0, cashflow_graph(1000, 500));
-        assert_eq!(0.0, cashflow_graph(1000, 1000));
-    }
-    #[test]
-    fn test_inactive_tax_graph() {
-        assert_eq!(0.0, inactive_tax_graph(500, 1000, 1000.0));
-        assert_eq!(-50.0, inactive_tax_graph(1000, 1000, 500.0));
+    /// The float implementation the graphs were first written with, to cross-check the fixed-point results
+    mod float {
+        use crate::consensus::parameters::GraphParameters;
+        use pog_proto::api::SignedBlock;
+
+        const WEEK_IN_SECONDS: f64 = 60.0 * 60.0 * 24.0 * 7.0;
+
+        pub fn balance_graph(balance: u64, params: &GraphParameters) -> f64 {
+            balance as f64 / params.normalize_balance
+        }
+
+        pub fn cashflow_graph(new_block_balance: u64, old_block_balance: u64, params: &GraphParameters) -> f64 {
+            let cashflow = new_block_balance as i128 - old_block_balance as i128;
+            -cashflow as f64 / params.normalize_cashflow
+        }
+
+        pub fn inactive_tax_graph(new: u64, old: u64, net_importance: f64, params: &GraphParameters) -> f64 {
+            let cashflow = new as i128 - old as i128;
+            let percentage_buffer = (new as f64 * params.inactive_tax_bound) as i128;
+            if cashflow < percentage_buffer && cashflow > -percentage_buffer && new > 0 {
+                return -(net_importance * params.inactive_tax_percentage) * params.normalize_inactive_tax;
+            }
+            0.0
+        }
+
+        pub fn block_graph(
+            height: u64,
+            new: &SignedBlock,
+            old: Option<&SignedBlock>,
+            params: &GraphParameters,
+        ) -> f64 {
+            let old_block_time = old.map_or(new.header.timestamp, |b| b.header.timestamp);
+            let time = if new.header.timestamp - old_block_time < WEEK_IN_SECONDS as u64 {
+                WEEK_IN_SECONDS
+            } else {
+                (new.header.timestamp - old_block_time) as f64
+            };
+            let blocks_per_week = (time / height as f64) / WEEK_IN_SECONDS;
+            let graph_result =
+                1.0 / (blocks_per_week / params.plateau_size - 1.0).powi(2 * params.tx_curve_max as i32) + 1.0;
+            graph_result * params.normalize_block
+        }
+
+        pub fn age_graph(account_age: u64, params: &GraphParameters) -> f64 {
+            let account_age_weeks = (account_age as f64 / WEEK_IN_SECONDS).floor();
+            let graph_result = (account_age_weeks + 1.0).log10() + (0.1 * account_age_weeks + 3.0).sqrt() - 4.0;
+            graph_result * params.normalize_age
+        }
     }
-    #[test]
-    fn test_block_graph() {
-        let new_block = SignedBlock::new(
+
+    fn block(timestamp: u64, height: u64) -> SignedBlock {
+        SignedBlock::new(
             BlockHeader {
                 signature: b"signature".to_vec(),
                 public_key: b"public_key".to_vec(),
-                timestamp: 100_000,
+                timestamp,
             },
             BlockData {
                 version: 0,
                 signature_type: 1,
                 balance: 1000,
-                height: 20,
+                height,
                 previous: b"previous".to_vec(),
                 transactions: vec![],
             },
-        );
+        )
+    }
 
-        let old_block = SignedBlock::new(
-            BlockHeader {
-                signature: b"signature".to_vec(),
-                public_key: b"public_key".to_vec(),
-                timestamp: 50_000,
-            },
-            BlockData {
-                version: 0,
-                signature_type: 1,
-                balance: 500,
-                height: 19,
-                previous: b"other_previous".to_vec(),
-                transactions: vec![],
-            },
-        );
+    /// Asserts that a fixed-point result matches the float result up to the rounding of 9 digits
+    fn assert_close(fixed: Fixed, float: f64) {
+        let tolerance = (float.abs() * 1e-7).max(1e-7);
+        assert!((fixed.to_f64() - float).abs() <= tolerance, "fixed {fixed} does not match float {float}");
+    }
+
+    #[test]
+    fn test_balance_graph() {
+        let params = GraphParameters::default();
+        assert_eq!(Fixed::from(1000), balance_graph(1000, &params));
+        assert_eq!(Fixed::from(5), balance_graph(5, &params));
+    }
+    #[test]
+    fn test_cashflow_graph() {
+        let params = GraphParameters::default();
+        assert_eq!(Fixed::from(500), cashflow_graph(500, 1000, &params));
+        assert_eq!(-Fixed::from(500), cashflow_graph(1000, 500, &params));
+        assert_eq!(Fixed::ZERO, cashflow_graph(1000, 1000, &params));
+    }
+    #[test]
+    fn test_inactive_tax_graph() {
+        let params = GraphParameters::default();
+        assert_eq!(Fixed::ZERO, inactive_tax_graph(500, 1000, Fixed::from(1000), &params));
+        assert_eq!(-Fixed::from(50), inactive_tax_graph(1000, 1000, Fixed::from(500), &params));
+    }
+    #[test]
+    fn test_block_graph() {
+        let params = GraphParameters::default();
+        let new_block = block(100_000, 20);
+        let old_block = block(50_000, 19);
 
         assert_eq!(
             (20.17295623738592 * 100_000_f64) as u64,
-            (block_graph(10, &new_block, Some(&old_block)) * 100_000_f64) as u64
+            (block_graph(10, &new_block, Some(&old_block), &params).to_f64() * 100_000_f64) as u64
         );
+        // blocks without a height count as an infinite time per block
+        assert_eq!(Fixed::from(10), block_graph(0, &new_block, None, &params));
     }
     #[test]
     fn test_age_graph() {
-        assert_eq!((-2.267949192431123 * 100_000_f64) as u64, (age_graph(100_000) * 100_000_f64) as u64);
-        assert_eq!((2.635988521203979 * 100_000_f64) as u64, (age_graph(100_000_000) * 100_000_f64) as u64);
+        let params = GraphParameters::default();
+        assert_eq!(
+            (-2.267949192431123 * 100_000_f64) as u64,
+            (age_graph(100_000, &params).to_f64() * 100_000_f64) as u64
+        );
+        assert_eq!(
+            (2.635988521203979 * 100_000_f64) as u64,
+            (age_graph(100_000_000, &params).to_f64() * 100_000_f64) as u64
+        );
+    }
+    #[test]
+    fn test_custom_parameters() {
+        let params = GraphParameters {
+            normalize_balance: 10.0,
+            inactive_tax_percentage: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(Fixed::from(100), balance_graph(1000, &params));
+        assert_eq!(-Fixed::from(250), inactive_tax_graph(1000, 1000, Fixed::from(500), &params));
+    }
+    #[test]
+    fn test_cross_check_float() {
+        let params = GraphParameters::default();
+        let balances = [0, 1, 999, 1000, 1009, 1100, 123_456_789, 40_000_000_000_000];
+
+        for new in balances {
+            assert_close(balance_graph(new, &params), float::balance_graph(new, &params));
+            for old in balances {
+                assert_close(cashflow_graph(new, old, &params), float::cashflow_graph(new, old, &params));
+                for net in [0.0, 1.5, 1000.0, 123_456.789] {
+                    assert_close(
+                        inactive_tax_graph(new, old, Fixed::from_f64(net), &params),
+                        float::inactive_tax_graph(new, old, net, &params),
+                    );
+                }
+            }
+        }
+
+        let week = 60 * 60 * 24 * 7;
+        for age in [0, 1, week - 1, week, 4 * week, 100_000_000, 52 * week, 10 * 52 * week + 17] {
+            assert_close(age_graph(age, &params), float::age_graph(age, &params));
+        }
+
+        for time in [0, 60 * 60, week, 30 * 24 * 60 * 60, 52 * week] {
+            for height in [1, 2, 10, 100, 1000, 100_000] {
+                let new_block = block(1_650_000_000 + time, height);
+                let old_block = block(1_650_000_000, height - 1);
+                assert_close(
+                    block_graph(height, &new_block, Some(&old_block), &params),
+                    float::block_graph(height, &new_block, Some(&old_block), &params),
+                );
+            }
+        }
     }
     #[test]
     fn test_snapshots() {
+        let params = GraphParameters::default();
         assert_yaml_snapshot!(vec![
-            balance_graph(1000).to_string(),
-            balance_graph(0).to_string(),
-            balance_graph(2500).to_string()
+            balance_graph(1000, &params).to_string(),
+            balance_graph(0, &params).to_string(),
+            balance_graph(2500, &params).to_string()
         ]);
         assert_yaml_snapshot!(vec![
-            cashflow_graph(500, 1000).to_string(),
-            cashflow_graph(1000, 1000).to_string(),
-            cashflow_graph(0, 0).to_string()
+            cashflow_graph(500, 1000, &params).to_string(),
+            cashflow_graph(1000, 1000, &params).to_string(),
+            cashflow_graph(0, 0, &params).to_string()
         ]);
         assert_yaml_snapshot!(vec![
-            inactive_tax_graph(1000, 1000, 500.0).to_string(),
-            inactive_tax_graph(0, 0, 0.0).to_string(),
-            inactive_tax_graph(1000, 1500, 1000.0).to_string(),
-            inactive_tax_graph(1000, 1100, 1000.0).to_string(),
-            inactive_tax_graph(1000, 1009, 1000.0).to_string()
+            inactive_tax_graph(1000, 1000, Fixed::from(500), &params).to_string(),
+            inactive_tax_graph(0, 0, Fixed::ZERO, &params).to_string(),
+            inactive_tax_graph(1000, 1500, Fixed::from(1000), &params).to_string(),
+            inactive_tax_graph(1000, 1100, Fixed::from(1000), &params).to_string(),
+            inactive_tax_graph(1000, 1009, Fixed::from(1000), &params).to_string()
         ]);
         assert_yaml_snapshot!(vec![
-            age_graph(605000).to_string(),
-            age_graph(100_000_000).to_string(),
-            age_graph(0).to_string()
+            age_graph(605000, &params).to_string(),
+            age_graph(100_000_000, &params).to_string(),
+            age_graph(0, &params).to_string()
         ]);
     }
 }
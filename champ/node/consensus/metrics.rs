@@ -0,0 +1,31 @@
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter, register_int_gauge};
+
+use super::network_power::NetworkPowerSnapshot;
+
+lazy_static! {
+    static ref VOTING_POWER_CACHE_HITS: prometheus::IntCounter =
+        register_int_counter!("voting_power_cache_hits", "voting power cache hits").unwrap();
+    static ref VOTING_POWER_CACHE_MISSES: prometheus::IntCounter =
+        register_int_counter!("voting_power_cache_misses", "voting power cache misses").unwrap();
+    static ref NETWORK_POWER_EPOCH: prometheus::IntGauge =
+        register_int_gauge!("network_power_epoch", "epoch of the network power").unwrap();
+    static ref NETWORK_POWER_TOTAL: prometheus::IntGauge =
+        register_int_gauge!("network_power_total", "voting power of all prime delegates").unwrap();
+    static ref NETWORK_POWER_ONLINE: prometheus::IntGauge =
+        register_int_gauge!("network_power_online", "voting power of online prime delegates").unwrap();
+}
+
+pub fn cache_hit() {
+    VOTING_POWER_CACHE_HITS.inc();
+}
+
+pub fn cache_miss() {
+    VOTING_POWER_CACHE_MISSES.inc();
+}
+
+pub fn set_network_power(snapshot: &NetworkPowerSnapshot) {
+    NETWORK_POWER_EPOCH.set(snapshot.epoch as i64);
+    NETWORK_POWER_TOTAL.set(snapshot.total_power as i64);
+    NETWORK_POWER_ONLINE.set(snapshot.online_power as i64);
+}
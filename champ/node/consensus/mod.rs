@@ -1,2 +1,12 @@
+pub mod cache;
+pub mod epoch_power;
+pub mod equivocation;
+pub mod farming;
+pub mod fixed;
 pub mod graphs;
+mod metrics;
+pub mod network_power;
+pub mod parameters;
+pub mod prime_delegates;
+pub mod simulation;
 pub mod voting_power;
@@ -0,0 +1,69 @@
+use std::sync::RwLock;
+
+use super::metrics;
+
+/// Voting power of the network during an epoch
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NetworkPowerSnapshot {
+    pub epoch: u64,
+    /// power of all prime delegates
+    pub total_power: u64,
+    /// power of the prime delegates that are currently online
+    pub online_power: u64,
+}
+
+impl NetworkPowerSnapshot {
+    /// Sums up the power of each prime delegate and whether it is online
+    pub fn new(epoch: u64, delegates: &[(u64, bool)]) -> Self {
+        Self {
+            epoch,
+            total_power: delegates.iter().map(|(power, _)| power).sum(),
+            online_power: delegates.iter().filter(|(_, online)| *online).map(|(power, _)| power).sum(),
+        }
+    }
+}
+
+/// The network power of the current epoch, calculated by the p2p server
+#[derive(Debug, Default)]
+pub struct NetworkPower {
+    snapshot: RwLock<Option<NetworkPowerSnapshot>>,
+}
+
+impl NetworkPower {
+    pub fn get(&self) -> Option<NetworkPowerSnapshot> {
+        *self.snapshot.read().expect("network power lock is poisoned")
+    }
+
+    pub fn set(&self, snapshot: NetworkPowerSnapshot) {
+        metrics::set_network_power(&snapshot);
+        *self.snapshot.write().expect("network power lock is poisoned") = Some(snapshot);
+    }
+
+    /// Epoch the network power was last calculated for
+    pub fn epoch(&self) -> Option<u64> {
+        self.get().map(|snapshot| snapshot.epoch)
+    }
+
+    /// Gets the voting power of the network that is currently online
+    pub fn online_power(&self) -> f64 {
+        self.get().map_or(0.0, |snapshot| snapshot.online_power as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_power() {
+        let network_power = NetworkPower::default();
+        assert_eq!(None, network_power.epoch());
+        assert_eq!(None, network_power.get());
+        assert_eq!(0.0, network_power.online_power());
+
+        network_power.set(NetworkPowerSnapshot::new(3, &[(100, true), (50, false), (20, true)]));
+        assert_eq!(Some(3), network_power.epoch());
+        assert_eq!(Some(170), network_power.get().map(|snapshot| snapshot.total_power));
+        assert_eq!(120.0, network_power.online_power());
+    }
+}
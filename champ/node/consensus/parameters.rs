@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+/// Consensus parameters of a chain, loaded from its chain spec
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ConsensusParameters {
+    // To balance each graph
+    pub block_weight: f64,
+    pub balance_weight: f64,
+    pub cashflow_weight: f64,
+    pub age_weight: f64,
+    pub inactive_tax_weight: f64,
+
+    /// maximum share of the total network power a single account can have
+    pub max_network_power: f64,
+
+    /// maximum number of prime delegates elected each epoch
+    pub max_prime_delegates: usize,
+    /// prime delegates are elected until they hold this share of the voting power of all accounts
+    pub prime_delegate_power_threshold: f64,
+
+    /// the balance of the last block before this many seconds is compared to the current balance
+    pub lookback_range: u64,
+    /// blocks older than this many seconds are not used for the comparison
+    pub max_lookback_range: u64,
+
+    /// seconds the power of a voter that signed votes for competing blocks is excluded from quorum
+    pub equivocation_exclusion_period: u64,
+
+    pub graphs: GraphParameters,
+    pub farming: FarmingParameters,
+}
+
+impl Default for ConsensusParameters {
+    fn default() -> Self {
+        Self {
+            block_weight: 1.2,
+            balance_weight: 0.75,
+            cashflow_weight: 1.0,
+            age_weight: 1.0,
+            inactive_tax_weight: 1.0,
+            max_network_power: 0.3,
+            max_prime_delegates: 100,
+            prime_delegate_power_threshold: 0.9,
+            // a month
+            lookback_range: 60 * 60 * 24 * 30,
+            // two months
+            max_lookback_range: 60 * 60 * 24 * 30 * 2,
+            // a week
+            equivocation_exclusion_period: 60 * 60 * 24 * 7,
+            graphs: GraphParameters::default(),
+            farming: FarmingParameters::default(),
+        }
+    }
+}
+
+/// Shape of the curves in `consensus::graphs`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct GraphParameters {
+    /// steepness of the block graph around its plateau
+    pub tx_curve_max: u32,
+    /// blocks per week at which the block graph plateaus
+    pub plateau_size: f64,
+    /// share of the importance inactive accounts lose (0.1 = 10%)
+    pub inactive_tax_percentage: f64,
+    /// accounts whose balance changed less than this share are inactive
+    pub inactive_tax_bound: f64,
+
+    // so we can normalize the curves
+    pub normalize_balance: f64,
+    pub normalize_cashflow: f64,
+    pub normalize_inactive_tax: f64,
+    pub normalize_block: f64,
+    pub normalize_age: f64,
+}
+
+impl Default for GraphParameters {
+    fn default() -> Self {
+        Self {
+            tx_curve_max: 15,
+            plateau_size: 175.0,
+            inactive_tax_percentage: 0.1,
+            inactive_tax_bound: 0.1,
+            normalize_balance: 1.0,
+            normalize_cashflow: 1.0,
+            normalize_inactive_tax: 1.0,
+            normalize_block: 10.0,
+            normalize_age: 1.0,
+        }
+    }
+}
+
+/// Detection of importance farming wallets in `consensus::farming`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct FarmingParameters {
+    /// reduce the power flagged accounts delegate to their representative
+    pub discount_delegated_power: bool,
+    /// accounts with an average score of at least this are flagged
+    pub flag_threshold: f64,
+    /// share of the delegated power flagged accounts lose
+    pub discount: f64,
+    /// delegations within this many seconds of the first block are young
+    pub young_delegation_age: u64,
+    /// balance changes needed before transactions are checked for uniformity
+    pub min_transactions: usize,
+}
+
+impl Default for FarmingParameters {
+    fn default() -> Self {
+        Self {
+            discount_delegated_power: false,
+            flag_threshold: 0.75,
+            discount: 0.5,
+            // four weeks
+            young_delegation_age: 60 * 60 * 24 * 28,
+            min_transactions: 4,
+        }
+    }
+}
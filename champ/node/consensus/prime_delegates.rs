@@ -0,0 +1,150 @@
+use std::sync::RwLock;
+
+use anyhow::Result;
+use pog_proto::api::AccountID;
+
+use super::{epoch_power::PowerSnapshot, parameters::ConsensusParameters, voting_power};
+use crate::state::ChampStateArc;
+
+/// The prime delegates elected for the current epoch, with the voting power they were elected with
+#[derive(Debug, Default)]
+pub struct PrimeDelegates {
+    elected: RwLock<Option<(u64, Vec<(AccountID, u64)>)>>,
+}
+
+impl PrimeDelegates {
+    pub fn get(&self) -> Vec<(AccountID, u64)> {
+        self.elected.read().expect("prime delegates lock is poisoned").as_ref().map_or(vec![], |(_, d)| d.clone())
+    }
+
+    pub fn set(&self, epoch: u64, delegates: Vec<(AccountID, u64)>) {
+        *self.elected.write().expect("prime delegates lock is poisoned") = Some((epoch, delegates));
+    }
+
+    /// Epoch the prime delegates were last elected for
+    pub fn epoch(&self) -> Option<u64> {
+        self.elected.read().expect("prime delegates lock is poisoned").as_ref().map(|(epoch, _)| *epoch)
+    }
+
+    pub fn contains(&self, account_id: &AccountID) -> bool {
+        self.elected
+            .read()
+            .expect("prime delegates lock is poisoned")
+            .as_ref()
+            .map_or(false, |(_, delegates)| delegates.iter().any(|(account, _)| account == account_id))
+    }
+}
+
+/// Elects the prime delegates of an epoch from its voting power snapshot and persists them.
+///
+/// The snapshot only counts blocks from before the epoch started, so every node elects the same set no matter when
+/// it does so or which newer blocks it has.
+pub async fn elect(state: &ChampStateArc, snapshot: &PowerSnapshot) -> Result<Vec<(AccountID, u64)>> {
+    let params = state.config.read().await.chain_spec.consensus.clone();
+    let delegates = select(snapshot.powers.clone(), &params);
+    tracing::debug!("elected {} prime delegates for epoch {}", delegates.len(), snapshot.epoch);
+
+    state.db.lock().await.set_prime_delegates(snapshot.epoch, delegates.clone()).await?;
+    state.prime_delegates.set(snapshot.epoch, delegates.clone());
+    Ok(delegates)
+}
+
+/// Ranks accounts by their active voting power and selects the prime delegates.
+///
+/// The power of each account is capped at `max_network_power` of the power of all candidates.
+/// Accounts are selected in order until either `max_prime_delegates` is reached or the selected accounts
+/// hold `prime_delegate_power_threshold` of the power. Ties are broken by the account id so every node
+/// elects the same set.
+pub fn select(candidates: Vec<(AccountID, u64)>, params: &ConsensusParameters) -> Vec<(AccountID, u64)> {
+    let total_power = candidates.iter().fold(0u64, |sum, (_, power)| sum.saturating_add(*power));
+
+    let mut candidates: Vec<(AccountID, u64)> = candidates
+        .into_iter()
+        .map(|(account, power)| (account, voting_power::limit_active_power(power, total_power as f64, params)))
+        .filter(|(_, power)| *power > 0)
+        .collect();
+    candidates.sort_by(|(a, a_power), (b, b_power)| b_power.cmp(a_power).then_with(|| a.cmp(b)));
+
+    let active_power = candidates.iter().fold(0u64, |sum, (_, power)| sum.saturating_add(*power));
+    let power_needed = active_power as f64 * params.prime_delegate_power_threshold;
+
+    let mut elected_power = 0u64;
+    let mut delegates = vec![];
+    for (account, power) in candidates {
+        if delegates.len() >= params.max_prime_delegates || elected_power as f64 >= power_needed {
+            break;
+        }
+        elected_power = elected_power.saturating_add(power);
+        delegates.push((account, power));
+    }
+
+    delegates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ChampState;
+
+    fn params(max_prime_delegates: usize, prime_delegate_power_threshold: f64) -> ConsensusParameters {
+        ConsensusParameters {
+            max_network_power: 1.0,
+            max_prime_delegates,
+            prime_delegate_power_threshold,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let candidates = vec![([1; 24], 100), ([2; 24], 400), ([3; 24], 0), ([4; 24], 300), ([5; 24], 200)];
+
+        // ranked by power, accounts without power are never elected
+        assert_eq!(
+            vec![([2; 24], 400), ([4; 24], 300), ([5; 24], 200), ([1; 24], 100)],
+            select(candidates.clone(), &params(10, 1.0))
+        );
+        // count limit
+        assert_eq!(vec![([2; 24], 400), ([4; 24], 300)], select(candidates.clone(), &params(2, 1.0)));
+        // 400 + 300 reach 70% of the power
+        assert_eq!(vec![([2; 24], 400), ([4; 24], 300)], select(candidates.clone(), &params(10, 0.7)));
+        assert_eq!(3, select(candidates, &params(10, 0.71)).len());
+
+        assert!(select(vec![], &params(10, 1.0)).is_empty());
+    }
+
+    #[test]
+    fn test_select_ties_and_cap() {
+        // equal power is ordered by account id
+        let candidates = vec![([3; 24], 100), ([1; 24], 100), ([2; 24], 100)];
+        assert_eq!(vec![([1; 24], 100), ([2; 24], 100)], select(candidates, &params(2, 1.0)));
+
+        // a single account can only have 30% of the power
+        let candidates = vec![([1; 24], 800), ([2; 24], 100), ([3; 24], 100)];
+        let params = ConsensusParameters {
+            prime_delegate_power_threshold: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(vec![([1; 24], 300), ([2; 24], 100), ([3; 24], 100)], select(candidates, &params));
+    }
+
+    #[tokio::test]
+    async fn test_elect() -> Result<()> {
+        let state = ChampState::mock().await;
+        state.config.write().await.chain_spec.consensus = params(1, 1.0);
+
+        // accounts are ranked by the snapshot, not by the blocks the node has
+        let snapshot = PowerSnapshot::new(5, vec![([1; 24], 1_000), ([2; 24], 40_000_000)]);
+        let delegates = elect(&state, &snapshot).await?;
+        assert_eq!(vec![([2; 24], 40_000_000)], delegates);
+        assert!(state.prime_delegates.contains(&[2; 24]));
+        assert_eq!(Some(5), state.prime_delegates.epoch());
+        assert_eq!(Some((5, delegates.clone())), state.db.lock().await.get_prime_delegates().await?);
+
+        let next = PowerSnapshot::new(6, vec![([1; 24], 40_000_000), ([2; 24], 1_000)]);
+        assert_eq!(vec![([1; 24], 40_000_000)], elect(&state, &next).await?);
+        assert!(!state.prime_delegates.contains(&[2; 24]));
+        assert_eq!(Some(6), state.prime_delegates.epoch());
+        Ok(())
+    }
+}
@@ -0,0 +1,363 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use encoding::account::parse_account_address_string;
+use pog_proto::api::{AccountID, BlockData, BlockHeader, SignedBlock};
+use serde::Deserialize;
+
+use super::parameters::ConsensusParameters;
+use super::voting_power::{limit_active_power, PowerComponents};
+use super::{farming, prime_delegates};
+use crate::state::ChampStateArc;
+
+/// Hypothetical blocks and delegations that are applied on top of the database
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Scenario {
+    #[serde(default)]
+    pub blocks: Vec<ScenarioBlock>,
+    #[serde(default)]
+    pub delegations: Vec<ScenarioDelegation>,
+}
+
+/// A block that is appended to the chain of an account
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ScenarioBlock {
+    pub account: String,
+    pub timestamp: u64,
+    pub balance: u64,
+}
+
+/// Changes the representative of an account. Without a representative, the account stops delegating.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ScenarioDelegation {
+    pub account: String,
+    pub representative: Option<String>,
+}
+
+impl Scenario {
+    pub fn from_toml(scenario: &str) -> Result<Self> {
+        Ok(toml::from_str(scenario)?)
+    }
+}
+
+/// The voting power of an account at a point in time
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatedPower {
+    pub account: AccountID,
+    pub components: PowerComponents,
+    pub actual_power: u64,
+    pub delegated_power: u64,
+    pub active_power: u64,
+}
+
+/// Calculates the voting power of an account, or of all accounts, at `timestamp` as if the scenario had happened.
+///
+/// Blocks after `timestamp` are ignored. The database only knows the current representative of each account,
+/// so delegations are not rewound. The network power is the power of the prime delegates that would be elected.
+pub async fn simulate(
+    state: &ChampStateArc,
+    account: Option<AccountID>,
+    timestamp: u64,
+    scenario: &Scenario,
+) -> Result<Vec<SimulatedPower>> {
+    let params = state.config.read().await.chain_spec.consensus.clone();
+    let mut chains = BTreeMap::new();
+    let mut delegations = HashMap::new();
+
+    {
+        let db = state.db.lock().await;
+        for account in db.get_accounts().await? {
+            let mut blocks = db.get_blocks(true, u32::MAX, 0, Some(account)).await?;
+            blocks.reverse();
+            chains.insert(account, blocks);
+
+            if let Some(representative) = db.get_account_delegate(account).await? {
+                delegations.insert(account, representative);
+            }
+        }
+    }
+
+    let mut blocks = scenario.blocks.clone();
+    blocks.sort_by_key(|block| block.timestamp);
+    for block in blocks {
+        let chain: &mut Vec<SignedBlock> = chains.entry(parse_account_address_string(&block.account)?).or_default();
+        let height = chain.last().map_or(0, |b| b.data.height + 1);
+        chain.push(hypothetical_block(&block, height));
+    }
+
+    for delegation in &scenario.delegations {
+        let account = parse_account_address_string(&delegation.account)?;
+        match &delegation.representative {
+            Some(representative) => delegations.insert(account, parse_account_address_string(representative)?),
+            None => delegations.remove(&account),
+        };
+    }
+
+    Ok(calculate(&params, &chains, &delegations, timestamp, account))
+}
+
+/// Calculates the voting power from the chains of all accounts, ordered from their first to their latest block
+pub fn calculate(
+    params: &ConsensusParameters,
+    chains: &BTreeMap<AccountID, Vec<SignedBlock>>,
+    delegations: &HashMap<AccountID, AccountID>,
+    timestamp: u64,
+    account: Option<AccountID>,
+) -> Vec<SimulatedPower> {
+    let mut actual = BTreeMap::new();
+    for (account_id, chain) in chains {
+        let blocks: Vec<&SignedBlock> = chain.iter().filter(|b| b.header.timestamp <= timestamp).collect();
+        let (first_block, block) = match (blocks.first(), blocks.last()) {
+            (Some(first_block), Some(block)) => (first_block, block),
+            // the account did not exist yet
+            _ => continue,
+        };
+
+        // the same block `get_latest_block_by_account_before` finds in the database
+        let lookback_from = block.header.timestamp.saturating_sub(params.lookback_range);
+        let lookback_limit = block.header.timestamp.saturating_sub(params.max_lookback_range);
+        let old_block = blocks
+            .iter()
+            .rev()
+            .find(|b| b.header.timestamp < lookback_from)
+            .filter(|b| b.header.timestamp >= lookback_limit)
+            .copied();
+
+        let components = PowerComponents::calculate(params, block, old_block, first_block);
+        actual.insert(*account_id, (components, components.actual_power(params)));
+    }
+
+    // flagged farming wallets delegate less power, like in `voting_power::get_delegated_power`
+    let farming_params = &params.farming;
+    let sends = match farming_params.discount_delegated_power {
+        true => farming::sends(chains, timestamp),
+        false => HashMap::new(),
+    };
+
+    let mut delegated: HashMap<AccountID, u64> = HashMap::new();
+    for (delegator, representative) in delegations {
+        if let Some((_, power)) = actual.get(delegator) {
+            let power = match farming_params.discount_delegated_power {
+                true => {
+                    let score = farming::score_chain(&chains[delegator], &sends, timestamp, farming_params);
+                    farming::discount(*power, &score, farming_params)
+                }
+                false => *power,
+            };
+            *delegated.entry(*representative).or_default() += power;
+        }
+    }
+
+    let total_power = |account_id: &AccountID, actual_power: u64| {
+        actual_power + delegated.get(account_id).copied().unwrap_or_default()
+    };
+
+    let candidates = actual.iter().map(|(account_id, (_, power))| (*account_id, total_power(account_id, *power)));
+    let network_power: u64 = prime_delegates::select(candidates.collect(), params).iter().map(|(_, p)| p).sum();
+
+    actual
+        .iter()
+        .filter(|(account_id, _)| account.map_or(true, |account| &account == *account_id))
+        .map(|(account_id, (components, actual_power))| SimulatedPower {
+            account: *account_id,
+            components: *components,
+            actual_power: *actual_power,
+            delegated_power: delegated.get(account_id).copied().unwrap_or_default(),
+            active_power: limit_active_power(total_power(account_id, *actual_power), network_power as f64, params),
+        })
+        .collect()
+}
+
+/// An unsigned block with only the fields the graphs use
+fn hypothetical_block(block: &ScenarioBlock, height: u64) -> SignedBlock {
+    SignedBlock::new(
+        BlockHeader {
+            signature: vec![],
+            public_key: vec![],
+            timestamp: block.timestamp,
+        },
+        BlockData {
+            version: 0,
+            signature_type: 0,
+            balance: block.balance,
+            height,
+            previous: vec![],
+            transactions: vec![],
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus::fixed::Fixed;
+    use crate::fixtures::{account, genesis_block, GENESIS_TIMESTAMP};
+    use crate::state::ChampState;
+    use crypto::signatures::ed25519::generate_private_key;
+    use encoding::zbase32::ToZbase;
+    use pog_proto::api::{
+        transaction::{Data, TxClaim, TxDelegate, TxSend},
+        Transaction,
+    };
+
+    const DAY: u64 = 60 * 60 * 24;
+    const WEEK: u64 = 7 * DAY;
+
+    fn block(timestamp: u64, height: u64, balance: u64) -> SignedBlock {
+        hypothetical_block(
+            &ScenarioBlock {
+                account: String::new(),
+                timestamp,
+                balance,
+            },
+            height,
+        )
+    }
+
+    #[test]
+    fn test_calculate() {
+        let params = ConsensusParameters::default();
+        let mut chains = BTreeMap::new();
+        chains.insert([1; 24], vec![block(0, 0, 1_000_000), block(10 * WEEK, 1, 900_000)]);
+        chains.insert([2; 24], vec![block(0, 0, 2_000_000)]);
+        chains.insert([3; 24], vec![block(20 * WEEK, 0, 5_000_000)]);
+        let delegations = HashMap::from([([2; 24], [1; 24])]);
+
+        let results = calculate(&params, &chains, &delegations, 10 * WEEK, None);
+        // the third account did not exist yet
+        assert_eq!(2, results.len());
+
+        let first = &results[0];
+        let expected = PowerComponents::calculate(&params, &chains[&[1; 24]][1], None, &chains[&[1; 24]][0]);
+        assert_eq!(expected, first.components);
+        assert_eq!(expected.actual_power(&params), first.actual_power);
+        assert_eq!(results[1].actual_power, first.delegated_power);
+        assert!(first.active_power <= first.actual_power + first.delegated_power);
+
+        // earlier, the first account only had its first block
+        let results = calculate(&params, &chains, &delegations, WEEK, Some([1; 24]));
+        assert_eq!(1, results.len());
+        assert_eq!(
+            PowerComponents::calculate(&params, &chains[&[1; 24]][0], None, &chains[&[1; 24]][0]),
+            results[0].components
+        );
+    }
+
+    #[test]
+    fn test_farming_discount() {
+        let with_transaction = |block: SignedBlock, data: Data| {
+            let mut block_data = block.data.clone();
+            block_data.transactions.push(Transaction {
+                data: Some(data),
+            });
+            SignedBlock::new(block.header, block_data)
+        };
+
+        let mut params = ConsensusParameters::default();
+        let mut chains = BTreeMap::new();
+        let funding = with_transaction(
+            block(0, 0, 10_000_000),
+            Data::TxSend(TxSend {
+                receiver: vec![2; 24],
+                amount: 100_000,
+                data: vec![],
+            }),
+        );
+        let funding_id = Transaction::get_id(funding.get_id(), 0).expect("should get id");
+
+        // delegates on its first day, is funded by a single account and then receives the same amount every day
+        let mut farming_wallet = vec![block(0, 0, 0)];
+        for height in 1..10 {
+            let next = block(height * DAY, height, height * 100_000);
+            farming_wallet.push(match height {
+                1 => with_transaction(
+                    next,
+                    Data::TxDelegate(TxDelegate {
+                        representative: vec![1; 24],
+                    }),
+                ),
+                2 => with_transaction(
+                    next,
+                    Data::TxClaim(TxClaim {
+                        send_transaction_id: funding_id.to_vec(),
+                    }),
+                ),
+                _ => next,
+            });
+        }
+
+        chains.insert([1; 24], vec![block(0, 0, 1000)]);
+        chains.insert([2; 24], farming_wallet);
+        chains.insert([3; 24], vec![funding]);
+        for account in 4..10 {
+            chains.insert([account; 24], vec![block(0, 0, 10_000_000)]);
+        }
+        let delegations = HashMap::from([([2; 24], [1; 24])]);
+
+        let sends = farming::sends(&chains, 9 * DAY);
+        let score = farming::score_chain(&chains[&[2; 24]], &sends, 9 * DAY, &params.farming);
+        assert_eq!(Fixed::ONE, score.single_funding_source);
+        assert!(score.is_flagged(&params.farming), "{score:?}");
+
+        let full = calculate(&params, &chains, &delegations, 9 * DAY, Some([1; 24])).remove(0);
+        params.farming.discount_delegated_power = true;
+        let discounted = calculate(&params, &chains, &delegations, 9 * DAY, Some([1; 24])).remove(0);
+
+        assert!(full.delegated_power > 0);
+        assert_eq!(farming::discount(full.delegated_power, &score, &params.farming), discounted.delegated_power);
+        // the epoch snapshots are taken from the active power
+        assert!(discounted.active_power < full.active_power);
+    }
+
+    #[test]
+    fn test_scenario() {
+        let scenario = Scenario::from_toml(
+            r#"
+            [[blocks]]
+            account = "pog-abc"
+            timestamp = 1650000000
+            balance = 1000
+
+            [[delegations]]
+            account = "pog-abc"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(1, scenario.blocks.len());
+        assert_eq!(1000, scenario.blocks[0].balance);
+        assert_eq!(None, scenario.delegations[0].representative);
+    }
+
+    #[tokio::test]
+    async fn test_simulate() -> Result<()> {
+        let state = ChampState::mock().await;
+        let private_key = generate_private_key()?;
+        state.db.lock().await.add_block(genesis_block(&private_key, 1_000_000)).await?;
+
+        let account_id = account(&private_key)?;
+        let address = format!("pog-{}", account_id.encode_zbase()?);
+        let representative = format!("pog-{}", [0u8; 24].encode_zbase()?);
+        let scenario = Scenario::from_toml(&format!(
+            r#"
+            [[blocks]]
+            account = "{address}"
+            timestamp = 1650100000
+            balance = 2000000
+
+            [[delegations]]
+            account = "{address}"
+            representative = "{representative}"
+            "#
+        ))?;
+
+        let before = simulate(&state, Some(account_id), GENESIS_TIMESTAMP, &scenario).await?;
+        let after = simulate(&state, Some(account_id), 1_650_100_000, &scenario).await?;
+        assert_eq!(1, before.len());
+        assert!(after[0].components.balance > before[0].components.balance);
+
+        // accounts without blocks have no power, even if power is delegated to them
+        assert!(simulate(&state, Some([0; 24]), 1_650_100_000, &scenario).await?.is_empty());
+        Ok(())
+    }
+}
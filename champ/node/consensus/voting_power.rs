@@ -1,34 +1,86 @@
 use anyhow::{anyhow, Result};
 use tracing::{debug, trace};
 
-use crate::consensus::graphs::*;
+use crate::consensus::{farming, fixed::Fixed, graphs::*, parameters::ConsensusParameters};
 use crate::state::ChampStateArc;
 use pog_proto::api;
 
-// To balance each graph
-const BLOCK_WEIGHT: f64 = 1.2;
-const BALANCE_WEIGHT: f64 = 0.75;
-const CASHFLOW_WEIGHT: f64 = 1.0;
-const AGE_WEIGHT: f64 = 1.0;
-const INACTIVE_TAX_WEIGHT: f64 = 1.0;
+// Quorum Percentage (60%)
+pub const VOTE_PERCENTAGE_NEEDED: f64 = 0.6;
 
-// so we can normalize the network affect
-const MAX_NETWORK_POWER: f64 = 0.3;
+/// The graph results the actual voting power of an account is made of
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerComponents {
+    pub balance: Fixed,
+    pub cashflow: Fixed,
+    pub block: Fixed,
+    pub age: Fixed,
+    pub inactive_tax: Fixed,
+}
 
-// Month in Seconds
-const LOOKBACK_RANGE: u64 = 60 * 60 * 24 * 30;
-// 2 Months in Seconds
-const MAX_LOOKBACK_RANGE: u64 = LOOKBACK_RANGE * 2;
+impl PowerComponents {
+    /// Calculates the graphs from the latest block of an account, its last block before the lookback range
+    /// and its first block
+    pub fn calculate(
+        params: &ConsensusParameters,
+        block: &api::SignedBlock,
+        old_block: Option<&api::SignedBlock>,
+        first_block: &api::SignedBlock,
+    ) -> Self {
+        let graphs = &params.graphs;
+        let new_block_balance = block.data.balance;
+        // accounts without a block in the lookback range are treated as if their balance did not change
+        let old_block_balance = old_block.map_or(new_block_balance, |b| b.data.balance);
 
-// Quorum Percentage (60%)
-// pub const VOTE_PERCENTAGE_NEEDED: f64 = 0.6;
+        let mut components = Self {
+            balance: balance_graph(new_block_balance, graphs),
+            cashflow: cashflow_graph(new_block_balance, old_block_balance, graphs),
+            block: block_graph(block.data.height, block, old_block, graphs),
+            age: age_graph(block.header.timestamp.saturating_sub(first_block.header.timestamp), graphs),
+            inactive_tax: Fixed::ZERO,
+        };
+        components.inactive_tax =
+            inactive_tax_graph(new_block_balance, old_block_balance, components.weighted_sum(params), graphs);
+        components
+    }
+
+    // Weights to change how much impact each factor should have
+    fn weighted_sum(&self, params: &ConsensusParameters) -> Fixed {
+        self.block * Fixed::from_f64(params.block_weight)
+            + self.balance * Fixed::from_f64(params.balance_weight)
+            + self.age * Fixed::from_f64(params.age_weight)
+            + self.cashflow * Fixed::from_f64(params.cashflow_weight)
+    }
+
+    /// Combines the weighted graphs to the actual voting power.
+    /// Negative results are 0 and the fraction is truncated.
+    pub fn actual_power(&self, params: &ConsensusParameters) -> u64 {
+        let graph_result = self.weighted_sum(params) + self.inactive_tax * Fixed::from_f64(params.inactive_tax_weight);
+        graph_result.to_u64()
+    }
+}
+
+/// Limits the power of an account to its share of the total network power
+pub fn limit_active_power(total_power: u64, total_network_power: f64, params: &ConsensusParameters) -> u64 {
+    // a single node can only have a percentage of the max network power (current 30% but this will change)
+    let total_allowed_voting_power = (total_network_power * params.max_network_power) as u64;
+    total_power.min(total_allowed_voting_power)
+}
 
 /// Returns actual voting power of an account.
 /// Actual voting power is without the delegated power.
+/// Accounts without a block before the lookback range, like new accounts, are treated as if their balance did not
+/// change.
 #[tracing::instrument]
 pub async fn get_actual_power(state: &ChampStateArc, account_id: api::AccountID) -> Result<u64> {
     debug!("Calculating actual voting power");
 
+    if let Some(power) = state.voting_power_cache.get_actual_power(&account_id) {
+        trace!("cached actual voting power: {}", power);
+        return Ok(power);
+    }
+
+    let params = state.config.read().await.chain_spec.consensus.clone();
     let db = &state.db.lock().await;
     let block = db.get_latest_block_by_account(account_id).await?;
 
@@ -36,90 +88,129 @@ pub async fn get_actual_power(state: &ChampStateArc, account_id: api::AccountID)
     let old_block_result = db
         .get_latest_block_by_account_before(
             account_id,
-            block.header.timestamp - LOOKBACK_RANGE,
-            block.header.timestamp - MAX_LOOKBACK_RANGE,
+            block.header.timestamp.saturating_sub(params.lookback_range),
+            block.header.timestamp.saturating_sub(params.max_lookback_range),
         )
         .await?;
 
     // First Block from an account
     let first_block = db.get_block_by_height(account_id, &0).await?.ok_or_else(|| anyhow!("no block found"))?;
 
-    let new_block_balance = block.data.balance;
-    let old_block_balance = old_block_result.clone().ok_or_else(|| anyhow!("block not found"))?.data.balance;
-
-    let bresult = balance_graph(block.data.balance);
-    let cresult = cashflow_graph(new_block_balance, old_block_balance);
-    let bbresult = block_graph(block.data.height, &block, old_block_result.as_ref());
-    let aresult = age_graph(block.header.timestamp - first_block.header.timestamp);
-
-    // Weights to change how much impact each factor should have
-    let net_result =
-        bbresult * BLOCK_WEIGHT + bresult * BALANCE_WEIGHT + aresult * AGE_WEIGHT + cresult * CASHFLOW_WEIGHT;
-
-    let iresult = inactive_tax_graph(new_block_balance, old_block_balance, net_result);
-
-    trace!("Graph results: balance={0}, cashflow={1}, block={2}, age={3}", bresult, cresult, bbresult, aresult);
+    let components = PowerComponents::calculate(&params, &block, old_block_result.as_ref(), &first_block);
+    trace!("Graph results: {:?}", components);
     // TODO: Green Adresses?
 
-    let graph_result = net_result + iresult * INACTIVE_TAX_WEIGHT;
-
-    let result = if graph_result < 0.0 {
-        0
-    } else {
-        graph_result as u64
-    };
-
+    let result = components.actual_power(&params);
     trace!("total actual voting power result: {}", result);
 
+    state.voting_power_cache.set_actual_power(account_id, result);
     Ok(result)
 }
 
 /// Returns the active power of an account that is being used on the network.
 /// Active power is the account power with the delegated power.
+/// Fails while the network power is not known, since the power can't be limited without it.
 #[tracing::instrument]
 pub async fn get_active_power(state: &ChampStateArc, account_id: api::AccountID) -> Result<u64> {
     debug!("Calculating actual voting power");
+    // get max voting power in the network (all nodes combined)
+    let network_power = state.network_power.get().ok_or_else(|| anyhow!("the network power is not known yet"))?;
+    let total_power = get_total_power(state, account_id).await?;
+    let params = state.config.read().await.chain_spec.consensus.clone();
+    let active_power = limit_active_power(total_power, network_power.total_power as f64, &params);
+    trace!("total active voting power result: {}", active_power);
+    Ok(active_power)
+}
+
+/// Returns the power of an account with the delegated power, without the limit of the active power.
+pub async fn get_total_power(state: &ChampStateArc, account_id: api::AccountID) -> Result<u64> {
     let actual_power = get_actual_power(state, account_id).await?;
     let delegate_power = get_delegated_power(state, account_id).await?;
-    // get max voting power in the network (all nodes combined)
-    let total_network_power = state.blockpool_client.clone().get_total_network_power();
-    // a single node can only have a percentage of the max network power (current 30% but this will change)
-    let total_allowed_voting_power = (total_network_power * MAX_NETWORK_POWER) as u64;
-    let total_power = actual_power + delegate_power;
-    if total_power > total_allowed_voting_power {
-        return Ok(total_allowed_voting_power);
-    }
-    trace!("total active voting power result: {}", total_power);
-    Ok(total_power)
+    Ok(actual_power + delegate_power)
 }
 
 /// Gets the sum of the power of each delegate of an account
 async fn get_delegated_power(state: &ChampStateArc, account_id: api::AccountID) -> Result<u64> {
     debug!("calculating delegated power");
-    // TODO: Cache this
-    let mut power = 0;
-    let db = &state.db.lock().await;
 
-    let mut delegates = db.get_delegates_by_account(account_id).await?;
+    if let Some(power) = state.voting_power_cache.get_delegated_power(&account_id) {
+        trace!("cached delegated voting power: {}", power);
+        return Ok(power);
+    }
+
+    let farming_params = state.config.read().await.chain_spec.consensus.farming.clone();
+    let mut power = 0;
+    // the lock has to be released before calculating the power of each delegate
+    let delegates = state.db.lock().await.get_delegates_by_account(account_id).await?;
     // TODO: Test Performance and do this concurrently?
-    while let Some(d) = delegates.pop() {
-        let p = get_actual_power(state, d.to_owned()).await?;
+    for d in &delegates {
+        let mut p = get_actual_power(state, d.to_owned()).await?;
+        if farming_params.discount_delegated_power {
+            let score = farming::score(state, d.to_owned()).await?;
+            p = farming::discount(p, &score, &farming_params);
+        }
         power += p;
     }
 
     trace!("total delegated voting power: {}", power);
+    state.voting_power_cache.set_delegated_power(account_id, power, delegates);
     Ok(power)
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use crate::consensus::{
+        fixed::Fixed,
         graphs::{balance_graph, cashflow_graph},
-        voting_power::BALANCE_WEIGHT,
-        voting_power::CASHFLOW_WEIGHT,
+        network_power::NetworkPowerSnapshot,
+        parameters::ConsensusParameters,
     };
+    use crate::fixtures::{account, genesis_block, DELEGATE_BALANCE};
+    use crate::state::ChampState;
+    use crypto::signatures::ed25519::generate_private_key;
     use pog_proto::api::SignedBlock;
     use pog_proto::api::{BlockData, BlockHeader};
+
+    #[tokio::test]
+    async fn test_active_power() -> Result<()> {
+        let state = ChampState::mock().await;
+        let private_key = generate_private_key()?;
+        let account_id = account(&private_key)?;
+        state.db.lock().await.add_block(genesis_block(&private_key, DELEGATE_BALANCE)).await?;
+        let total_power = get_total_power(&state, account_id).await?;
+        assert!(total_power > 0);
+
+        // the power can't be limited before the network power is known
+        get_active_power(&state, account_id).await.expect_err("the network power is not known yet");
+
+        state.network_power.set(NetworkPowerSnapshot::new(0, &[(total_power, true)]));
+        let params = state.config.read().await.chain_spec.consensus.clone();
+        let limit = (total_power as f64 * params.max_network_power) as u64;
+        assert!(limit < total_power);
+        assert_eq!(limit, get_active_power(&state, account_id).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_actual_power_without_old_block() -> Result<()> {
+        let state = ChampState::mock().await;
+        let private_key = generate_private_key()?;
+        let genesis = genesis_block(&private_key, DELEGATE_BALANCE);
+        state.db.lock().await.add_block(genesis.clone()).await?;
+
+        // an account with only one block has no block before the lookback range, so its balance did not change
+        let params = state.config.read().await.chain_spec.consensus.clone();
+        let components = PowerComponents::calculate(&params, &genesis, None, &genesis);
+        assert_eq!(
+            PowerComponents::calculate(&params, &genesis, Some(&genesis), &genesis).cashflow,
+            components.cashflow
+        );
+        assert!(components.actual_power(&params) > 0);
+        assert_eq!(components.actual_power(&params), get_actual_power(&state, account(&private_key)?).await?);
+        Ok(())
+    }
+
     #[test]
     fn check_voting_power() {
         let mut blocks: Vec<SignedBlock> = Vec::new();
@@ -149,10 +240,13 @@ mod tests {
             previous: b"some".to_vec(),
             transactions: vec![],
         };
+        let params = ConsensusParameters::default();
         println!("Old Balance - New Balance  -  Balance I  -  Cashflow I - Total I");
         for block in blocks {
-            let balance_importance = balance_graph(block.data.balance) * BALANCE_WEIGHT;
-            let cashflow_importance = cashflow_graph(block.data.balance, old_data.balance) * CASHFLOW_WEIGHT;
+            let balance_importance =
+                balance_graph(block.data.balance, &params.graphs) * Fixed::from_f64(params.balance_weight);
+            let cashflow_importance = cashflow_graph(block.data.balance, old_data.balance, &params.graphs)
+                * Fixed::from_f64(params.cashflow_weight);
             let total_importance = balance_importance + cashflow_importance;
             println!(
                 "{0} \t|----| {1} \t|----| {2} \t|----| {3} \t|----| {4}",
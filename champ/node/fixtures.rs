@@ -0,0 +1,139 @@
+//! Blocks and mocked nodes shared by the unit tests
+
+use anyhow::Result;
+use crypto::signatures::ed25519::{create_public_key, create_signature, generate_private_key};
+use encoding::{account::generate_account_address, zbase32::ToZbase};
+use pog_proto::api::{AccountID, BlockData, BlockHeader, RawBlock, SignedBlock, Transaction};
+use pog_proto::rpc::node_admin::Mode;
+use prost::Message;
+use tokio::sync::mpsc::Receiver;
+
+use crate::consensus::{epoch_power::PowerSnapshot, network_power::NetworkPowerSnapshot};
+use crate::p2p::types::Command;
+use crate::state::{ChampState, ChampStateArc};
+
+/// timestamp of the blocks created by `genesis_block`
+pub const GENESIS_TIMESTAMP: u64 = 1_650_000_000;
+/// balance of the accounts of mocked nodes
+pub const DELEGATE_BALANCE: u64 = 40_000_000;
+
+pub fn account(private_key: &[u8]) -> Result<AccountID> {
+    Ok(generate_account_address(create_public_key(private_key)?.to_vec())?)
+}
+
+pub fn genesis_block(private_key: &[u8], balance: u64) -> SignedBlock {
+    genesis_block_at(private_key, balance, GENESIS_TIMESTAMP)
+}
+
+pub fn genesis_block_at(private_key: &[u8], balance: u64, timestamp: u64) -> SignedBlock {
+    let data = BlockData {
+        version: 0,
+        signature_type: 0,
+        balance,
+        height: 0,
+        previous: b"genesis".to_vec(),
+        transactions: vec![],
+    };
+
+    SignedBlock::new(
+        BlockHeader {
+            signature: create_signature(&data.encode_to_vec(), private_key).expect("should sign").to_vec(),
+            public_key: create_public_key(private_key).expect("should create public key").to_vec(),
+            timestamp,
+        },
+        data,
+    )
+}
+
+pub fn child_block(private_key: &[u8], parent: &SignedBlock) -> SignedBlock {
+    let data = BlockData {
+        height: parent.data.height + 1,
+        previous: parent.get_id().to_vec(),
+        ..parent.data.clone()
+    };
+
+    SignedBlock::new(
+        BlockHeader {
+            signature: create_signature(&data.encode_to_vec(), private_key).expect("should sign").to_vec(),
+            ..parent.header.clone()
+        },
+        data,
+    )
+}
+
+pub fn with_transactions(private_key: &[u8], block: &SignedBlock, transactions: Vec<Transaction>) -> SignedBlock {
+    let data = BlockData {
+        transactions,
+        ..block.data.clone()
+    };
+
+    SignedBlock::new(
+        BlockHeader {
+            signature: create_signature(&data.encode_to_vec(), private_key).expect("should sign").to_vec(),
+            ..block.header.clone()
+        },
+        data,
+    )
+}
+
+pub fn raw_block(block: &SignedBlock) -> RawBlock {
+    RawBlock {
+        header: Some(block.header.clone()),
+        data: block.data_raw.clone(),
+    }
+}
+
+/// A mocked node and the requests it sends to the network
+pub struct MockNode {
+    pub state: ChampStateArc,
+    pub p2p_rx: Receiver<Command>,
+    pub private_key: [u8; 32],
+    pub account: AccountID,
+}
+
+/// Mocks a node for each mode, where every node knows the genesis blocks of all nodes' accounts
+///
+/// The accounts of the nodes running in `Mode::Prime` are the elected prime delegates of `epoch`, and their power
+/// from the epoch's snapshot is `DELEGATE_BALANCE`. All of them are online and make up `network_power`.
+pub async fn mock_network(modes: &[Mode], epoch: u64, network_power: u64) -> Result<Vec<MockNode>> {
+    let private_keys = modes.iter().map(|_| generate_private_key()).collect::<Result<Vec<_>, _>>()?;
+    let accounts = private_keys.iter().map(|key| account(key)).collect::<Result<Vec<_>>>()?;
+    let prime_delegates: Vec<(AccountID, u64)> = modes
+        .iter()
+        .zip(accounts.iter())
+        .filter(|(mode, _)| **mode == Mode::Prime)
+        .map(|(_, account)| (*account, DELEGATE_BALANCE))
+        .collect();
+
+    let mut nodes = vec![];
+    for ((mode, private_key), account) in modes.iter().zip(private_keys.iter()).zip(accounts.iter()) {
+        let (state, p2p_rx) = ChampState::mock_with_p2p().await;
+
+        {
+            let mut config = state.config.write().await;
+            config.consensus.mode = *mode;
+            config.consensus.primary_wallet = Some(format!("pog-{}", account.encode_zbase()?));
+        }
+
+        state.network_power.set(NetworkPowerSnapshot {
+            epoch,
+            total_power: network_power,
+            online_power: network_power,
+        });
+        state.prime_delegates.set(epoch, prime_delegates.clone());
+        state.epoch_power.set(PowerSnapshot::new(epoch, prime_delegates.clone()));
+
+        for private_key in &private_keys {
+            state.db.lock().await.add_block(genesis_block(private_key, DELEGATE_BALANCE)).await?;
+        }
+
+        nodes.push(MockNode {
+            state,
+            p2p_rx,
+            private_key: *private_key,
+            account: *account,
+        });
+    }
+
+    Ok(nodes)
+}
@@ -1,31 +1,39 @@
 mod auth;
 mod blockpool;
+mod chain;
 mod cli;
 mod config;
 mod consensus;
 mod env;
+#[cfg(test)]
+mod fixtures;
 mod http;
 mod metrics;
 mod p2p;
 mod rpc;
 mod state;
 pub mod storage;
+mod sync;
 pub mod validation;
 pub mod wallets;
 
 use anyhow::{anyhow, Result};
 use http::HttpServer;
 use roughtime::server::RoughTime;
-use tokio::{sync::RwLock, try_join};
+use tokio::{
+    sync::{mpsc, RwLock},
+    try_join,
+};
 use tracing::{debug, Level};
 
 use crate::{
     blockpool::Blockpool,
     env::process_env,
     metrics::MetricsServer,
-    p2p::server::P2PServer,
+    p2p::{client::P2PClient, server::P2PServer},
     rpc::server::RpcServer,
     state::{ChampState, ChampStateArgs},
+    sync::SyncService,
     wallets::WalletManager,
 };
 
@@ -60,6 +68,12 @@ pub async fn run() -> Result<()> {
     debug!("initializing blockpool");
     let mut blockpool = Blockpool::new();
 
+    debug!("initializing sync service");
+    let mut sync_service = SyncService::new();
+
+    debug!("initializing p2p client");
+    let (p2p_tx, p2p_rx) = mpsc::channel(1000);
+
     debug!("initializing wallet manager");
     let wallet_manager = WalletManager::new();
     let wallet_manager = RwLock::new(wallet_manager);
@@ -70,11 +84,16 @@ pub async fn run() -> Result<()> {
         config,
         wallet_manager,
         blockpool_client: blockpool.get_client(),
+        p2p_client: P2PClient::new(p2p_tx),
+        sync_client: sync_service.get_client(),
     });
 
     debug!("injecting state into blockpool");
     blockpool.add_state(state.clone());
 
+    debug!("injecting state into sync service");
+    sync_service.add_state(state.clone());
+
     debug!("injecting state into walletmanager");
     {
         let wallet_manager = &mut state.wallet_manager.write().await;
@@ -90,6 +109,12 @@ pub async fn run() -> Result<()> {
         return Ok(());
     }
 
+    if let Some(matches) = matches.subcommand_matches("consensus") {
+        debug!("command matched to consensus subcommand");
+        cli::consensus::run(matches, &state).await?;
+        return Ok(());
+    }
+
     if let Some(matches) = matches.subcommand_matches("wallet") {
         debug!("command matched to wallet subcommand");
         cli::wallet::run(matches, &state).await?;
@@ -98,9 +123,14 @@ pub async fn run() -> Result<()> {
 
     debug!("proccessing env vars");
     process_env(state.clone()).await?;
+
+    debug!("restoring equivocation evidence");
+    consensus::equivocation::restore(&state).await?;
+    debug!("restoring banned peers");
+    p2p::reputation::restore(&state).await?;
     debug!("creating services");
 
-    let mut p2p_server = P2PServer::new(state.clone()).await?;
+    let mut p2p_server = P2PServer::new(state.clone(), p2p_rx).await?;
     let rpc_server = RpcServer::new(state.clone());
     let http_server = HttpServer::new();
     let rough_time_server = RoughTime::new();
@@ -119,6 +149,12 @@ pub async fn run() -> Result<()> {
         }
     });
 
+    tokio::spawn(async move {
+        if let Err(e) = sync_service.start().await {
+            tracing::error!("sync service stopped: {:?}", e);
+        }
+    });
+
     match try_join!(
         rpc_server.start(rpc_addr),
         metrics_server.start(metrics_addr, matches.is_present("metrics")),
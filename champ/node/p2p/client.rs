@@ -0,0 +1,43 @@
+use super::types::ResponseBodyData;
+use super::types::{Command, RequestBodyData};
+use anyhow::{Context, Result};
+use tokio::sync::{mpsc, oneshot};
+
+/// Sends requests to the network through the p2p server
+#[derive(Debug, Clone)]
+pub struct P2PClient {
+    tx: mpsc::Sender<Command>,
+}
+
+impl P2PClient {
+    pub fn new(tx: mpsc::Sender<Command>) -> Self {
+        Self {
+            tx,
+        }
+    }
+
+    /// Sends a request to all prime delegates and a number of random peers
+    ///
+    /// The p2p server waits for the blockpool while it processes votes, so broadcasts don't wait for room in its queue.
+    /// They fail instead if it is full.
+    pub fn broadcast(&self, request: RequestBodyData) -> Result<()> {
+        self.tx
+            .try_send(Command::Broadcast {
+                request,
+            })
+            .with_context(|| "error sending broadcast request")
+    }
+
+    /// Sends a request to a single peer and returns its response
+    pub async fn request(&self, request: RequestBodyData) -> Result<ResponseBodyData> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.tx
+            .send(Command::Request {
+                request,
+                resp,
+            })
+            .await
+            .with_context(|| "error sending request")?;
+        resp_rx.await?
+    }
+}
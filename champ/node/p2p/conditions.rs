@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use dashmap::DashMap;
+use libp2p::PeerId;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+/// How messages from a peer reach this node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Link {
+    Open,
+    /// messages are processed this long after they arrived
+    Delayed(Duration),
+    /// messages are held until the link is open again
+    Partitioned,
+}
+
+/// Simulated network conditions for the links to other peers
+///
+/// All links are open unless changed, which only the multi-node test harness does.
+#[derive(Debug)]
+pub struct LinkConditions {
+    links: DashMap<PeerId, Link>,
+    changed: watch::Sender<()>,
+    // keeps the channel open, so changes can always be sent
+    receiver: watch::Receiver<()>,
+}
+
+impl Default for LinkConditions {
+    fn default() -> Self {
+        let (changed, receiver) = watch::channel(());
+        Self {
+            links: DashMap::new(),
+            changed,
+            receiver,
+        }
+    }
+}
+
+impl LinkConditions {
+    pub fn get(&self, peer_id: &PeerId) -> Link {
+        self.links.get(peer_id).map_or(Link::Open, |link| *link)
+    }
+
+    pub fn set(&self, peer_id: PeerId, link: Link) {
+        match link {
+            Link::Open => self.links.remove(&peer_id),
+            _ => self.links.insert(peer_id, link),
+        };
+        let _ = self.changed.send(());
+    }
+
+    /// Opens the links to all peers
+    pub fn clear(&self) {
+        self.links.clear();
+        let _ = self.changed.send(());
+    }
+
+    pub fn is_open(&self, peer_id: &PeerId) -> bool {
+        self.get(peer_id) == Link::Open
+    }
+
+    /// Waits until a message that arrived from a peer now can be processed
+    pub async fn wait(&self, peer_id: PeerId) {
+        let received = Instant::now();
+        let mut changed = self.receiver.clone();
+
+        loop {
+            match self.get(&peer_id) {
+                Link::Open => return,
+                Link::Delayed(delay) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(received + delay) => return,
+                        _ = changed.changed() => {}
+                    }
+                }
+                Link::Partitioned => {
+                    let _ = changed.changed().await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_link_conditions() {
+        let conditions = std::sync::Arc::new(LinkConditions::default());
+        let peer_id = PeerId::random();
+        assert!(conditions.is_open(&peer_id));
+        conditions.wait(peer_id).await;
+
+        conditions.set(peer_id, Link::Delayed(Duration::from_millis(50)));
+        let started = Instant::now();
+        conditions.wait(peer_id).await;
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        conditions.set(peer_id, Link::Partitioned);
+        let mut waiting = tokio::spawn({
+            let conditions = conditions.clone();
+            async move { conditions.wait(peer_id).await }
+        });
+        assert!(tokio::time::timeout(Duration::from_millis(50), &mut waiting).await.is_err());
+
+        conditions.clear();
+        tokio::time::timeout(Duration::from_secs(1), waiting).await.expect("partition should heal").unwrap();
+        assert_eq!(Link::Open, conditions.get(&peer_id));
+    }
+}
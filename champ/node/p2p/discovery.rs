@@ -0,0 +1,172 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use libp2p::{Multiaddr, PeerId};
+
+// only this many addresses of a peer list are looked at, so a long list can't flood the node
+const MAX_ADDRESSES_PER_LIST: usize = 10;
+// a single peer can't make this node dial more than this many of its addresses at once
+const MAX_DIALS_PER_SOURCE: usize = 3;
+// seconds after which a dial that did not lead to a connection counts as failed
+const DIAL_TIMEOUT: u64 = 30;
+// failed addresses that are not dialed again
+const MAX_FAILED: usize = 1000;
+
+/// Where this node learned about a peer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PeerSource {
+    /// `consensus.initial_peers`, the bootstrap peers of the chain spec or the peer store (see `peer_store`)
+    Initial,
+    /// the peer connected to this node
+    Inbound,
+    /// the peer list another peer sent in a pong
+    Exchange(PeerId),
+}
+
+struct Dial {
+    source: PeerId,
+    started: u64,
+}
+
+/// Learns new peers from the peer lists other peers send
+///
+/// Peer lists can't be trusted, so each list can only add a few addresses and addresses that could not be reached
+/// are remembered and not dialed again.
+#[derive(Default)]
+pub struct Discovery {
+    dialing: HashMap<Multiaddr, Dial>,
+    failed: HashSet<Multiaddr>,
+    failed_order: VecDeque<Multiaddr>,
+}
+
+impl Discovery {
+    /// Picks the addresses of a peer list to dial, until the node has `target` connections
+    ///
+    /// `known` are the addresses of the connected peers and this node's own addresses.
+    pub fn candidates(
+        &mut self,
+        source: PeerId,
+        addresses: &[Vec<u8>],
+        known: &HashSet<Multiaddr>,
+        connected: usize,
+        target: usize,
+        now: u64,
+    ) -> Vec<Multiaddr> {
+        self.expire(now);
+
+        let dials_by_source = self.dialing.values().filter(|dial| dial.source == source).count();
+        let free = target
+            .saturating_sub(connected + self.dialing.len())
+            .min(MAX_DIALS_PER_SOURCE.saturating_sub(dials_by_source));
+
+        let mut candidates = vec![];
+        for address in addresses.iter().take(MAX_ADDRESSES_PER_LIST) {
+            if candidates.len() >= free {
+                break;
+            }
+
+            let address = match Multiaddr::try_from(address.clone()) {
+                Ok(address) => address,
+                Err(_) => continue,
+            };
+            if known.contains(&address)
+                || self.dialing.contains_key(&address)
+                || self.failed.contains(&address)
+                || candidates.contains(&address)
+            {
+                continue;
+            }
+
+            self.dialing.insert(
+                address.clone(),
+                Dial {
+                    source,
+                    started: now,
+                },
+            );
+            candidates.push(address);
+        }
+        candidates
+    }
+
+    /// Where a peer we connected to was learned from
+    pub fn connected(&mut self, address: &Multiaddr) -> PeerSource {
+        match self.dialing.remove(address) {
+            Some(dial) => PeerSource::Exchange(dial.source),
+            None => PeerSource::Initial,
+        }
+    }
+
+    /// Remembers an address that could not be dialed
+    pub fn failed(&mut self, address: Multiaddr) {
+        self.dialing.remove(&address);
+        if self.failed.insert(address.clone()) {
+            self.failed_order.push_back(address);
+        }
+
+        while self.failed_order.len() > MAX_FAILED {
+            if let Some(oldest) = self.failed_order.pop_front() {
+                self.failed.remove(&oldest);
+            }
+        }
+    }
+
+    fn expire(&mut self, now: u64) {
+        let expired: Vec<Multiaddr> = self
+            .dialing
+            .iter()
+            .filter(|(_, dial)| now.saturating_sub(dial.started) > DIAL_TIMEOUT)
+            .map(|(address, _)| address.clone())
+            .collect();
+
+        for address in expired {
+            self.failed(address);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(port: u16) -> Multiaddr {
+        format!("/ip4/10.0.0.1/tcp/{port}").parse().unwrap()
+    }
+
+    #[test]
+    fn test_candidates() {
+        let mut discovery = Discovery::default();
+        let (source, other) = (PeerId::random(), PeerId::random());
+        let known = HashSet::from([address(1)]);
+        let list = vec![b"not an address".to_vec(), address(1).to_vec(), address(2).to_vec(), address(2).to_vec()];
+
+        assert_eq!(vec![address(2)], discovery.candidates(source, &list, &known, 1, 10, 0));
+        // addresses that are being dialed are not dialed again
+        assert!(discovery.candidates(other, &list, &known, 1, 10, 0).is_empty());
+        // the node already has enough connections
+        assert!(discovery.candidates(other, &[address(3).to_vec()], &known, 9, 10, 0).is_empty());
+
+        assert_eq!(PeerSource::Exchange(source), discovery.connected(&address(2)));
+        assert_eq!(PeerSource::Initial, discovery.connected(&address(2)));
+    }
+
+    #[test]
+    fn test_poisoning() {
+        let mut discovery = Discovery::default();
+        let (source, other) = (PeerId::random(), PeerId::random());
+        let list: Vec<Vec<u8>> = (1..100).map(|port| address(port).to_vec()).collect();
+
+        // a single peer can only make the node dial a few of its addresses
+        assert_eq!(MAX_DIALS_PER_SOURCE, discovery.candidates(source, &list, &HashSet::new(), 0, 50, 0).len());
+        assert!(discovery.candidates(source, &list, &HashSet::new(), 0, 50, 0).is_empty());
+        assert_eq!(vec![address(4)], discovery.candidates(other, &list[..4], &HashSet::new(), 0, 50, 0));
+
+        // unreachable addresses are not dialed again
+        discovery.failed(address(4));
+        assert!(discovery.candidates(other, &list[3..4], &HashSet::new(), 0, 50, 0).is_empty());
+
+        // dials that did not connect in time count as failed
+        let candidates = discovery.candidates(source, &list, &HashSet::new(), 0, 50, DIAL_TIMEOUT + 1);
+        assert_eq!(vec![address(5), address(6), address(7)], candidates);
+        assert_eq!(PeerSource::Initial, discovery.connected(&address(1)));
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::{HashSet, VecDeque};
+
+use crypto::hash::sha3;
+
+/// how many times a broadcast request is forwarded at most
+pub const TTL: u32 = 6;
+// hashes of seen messages that are remembered
+const MAX_SEEN: usize = 10_000;
+
+/// Hashes of the requests this node already processed, so each gossiped request is only processed and forwarded once
+#[derive(Debug, Default)]
+pub struct SeenMessages {
+    hashes: HashSet<[u8; 32]>,
+    order: VecDeque<[u8; 32]>,
+}
+
+impl SeenMessages {
+    /// Remembers a message and returns false if it was seen before
+    pub fn insert(&mut self, message: &[u8]) -> bool {
+        let hash = sha3(message);
+        if !self.hashes.insert(hash) {
+            return false;
+        }
+
+        self.order.push_back(hash);
+        while self.order.len() > MAX_SEEN {
+            if let Some(oldest) = self.order.pop_front() {
+                self.hashes.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seen_messages() {
+        let mut seen = SeenMessages::default();
+        assert!(seen.insert(b"vote"));
+        assert!(!seen.insert(b"vote"));
+        assert!(seen.insert(b"other vote"));
+
+        for message in 0..MAX_SEEN as u32 {
+            seen.insert(&message.to_be_bytes());
+        }
+        assert_eq!(MAX_SEEN, seen.hashes.len());
+        // the oldest messages are forgotten
+        assert!(seen.insert(b"vote"));
+    }
+}
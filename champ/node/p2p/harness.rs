@@ -0,0 +1,463 @@
+//! Runs a network of nodes in one process, to test consensus end to end
+//!
+//! The nodes are connected through libp2p's memory transport and each has its own database, blockpool and wallet.
+//! Scenarios are scripted as a list of [`Step`]s.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use encoding::zbase32::ToZbase;
+use libp2p::{Multiaddr, PeerId};
+use pog_proto::api::{AccountID, BlockID, SignedBlock};
+use pog_proto::rpc::node_admin::Mode;
+use tokio::task::JoinHandle;
+
+use super::conditions::{Link, LinkConditions};
+use super::server::{timestamp, P2PServer, TransportKind};
+use super::types::Peers;
+use crate::chain::ChainSpec;
+use crate::fixtures::{mock_network, raw_block, DELEGATE_BALANCE};
+use crate::state::ChampStateArc;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub struct TestNode {
+    pub state: ChampStateArc,
+    pub account: AccountID,
+    pub peer_id: PeerId,
+    peers: Peers,
+    conditions: Arc<LinkConditions>,
+    server: JoinHandle<()>,
+}
+
+/// A step of a scripted scenario
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// submits a block to a node, like a client would
+    Submit {
+        node: usize,
+        block: SignedBlock,
+    },
+    /// submits blocks to several nodes at the same time
+    SubmitAtOnce(Vec<(usize, SignedBlock)>),
+    /// holds all messages between the two groups of nodes until the network heals
+    Partition(Vec<usize>, Vec<usize>),
+    /// messages from a node reach all other nodes late
+    Delay {
+        from: usize,
+        delay: Duration,
+    },
+    /// opens all links again and delivers the held messages
+    Heal,
+    /// waits until each of the nodes has added the block to its chain
+    WaitForBlock {
+        nodes: Vec<usize>,
+        block_id: BlockID,
+    },
+    /// waits until no node has pending blocks and all nodes have the same chains
+    Converge,
+}
+
+pub struct Harness {
+    pub nodes: Vec<TestNode>,
+}
+
+impl Harness {
+    /// Starts a node for each mode and connects all of them to each other
+    pub async fn start(modes: &[Mode]) -> Result<Self> {
+        let connections: Vec<(usize, usize)> = (0..modes.len()).flat_map(|b| (0..b).map(move |a| (a, b))).collect();
+        Self::start_connected(modes, &connections).await
+    }
+
+    /// Starts a node for each mode, where the second node of each connection dials the first one
+    ///
+    /// The nodes are mocked like in [`mock_network`] for the current epoch.
+    pub async fn start_connected(modes: &[Mode], connections: &[(usize, usize)]) -> Result<Self> {
+        let epoch = ChainSpec::default().epoch(timestamp());
+        let power = DELEGATE_BALANCE * modes.iter().filter(|mode| **mode == Mode::Prime).count() as u64;
+        let addresses = modes
+            .iter()
+            .map(|_| format!("/memory/{}", crypto::rand::random::<u64>()).parse())
+            .collect::<Result<Vec<Multiaddr>, _>>()?;
+
+        let mut servers = vec![];
+        for (index, node) in mock_network(modes, epoch, power).await?.into_iter().enumerate() {
+            let address = format!("pog-{}", node.account.encode_zbase()?);
+
+            {
+                let mut config = node.state.config.write().await;
+                config.consensus.initial_peers = connections
+                    .iter()
+                    .filter(|(_, dialer)| *dialer == index)
+                    .map(|(listener, _)| addresses.get(*listener).map(ToString::to_string))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| anyhow!("connections can only name nodes of the network"))?;
+                config.chain_spec.bootstrap_peers.clear();
+            }
+
+            {
+                let mut wallet_manager = node.state.wallet_manager.write().await;
+                wallet_manager.add_state(node.state.clone());
+                wallet_manager.add_unlocked_wallet(&address, node.private_key)?;
+            }
+
+            let server = P2PServer::with_transport(
+                node.state.clone(),
+                node.p2p_rx,
+                TransportKind::Memory,
+                addresses[index].clone(),
+            )
+            .await?;
+            servers.push((node.state, node.account, server));
+        }
+
+        // all nodes listen before the first one dials
+        let nodes = servers
+            .into_iter()
+            .map(|(state, account, mut server)| TestNode {
+                state,
+                account,
+                peer_id: server.peer_id(),
+                peers: server.peers.clone(),
+                conditions: server.conditions.clone(),
+                server: tokio::spawn(async move {
+                    if let Err(e) = server.start().await {
+                        tracing::error!("p2p server stopped: {e}");
+                    }
+                }),
+            })
+            .collect();
+
+        let harness = Self {
+            nodes,
+        };
+        harness.wait_for_peers(connections).await?;
+        Ok(harness)
+    }
+
+    pub async fn run(&self, steps: &[Step]) -> Result<()> {
+        for step in steps {
+            tracing::debug!("running step {step:?}");
+            match step {
+                Step::Submit {
+                    node,
+                    block,
+                } => self.submit(*node, block).await?,
+                Step::SubmitAtOnce(blocks) => {
+                    libp2p::futures::future::try_join_all(
+                        blocks.iter().map(|(node, block)| self.submit(*node, block)),
+                    )
+                    .await?;
+                }
+                Step::Partition(first, second) => {
+                    for (a, b) in first.iter().flat_map(|a| second.iter().map(move |b| (*a, *b))) {
+                        self.nodes[a].conditions.set(self.nodes[b].peer_id, Link::Partitioned);
+                        self.nodes[b].conditions.set(self.nodes[a].peer_id, Link::Partitioned);
+                    }
+                }
+                Step::Delay {
+                    from,
+                    delay,
+                } => {
+                    let peer_id = self.nodes[*from].peer_id;
+                    for node in self.nodes.iter().filter(|node| node.peer_id != peer_id) {
+                        node.conditions.set(peer_id, Link::Delayed(*delay));
+                    }
+                }
+                Step::Heal => self.nodes.iter().for_each(|node| node.conditions.clear()),
+                Step::WaitForBlock {
+                    nodes,
+                    block_id,
+                } => {
+                    self.wait_until("the block to be added", || async {
+                        for node in nodes {
+                            if !self.has_block(*node, *block_id).await {
+                                return Ok(false);
+                            }
+                        }
+                        Ok(true)
+                    })
+                    .await?
+                }
+                Step::Converge => self.wait_until("the nodes to converge", || self.converged()).await?,
+            }
+        }
+        Ok(())
+    }
+
+    async fn submit(&self, node: usize, block: &SignedBlock) -> Result<()> {
+        self.nodes[node].state.blockpool_client.submit_block(raw_block(block)).await
+    }
+
+    pub async fn has_block(&self, node: usize, block_id: BlockID) -> bool {
+        self.nodes[node].state.db.lock().await.get_block_by_id(block_id).await.is_ok()
+    }
+
+    /// The latest block of each account in the chain of a node
+    pub async fn chains(&self, node: usize) -> Result<BTreeMap<AccountID, BlockID>> {
+        let db = self.nodes[node].state.db.lock().await;
+        let mut chains = BTreeMap::new();
+        for account in db.get_accounts().await? {
+            chains.insert(account, db.get_latest_block_by_account(account).await?.get_id());
+        }
+        Ok(chains)
+    }
+
+    async fn converged(&self) -> Result<bool> {
+        let first = self.chains(0).await?;
+        for (index, node) in self.nodes.iter().enumerate() {
+            if node.state.blockpool_client.get_queue_size().await? > 0 || self.chains(index).await? != first {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Waits until every connection is established and each node received a ping from all of its peers
+    ///
+    /// Nodes can connect to more peers than they dialed, since they learn about peers from each other.
+    async fn wait_for_peers(&self, connections: &[(usize, usize)]) -> Result<()> {
+        self.wait_until("the nodes to connect", || async {
+            Ok(self.nodes.iter().enumerate().all(|(index, node)| {
+                let dialed = connections.iter().filter(|(a, b)| *a == index || *b == index).count();
+                node.peers.len() >= dialed && node.peers.iter().all(|peer| peer.account.is_some())
+            }))
+        })
+        .await
+    }
+
+    async fn wait_until<F, Fut>(&self, what: &str, condition: F) -> Result<()>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = Result<bool>>,
+    {
+        let waiting = async {
+            while !condition().await? {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            Ok(())
+        };
+
+        tokio::time::timeout(TIMEOUT, waiting).await.map_err(|_| anyhow!("timed out waiting for {what}"))?
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        for node in &self.nodes {
+            node.server.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus::equivocation;
+    use crate::fixtures::{account, child_block, genesis_block};
+    use crate::p2p::types::RequestBodyData;
+    use crate::sync::service::sync_changed_accounts;
+    use crypto::signatures::ed25519::generate_private_key;
+    use pog_proto::p2p::request_body;
+
+    const NETWORK: [Mode; 4] = [Mode::Prime, Mode::Prime, Mode::Prime, Mode::Validating];
+
+    #[tokio::test]
+    async fn test_block_submission() -> Result<()> {
+        let harness = Harness::start(&NETWORK).await?;
+        let private_key = generate_private_key()?;
+        let genesis = genesis_block(&private_key, 100);
+        let child = child_block(&private_key, &genesis);
+
+        // the second block can reach a node before the first one is confirmed there
+        harness
+            .run(&[
+                Step::Submit {
+                    node: 3,
+                    block: genesis.clone(),
+                },
+                Step::Submit {
+                    node: 1,
+                    block: child.clone(),
+                },
+                Step::Converge,
+            ])
+            .await?;
+
+        for node in 0..harness.nodes.len() {
+            assert!(harness.has_block(node, genesis.get_id()).await);
+            assert!(harness.has_block(node, child.get_id()).await);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handshake() -> Result<()> {
+        let harness = Harness::start(&NETWORK).await?;
+
+        // peers prove their account in pings and pongs and get the active power of the current epoch
+        for node in &harness.nodes {
+            for peer in node.peers.iter() {
+                let account = peer.account.expect("peer should have proved its account");
+                assert_eq!(node.state.epoch_power.power(&account), peer.voting_power);
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gossip() -> Result<()> {
+        // each node only dials the one before it, so votes have to be forwarded to reach all prime delegates
+        let harness = Harness::start_connected(&NETWORK, &[(0, 1), (1, 2), (2, 3)]).await?;
+        let block = genesis_block(&generate_private_key()?, 100);
+
+        harness
+            .run(&[
+                Step::Submit {
+                    node: 3,
+                    block: block.clone(),
+                },
+                Step::Converge,
+            ])
+            .await?;
+
+        for node in 0..harness.nodes.len() {
+            assert!(harness.has_block(node, block.get_id()).await);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_partition() -> Result<()> {
+        let harness = Harness::start(&NETWORK).await?;
+        let block = genesis_block(&generate_private_key()?, 100);
+
+        // two of the three prime delegates still reach quorum
+        harness
+            .run(&[
+                Step::Partition(vec![0], vec![1, 2, 3]),
+                Step::Submit {
+                    node: 3,
+                    block: block.clone(),
+                },
+                Step::WaitForBlock {
+                    nodes: vec![1, 2, 3],
+                    block_id: block.get_id(),
+                },
+            ])
+            .await?;
+        assert!(!harness.has_block(0, block.get_id()).await);
+
+        harness.run(&[Step::Heal, Step::Converge]).await?;
+        assert!(harness.has_block(0, block.get_id()).await);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delayed_messages() -> Result<()> {
+        let harness = Harness::start(&NETWORK).await?;
+        let block = genesis_block(&generate_private_key()?, 100);
+        let delay = Duration::from_millis(500);
+
+        let submitted = tokio::time::Instant::now();
+        harness
+            .run(&[
+                Step::Delay {
+                    from: 0,
+                    delay,
+                },
+                Step::Submit {
+                    node: 0,
+                    block: block.clone(),
+                },
+            ])
+            .await?;
+
+        // only the first node has the block, so no node can confirm it before the others get it
+        tokio::time::sleep(delay / 2).await;
+        for node in 0..harness.nodes.len() {
+            assert!(!harness.has_block(node, block.get_id()).await);
+        }
+
+        harness
+            .run(&[
+                Step::WaitForBlock {
+                    nodes: vec![1, 2, 3],
+                    block_id: block.get_id(),
+                },
+                Step::Converge,
+            ])
+            .await?;
+        assert!(submitted.elapsed() >= delay);
+        assert!(harness.has_block(0, block.get_id()).await);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync() -> Result<()> {
+        let harness = Harness::start(&[Mode::Prime, Mode::Validating]).await?;
+
+        // blocks the second node missed
+        let private_key = generate_private_key()?;
+        let genesis = genesis_block(&private_key, 100);
+        let child = child_block(&private_key, &genesis);
+        {
+            let mut db = harness.nodes[0].state.db.lock().await;
+            db.add_block(genesis.clone()).await?;
+            db.add_block(child.clone()).await?;
+        }
+
+        sync_changed_accounts(&harness.nodes[1].state, 0).await?;
+        assert!(harness.has_block(1, genesis.get_id()).await);
+        assert!(harness.has_block(1, child.get_id()).await);
+        assert_eq!(harness.chains(0).await?, harness.chains(1).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_double_signer() -> Result<()> {
+        let harness = Harness::start(&NETWORK).await?;
+
+        // a malicious wallet signs two different blocks for the same height and sends them to different nodes
+        let private_key = generate_private_key()?;
+        let wallet = account(&private_key)?;
+        let (first, second) = (genesis_block(&private_key, 100), genesis_block(&private_key, 200));
+        harness.run(&[Step::SubmitAtOnce(vec![(0, first.clone()), (1, second.clone())]), Step::Converge]).await?;
+
+        // every node added the same block and rejected the other one
+        let chosen = harness.chains(0).await?.get(&wallet).copied();
+        assert!(chosen == Some(first.get_id()) || chosen == Some(second.get_id()));
+        for node in 0..harness.nodes.len() {
+            assert_eq!(harness.chains(node).await?.get(&wallet).copied(), chosen);
+            assert_ne!(harness.has_block(node, first.get_id()).await, harness.has_block(node, second.get_id()).await);
+        }
+
+        // a prime delegate votes for both blocks
+        let voter = &harness.nodes[2];
+        for block in [&first, &second] {
+            voter.state.p2p_client.broadcast(RequestBodyData::VoteProposal(request_body::VoteProposal {
+                block: Some(raw_block(block)),
+                vote: 1,
+            }))?;
+        }
+
+        // the other prime delegates exclude it, but nobody else
+        harness
+            .wait_until("the double voter to be excluded", || async {
+                Ok(harness.nodes[..2]
+                    .iter()
+                    .all(|node| node.state.equivocations.is_excluded(&voter.account, timestamp())))
+            })
+            .await?;
+        for node in &harness.nodes[..2] {
+            assert_eq!(node.state.equivocations.excluded(timestamp()), vec![voter.account]);
+            let evidence = equivocation::get_evidence(&node.state).await?;
+            assert!(evidence.iter().all(|evidence| evidence.voter == voter.account));
+            assert!(evidence.iter().any(|evidence| evidence.account == wallet));
+        }
+        Ok(())
+    }
+}
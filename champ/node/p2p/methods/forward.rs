@@ -1,11 +1,59 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use encoding::account::generate_account_address;
 use libp2p::PeerId;
 use pog_proto::p2p::request_body;
 
-use crate::p2p::server::P2PServer;
+use crate::consensus::equivocation::SignedVote;
+use crate::p2p::gossip;
+use crate::p2p::methods::{process_final_vote, process_vote_proposal};
+use crate::p2p::server::{decode_request, timestamp, MessageError, P2PServer, RequestResponse};
+use crate::p2p::types::RequestBodyData;
 
-pub fn process_forward(_server: &mut P2PServer, _data: request_body::Forward, peer_id: PeerId) -> Result<()> {
-    print!("got a forwarded message from {peer_id:?}");
+/// Processes a gossiped request and forwards it to more peers
+///
+/// The forwarded request is signed by the node that broadcast it, so votes count for that node's account and not for
+/// the peer that forwarded them. Requests are only forwarded the first time they are seen and if they are valid.
+/// The broadcasting node is not connected to this node, so instead of its peer id its account has to be registered.
+pub async fn process_forward(server: &mut P2PServer, data: request_body::Forward, peer_id: PeerId) -> Result<()> {
+    if !server.seen_messages.insert(&data.data) {
+        tracing::trace!("already got the message forwarded by {peer_id}");
+        return Ok(());
+    }
 
+    // the request keeps the timestamp and signature of the node that broadcast it
+    let now = timestamp();
+    let window = server.state.config.read().await.consensus.request_window;
+    let (header, request) = decode_request(&data.data, &data.header, now, window)?;
+
+    let signer = generate_account_address(header.public_key.clone())?;
+    if server.state.db.lock().await.get_latest_block_by_account(signer).await.is_err() {
+        return Err(MessageError::UnknownSigner.into());
+    }
+    // the request was already sent to this node directly
+    if !server.replays.insert(&header.public_key, &header.signature, now, window) {
+        return Ok(());
+    }
+
+    match request {
+        RequestBodyData::VoteProposal(proposal) => {
+            let signed_vote = SignedVote::from_request(&data.data, &header);
+            process_vote_proposal(server, proposal, &header, signed_vote, peer_id).await?
+        }
+        RequestBodyData::FinalVote(vote) => {
+            let signed_vote = SignedVote::from_request(&data.data, &header);
+            process_final_vote(server, vote, &header, signed_vote, peer_id).await?
+        }
+        _ => return Err(anyhow!("only votes can be forwarded")),
+    }
+
+    // peers can't make a request travel further than this node would send it
+    let ttl = data.ttl.min(gossip::TTL);
+    if ttl > 1 {
+        let forward = request_body::Forward {
+            ttl: ttl - 1,
+            ..data
+        };
+        server.forward(forward, Some(peer_id))?;
+    }
     Ok(())
 }
@@ -1,7 +1,9 @@
 mod forward;
 mod ping;
+mod sync;
 mod vote;
 
 pub use forward::process_forward;
-pub use ping::process_ping;
+pub use ping::{account_proof, process_ping, process_pong};
+pub use sync::{process_get_blocks, process_get_changed_accounts, process_get_latest_block};
 pub use vote::{process_final_vote, process_vote_proposal};
@@ -1,35 +1,107 @@
-use anyhow::Result;
+use std::convert::TryInto;
+
+use anyhow::{anyhow, Result};
 use crypto::rand::prelude::IteratorRandom;
+use crypto::signatures::ed25519::verify_signature;
+use encoding::account::generate_account_address;
 use libp2p::{request_response::ResponseChannel, PeerId};
-use pog_proto::p2p::{request_body, response_body};
+use pog_proto::api::AccountID;
+use pog_proto::p2p::{request_body, response_body, AccountProof};
 
+use crate::consensus::epoch_power;
 use crate::p2p::{
+    discovery::PeerSource,
     protocol::PogResponse,
+    reputation::Behavior,
     server::{timestamp, P2PServer, RequestResponse},
+    types::RequestHeader,
 };
+use crate::state::ChampStateArc;
 use pog_proto::p2p::response_body::Data as ResponseBodyData;
 
 const PING_PEER_COUNT: usize = 10;
 
-pub fn process_ping(
-    server: &mut P2PServer,
-    _data: request_body::Ping,
-    channel: ResponseChannel<PogResponse>,
+/// Proves that this node controls its account
+///
+/// The proof signs this node's peer id, so other nodes can't claim the account with it.
+pub fn account_proof(server: &mut P2PServer) -> Result<AccountProof> {
+    let peer_id = server.peer_id().to_bytes();
+    Ok(AccountProof {
+        account_id: server.node_wallet.account_address_bytes.to_vec(),
+        public_key: server.node_wallet.public_key()?.to_vec(),
+        signature: server.node_wallet.sign(&peer_id)?.to_vec(),
+    })
+}
+
+/// Verifies the account a peer claims to control
+///
+/// Returns `None` if the account is not in the local storage yet, e.g. because this node has not synced it.
+pub async fn verify_account_proof(
+    state: &ChampStateArc,
     peer_id: PeerId,
-) -> Result<()> {
-    tracing::debug!("got a ping, now sending pong");
+    proof: &AccountProof,
+) -> Result<Option<AccountID>> {
+    let account: AccountID = proof.account_id.as_slice().try_into().map_err(|_| anyhow!("invalid account id"))?;
+    if generate_account_address(proof.public_key.clone())? != account {
+        return Err(anyhow!("public key does not belong to the account"));
+    }
+    verify_signature(&peer_id.to_bytes(), &proof.public_key, &proof.signature)
+        .map_err(|_| anyhow!("invalid account signature"))?;
+
+    match state.db.lock().await.get_latest_block_by_account(account).await {
+        Ok(_) => Ok(Some(account)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Updates the account and voting power of a peer from the proof it sent in a ping or pong
+///
+/// The peer votes with the active power of its account in the current epoch, which counts towards the online power
+/// of the network right away if it is a prime delegate.
+async fn update_peer(server: &mut P2PServer, peer_id: PeerId, proof: Option<AccountProof>) -> Result<()> {
+    let account = match proof {
+        Some(proof) => match verify_account_proof(&server.state, peer_id, &proof).await {
+            Ok(account) => account,
+            Err(e) => {
+                tracing::debug!("{peer_id} sent an invalid account proof: {e}");
+                server.report(peer_id, Behavior::InvalidSignature).await;
+                None
+            }
+        },
+        None => None,
+    };
+    let voting_power = account.and_then(|account| epoch_power::get_vote_power(&server.state, &account).ok());
 
     {
-        let mut peer = server.peers.get_mut(&peer_id).ok_or_else(|| anyhow::anyhow!("cannot get peer"))?;
+        let mut peer = server.peers.get_mut(&peer_id).ok_or_else(|| anyhow!("cannot get peer"))?;
         peer.last_ping = Some(timestamp());
+        peer.account = account;
+        peer.voting_power = voting_power;
     }
 
+    // the peer may have just come online or proved a different account
+    server.update_online_power().await;
+    Ok(())
+}
+
+pub async fn process_ping(
+    server: &mut P2PServer,
+    data: request_body::Ping,
+    _header: &RequestHeader,
+    channel: ResponseChannel<PogResponse>,
+    peer_id: PeerId,
+) -> Result<()> {
+    tracing::debug!("got a ping, now sending pong");
+    update_peer(server, peer_id, data.account).await?;
+
     let peers = {
         // choose a number of random peers
+        // peers that connected to us are only known by the port they dialed from, which can't be dialed
         let mut r = crypto::rand::thread_rng();
         server
             .peers
             .iter()
+            .filter(|p| p.source != PeerSource::Inbound)
             .choose_multiple(&mut r, PING_PEER_COUNT)
             .iter()
             .map(|p| p.ip.to_vec())
@@ -38,6 +110,64 @@ pub fn process_ping(
 
     let pong = response_body::Pong {
         peers,
+        account: Some(account_proof(server)?),
     };
     server.send_response(channel, ResponseBodyData::Pong(pong))
 }
+
+/// Verifies the account of the peer and dials the peers of a pong this node does not know yet
+pub async fn process_pong(server: &mut P2PServer, pong: response_body::Pong, peer_id: PeerId) -> Result<()> {
+    tracing::debug!("got a pong with {} peers from {peer_id}", pong.peers.len());
+    update_peer(server, peer_id, pong.account).await?;
+    server.discover_peers(peer_id, &pong.peers).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, genesis_block};
+    use crate::state::ChampState;
+    use crypto::signatures::ed25519::{create_public_key, create_signature, generate_private_key};
+
+    fn proof(private_key: &[u8], peer_id: PeerId) -> Result<AccountProof> {
+        let public_key = create_public_key(private_key)?.to_vec();
+        Ok(AccountProof {
+            account_id: generate_account_address(public_key.clone())?.to_vec(),
+            signature: create_signature(&peer_id.to_bytes(), private_key)?.to_vec(),
+            public_key,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_verify_account_proof() -> Result<()> {
+        let state = ChampState::mock().await;
+        let (private_key, other_key) = (generate_private_key()?, generate_private_key()?);
+        let (peer_id, other_peer) = (PeerId::random(), PeerId::random());
+        let valid = proof(&private_key, peer_id)?;
+
+        // accounts that are not in the local storage are not trusted yet
+        assert_eq!(None, verify_account_proof(&state, peer_id, &valid).await?);
+
+        state.db.lock().await.add_block(genesis_block(&private_key, 100)).await?;
+        let account = generate_account_address(valid.public_key.clone())?;
+        assert_eq!(Some(account), verify_account_proof(&state, peer_id, &valid).await?);
+
+        // the proof can't be used by another peer
+        verify_account_proof(&state, other_peer, &valid).await.expect_err("proof is for another peer");
+
+        // or for another account
+        let other_account = AccountProof {
+            account_id: fixtures::account(&other_key)?.to_vec(),
+            ..valid.clone()
+        };
+        verify_account_proof(&state, peer_id, &other_account).await.expect_err("proof is for another account");
+
+        let forged = AccountProof {
+            signature: create_signature(&peer_id.to_bytes(), &other_key)?.to_vec(),
+            ..valid
+        };
+        verify_account_proof(&state, peer_id, &forged).await.expect_err("proof is not signed by the account");
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use std::convert::TryInto;
+
+use anyhow::Result;
+use libp2p::request_response::ResponseChannel;
+use pog_proto::api::{AccountID, RawBlock};
+use pog_proto::p2p::{request_body, response_body};
+
+use crate::p2p::{
+    protocol::PogResponse,
+    server::{P2PServer, RequestResponse},
+    types::{Failure, ResponseBodyData},
+};
+use crate::sync::{MAX_ACCOUNTS, MAX_BLOCKS};
+
+/// Sends the latest block of an account
+pub async fn process_get_latest_block(
+    server: &mut P2PServer,
+    data: request_body::GetLatestBlock,
+    channel: ResponseChannel<PogResponse>,
+) -> Result<()> {
+    let account_id: AccountID = match data.account_id.try_into() {
+        Ok(account_id) => account_id,
+        Err(_) => return server.send_response(channel, ResponseBodyData::Failure(Failure::MalformedRequest.into())),
+    };
+
+    let block = server.state.db.lock().await.get_latest_block_by_account(account_id).await.ok();
+    let latest_block = response_body::LatestBlock {
+        height: block.as_ref().map_or(0, |block| block.data.height),
+        block: block.map(Into::into),
+    };
+    server.send_response(channel, ResponseBodyData::LatestBlock(latest_block))
+}
+
+/// Sends the blocks of an account from `from_height` up to `to_height`, at most `MAX_BLOCKS` at a time
+pub async fn process_get_blocks(
+    server: &mut P2PServer,
+    data: request_body::GetBlocks,
+    channel: ResponseChannel<PogResponse>,
+) -> Result<()> {
+    let account_id: AccountID = match data.account_id.try_into() {
+        Ok(account_id) => account_id,
+        Err(_) => return server.send_response(channel, ResponseBodyData::Failure(Failure::MalformedRequest.into())),
+    };
+
+    let to_height = data.to_height.min(data.from_height.saturating_add(MAX_BLOCKS - 1));
+    let mut blocks: Vec<RawBlock> = vec![];
+    {
+        let db = server.state.db.lock().await;
+        for height in data.from_height..=to_height {
+            match db.get_block_by_height(account_id, &height).await? {
+                Some(block) => blocks.push(block.into()),
+                None => break,
+            }
+        }
+    }
+
+    server.send_response(
+        channel,
+        ResponseBodyData::Blocks(response_body::Blocks {
+            blocks,
+        }),
+    )
+}
+
+/// Sends the accounts whose latest block was signed after `since`, `MAX_ACCOUNTS` at a time starting at `offset`
+pub async fn process_get_changed_accounts(
+    server: &mut P2PServer,
+    data: request_body::GetChangedAccounts,
+    channel: ResponseChannel<PogResponse>,
+) -> Result<()> {
+    let accounts =
+        server.state.db.lock().await.get_changed_accounts(data.since, data.offset as usize, MAX_ACCOUNTS).await?;
+    let accounts = accounts.into_iter().map(|account_id| account_id.to_vec()).collect();
+
+    server.send_response(
+        channel,
+        ResponseBodyData::ChangedAccounts(response_body::ChangedAccounts {
+            accounts,
+        }),
+    )
+}
@@ -1,58 +1,50 @@
 use anyhow::{anyhow, Result};
+use encoding::account::generate_account_address;
 use libp2p::PeerId;
 use pog_proto::p2p::request_body;
 
-use crate::p2p::types::RequestBodyData;
-use crate::{
-    consensus::voting_power,
-    p2p::server::{P2PServer, RequestResponse},
-};
+use crate::consensus::equivocation::{self, SignedVote};
+use crate::p2p::server::P2PServer;
+use crate::p2p::types::RequestHeader;
 
 pub async fn process_vote_proposal(
     server: &mut P2PServer,
     data: request_body::VoteProposal,
+    header: &RequestHeader,
+    signed_vote: SignedVote,
     _peer_id: PeerId,
 ) -> Result<()> {
-    // if prime delegate: cast own vote and send to all other prime delegates and 10 non prime delegates
     let raw_block = match data.block {
         Some(block) => block,
         None => return Err(anyhow!("block was none")),
     };
 
-    if server.state.blockpool_client.process_vote_proposal(raw_block.clone(), data.vote).await.is_err() {
-        return Err(anyhow!("error during processing vote"));
-    }
-
-    //TODO: Only add vote if this is a prime delegate
-    let data = request_body::VoteProposal {
-        block: Some(raw_block),
-        vote: voting_power::get_active_power(&server.state, server.node_wallet.account_address_bytes).await?,
+    // the vote is weighted by the power of the account that signed the request, not by `data.vote`
+    // nodes that are not prime delegates propose blocks without voting for them
+    let voter = match data.vote {
+        0 => None,
+        _ => Some(generate_account_address(header.public_key.to_vec())?),
     };
 
-    //TODO: If quorum has been reached, send FinalVote
-    let _ = server.standard_send(RequestBodyData::VoteProposal(data));
+    equivocation::record(&server.state, signed_vote).await?;
 
-    Ok(())
+    // the blockpool casts our own vote and sends it to the network
+    server.state.blockpool_client.process_vote_proposal(raw_block, voter).await
 }
 
-pub fn process_final_vote(_server: &mut P2PServer, _data: request_body::FinalVote, _peer_id: PeerId) -> Result<()> {
-    unimplemented!("For now, we're just implementing vote proposals and will find a way to make this more generic for final votes in the future");
-    //     // all nodes who calculate a 60% quorum from the vote proposal need to send a final vote
-    //     let raw_block = match data.block {
-    //         Some(block) => block,
-    //         None => return Err(anyhow!("block was none")),
-    //     };
-
-    //     if self.state.blockpool_client.process_vote(raw_block.clone(), data.vote, true).await.is_err() {
-    //         return Err(anyhow!("error during processing vote"));
-    //     }
-
-    //     let data = request_body::FinalVote {
-    //         block: Some(raw_block),
-    //         vote: voting_power::get_active_power(&self.state, self.primary_wallet.account_address_bytes).await?,
-    //     };
-
-    //     self.standard_send(RequestBodyData::FinalVote(data))
+pub async fn process_final_vote(
+    server: &mut P2PServer,
+    data: request_body::FinalVote,
+    header: &RequestHeader,
+    signed_vote: SignedVote,
+    _peer_id: PeerId,
+) -> Result<()> {
+    let raw_block = match data.block {
+        Some(block) => block,
+        None => return Err(anyhow!("block was none")),
+    };
 
-    // Ok(())
+    let voter = generate_account_address(header.public_key.to_vec())?;
+    equivocation::record(&server.state, signed_vote).await?;
+    server.state.blockpool_client.process_final_vote(raw_block, voter).await
 }
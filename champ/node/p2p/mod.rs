@@ -1,5 +1,15 @@
+pub mod client;
+#[cfg(test)]
+mod conditions;
+pub mod discovery;
+pub mod gossip;
+#[cfg(test)]
+mod harness;
 mod methods;
 mod metrics;
+pub mod peer_store;
 pub mod protocol;
+pub mod replay;
+pub mod reputation;
 pub mod server;
-mod types;
+pub mod types;
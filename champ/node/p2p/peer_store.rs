@@ -0,0 +1,129 @@
+use anyhow::Result;
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+
+use crate::p2p::discovery::PeerSource;
+use crate::p2p::types::Peer;
+use crate::state::ChampStateArc;
+
+// peers that were not connected for this many seconds are forgotten (a week)
+const PEER_EXPIRY: u64 = 60 * 60 * 24 * 7;
+
+/// A peer this node was connected to, kept across restarts
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StoredPeer {
+    /// the encoded multiaddr the peer was dialed at
+    pub address: Vec<u8>,
+    /// when the node was last connected to the peer, in seconds since the unix epoch
+    pub last_seen: u64,
+    pub score: i32,
+    pub voting_power: Option<u64>,
+}
+
+impl StoredPeer {
+    fn is_expired(&self, now: u64) -> bool {
+        self.last_seen.saturating_add(PEER_EXPIRY) <= now
+    }
+}
+
+/// Stores connected peers that can be dialed again
+///
+/// Peers that connected to this node are skipped, since the address they connected from is usually not one they
+/// listen on.
+pub async fn save(state: &ChampStateArc, peers: &[Peer], now: u64) -> Result<()> {
+    let mut db = state.db.lock().await;
+    for peer in peers.iter().filter(|peer| peer.source != PeerSource::Inbound) {
+        let stored = StoredPeer {
+            address: peer.ip.to_vec(),
+            last_seen: now,
+            score: peer.score,
+            voting_power: peer.voting_power,
+        };
+        db.set_peer(peer.id.to_bytes(), serde_json::to_vec(&stored)?).await?;
+    }
+    Ok(())
+}
+
+/// Loads the stored peers and forgets the ones that were not connected for a long time
+pub async fn load(state: &ChampStateArc, now: u64) -> Result<Vec<(PeerId, StoredPeer)>> {
+    let mut db = state.db.lock().await;
+    let mut peers = vec![];
+    for (peer_id, stored) in db.get_peers().await? {
+        let peer = match (PeerId::from_bytes(&peer_id), serde_json::from_slice::<StoredPeer>(&stored)) {
+            (Ok(id), Ok(stored)) if !stored.is_expired(now) => (id, stored),
+            _ => {
+                db.remove_peer(peer_id).await?;
+                continue;
+            }
+        };
+        peers.push(peer);
+    }
+    Ok(peers)
+}
+
+/// The addresses of the `max` best stored peers that are not banned
+///
+/// Peers with the most voting power come first, then the ones with the best score and the ones seen most recently.
+pub async fn best(state: &ChampStateArc, max: usize, now: u64) -> Result<Vec<Multiaddr>> {
+    let mut peers = load(state, now).await?;
+    peers.retain(|(peer_id, _)| !state.bans.is_banned(peer_id, now));
+    peers
+        .sort_by(|(_, a), (_, b)| (b.voting_power, b.score, b.last_seen).cmp(&(a.voting_power, a.score, a.last_seen)));
+
+    Ok(peers.into_iter().filter_map(|(_, peer)| Multiaddr::try_from(peer.address).ok()).take(max).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::p2p::reputation;
+    use crate::state::ChampState;
+
+    fn peer(port: u16, score: i32, voting_power: Option<u64>, source: PeerSource) -> Peer {
+        Peer {
+            id: PeerId::random(),
+            ip: format!("/ip4/10.0.0.1/tcp/{port}").parse().unwrap(),
+            account: None,
+            voting_power,
+            last_ping: None,
+            source,
+            score,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_best_peers() {
+        let state = ChampState::mock().await;
+        let peers = vec![
+            peer(1, 0, None, PeerSource::Initial),
+            peer(2, 10, None, PeerSource::Initial),
+            peer(3, 0, Some(100), PeerSource::Initial),
+            peer(4, 50, Some(100), PeerSource::Inbound),
+            peer(5, 50, None, PeerSource::Initial),
+        ];
+        save(&state, &peers[..1], 0).await.unwrap();
+        save(&state, &peers[1..], 10).await.unwrap();
+        reputation::ban(&state, peers[4].id, 100).await.unwrap();
+
+        let addresses = best(&state, 10, 20).await.unwrap();
+        assert_eq!(vec![peers[2].ip.clone(), peers[1].ip.clone(), peers[0].ip.clone()], addresses);
+        assert_eq!(vec![peers[2].ip.clone()], best(&state, 1, 20).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_expiry() {
+        let state = ChampState::mock().await;
+        let peers = vec![peer(1, 0, None, PeerSource::Initial), peer(2, 0, None, PeerSource::Initial)];
+        save(&state, &peers[..1], 0).await.unwrap();
+        save(&state, &peers[1..], 10).await.unwrap();
+
+        assert_eq!(2, load(&state, PEER_EXPIRY - 1).await.unwrap().len());
+        assert_eq!(
+            vec![peers[1].id],
+            load(&state, PEER_EXPIRY).await.unwrap().into_iter().map(|(id, _)| id).collect::<Vec<_>>()
+        );
+
+        // expired peers are removed
+        assert_eq!(1, state.db.lock().await.get_peers().await.unwrap().len());
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::{HashSet, VecDeque};
+
+// signatures that are remembered at most, so a flood of requests can't exhaust memory
+const MAX_SIGNATURES: usize = 100_000;
+
+/// Whether a request signed at `timestamp` is within `window` seconds of `now`
+pub fn is_fresh(timestamp: u64, now: u64, window: u64) -> bool {
+    timestamp.max(now) - timestamp.min(now) <= window
+}
+
+/// Signatures of recently received requests, so a captured request can't be sent again
+///
+/// Requests are only accepted within `consensus.request_window` seconds of their timestamp, so a signature only has to
+/// be remembered until its request would be rejected anyway. Under heavy load the oldest signatures are forgotten
+/// earlier.
+#[derive(Debug, Default)]
+pub struct ReplayCache {
+    signatures: HashSet<(Vec<u8>, Vec<u8>)>,
+    // signatures with the time they were received, oldest first
+    order: VecDeque<(u64, (Vec<u8>, Vec<u8>))>,
+}
+
+impl ReplayCache {
+    /// Remembers the signature of a request and returns false if it was seen before
+    pub fn insert(&mut self, public_key: &[u8], signature: &[u8], now: u64, window: u64) -> bool {
+        // a request received at `received` has a timestamp of at least `received - window`
+        while let Some((received, _)) = self.order.front() {
+            if received.saturating_add(window.saturating_mul(2)) >= now {
+                break;
+            }
+            self.pop_oldest();
+        }
+
+        let key = (public_key.to_vec(), signature.to_vec());
+        if !self.signatures.insert(key.clone()) {
+            return false;
+        }
+
+        self.order.push_back((now, key));
+        while self.order.len() > MAX_SIGNATURES {
+            self.pop_oldest();
+        }
+        true
+    }
+
+    fn pop_oldest(&mut self) {
+        if let Some((_, key)) = self.order.pop_front() {
+            self.signatures.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_fresh() {
+        assert!(is_fresh(100, 100, 0));
+        assert!(is_fresh(40, 100, 60));
+        assert!(is_fresh(160, 100, 60));
+        assert!(!is_fresh(39, 100, 60));
+        assert!(!is_fresh(161, 100, 60));
+    }
+
+    #[test]
+    fn test_replay_cache() {
+        let mut cache = ReplayCache::default();
+        assert!(cache.insert(b"key", b"signature", 0, 60));
+        assert!(!cache.insert(b"key", b"signature", 100, 60));
+        assert!(cache.insert(b"other key", b"signature", 100, 60));
+
+        // signatures are forgotten once their requests are too old to be accepted
+        assert!(!cache.insert(b"key", b"signature", 120, 60));
+        assert!(cache.insert(b"key", b"signature", 121, 60));
+
+        for signature in 0..MAX_SIGNATURES as u32 {
+            cache.insert(b"key", &signature.to_be_bytes(), 200, 60);
+        }
+        assert_eq!(MAX_SIGNATURES, cache.signatures.len());
+        assert!(cache.insert(b"other key", b"signature", 200, 60));
+    }
+}
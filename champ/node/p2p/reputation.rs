@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use anyhow::Result;
+use libp2p::PeerId;
+
+use crate::state::ChampStateArc;
+
+/// Peers are disconnected and banned once their score drops to this
+pub const BAN_THRESHOLD: i32 = -100;
+// a peer can't build up more score than this before it starts misbehaving
+const MAX_SCORE: i32 = 100;
+
+/// Something a peer did that changes its score
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Behavior {
+    /// a message was not signed by the key in its header
+    InvalidSignature,
+    /// a message or its ADAD encoding could not be decoded
+    Malformed,
+    /// a vote or forwarded request failed validation
+    InvalidBlock,
+    /// a request to the peer timed out
+    Timeout,
+    /// the peer answered a request of this node
+    UsefulResponse,
+}
+
+impl Behavior {
+    fn score(self) -> i32 {
+        match self {
+            Behavior::InvalidSignature => -50,
+            Behavior::Malformed => -20,
+            Behavior::InvalidBlock => -10,
+            Behavior::Timeout => -5,
+            Behavior::UsefulResponse => 1,
+        }
+    }
+}
+
+/// The score of a peer after it behaved in some way
+pub fn update_score(score: i32, behavior: Behavior) -> i32 {
+    score.saturating_add(behavior.score()).min(MAX_SCORE)
+}
+
+/// Peers that are not allowed to connect until a timestamp
+#[derive(Debug, Default)]
+pub struct Bans {
+    bans: RwLock<HashMap<PeerId, u64>>,
+}
+
+impl Bans {
+    pub fn is_banned(&self, peer_id: &PeerId, now: u64) -> bool {
+        self.bans.read().expect("bans lock is poisoned").get(peer_id).map_or(false, |until| *until > now)
+    }
+
+    /// Peers that are currently banned, with the timestamp their ban ends at
+    pub fn banned(&self, now: u64) -> Vec<(PeerId, u64)> {
+        let bans = self.bans.read().expect("bans lock is poisoned");
+        bans.iter().filter(|(_, until)| **until > now).map(|(peer_id, until)| (*peer_id, *until)).collect()
+    }
+
+    fn insert(&self, peer_id: PeerId, until: u64) {
+        self.bans.write().expect("bans lock is poisoned").insert(peer_id, until);
+    }
+
+    fn remove(&self, peer_id: &PeerId) -> bool {
+        self.bans.write().expect("bans lock is poisoned").remove(peer_id).is_some()
+    }
+}
+
+/// Bans a peer until a timestamp
+///
+/// The p2p server disconnects banned peers and refuses their connections until the ban ends.
+pub async fn ban(state: &ChampStateArc, peer_id: PeerId, until: u64) -> Result<()> {
+    state.db.lock().await.add_ban(peer_id.to_bytes(), until).await?;
+    state.bans.insert(peer_id, until);
+    Ok(())
+}
+
+/// Lifts the ban of a peer and returns whether it was banned
+pub async fn unban(state: &ChampStateArc, peer_id: PeerId) -> Result<bool> {
+    state.db.lock().await.remove_ban(peer_id.to_bytes()).await?;
+    Ok(state.bans.remove(&peer_id))
+}
+
+/// Bans the stored peers again, e.g. after a restart
+pub async fn restore(state: &ChampStateArc) -> Result<()> {
+    for (peer_id, until) in state.db.lock().await.get_bans().await? {
+        match PeerId::from_bytes(&peer_id) {
+            Ok(peer_id) => state.bans.insert(peer_id, until),
+            Err(_) => tracing::warn!("ignoring ban of an invalid peer id"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ChampState;
+
+    #[test]
+    fn test_update_score() {
+        assert_eq!(-50, update_score(0, Behavior::InvalidSignature));
+        assert_eq!(MAX_SCORE, update_score(MAX_SCORE, Behavior::UsefulResponse));
+        assert_eq!(i32::MIN, update_score(i32::MIN, Behavior::Timeout));
+
+        let score = (0..5).fold(0, |score, _| update_score(score, Behavior::Malformed));
+        assert!(score <= BAN_THRESHOLD);
+    }
+
+    #[tokio::test]
+    async fn test_bans() {
+        let state = ChampState::mock().await;
+        let (peer, other) = (PeerId::random(), PeerId::random());
+
+        ban(&state, peer, 100).await.unwrap();
+        ban(&state, other, 10).await.unwrap();
+        assert!(state.bans.is_banned(&peer, 50));
+        assert!(!state.bans.is_banned(&other, 50));
+        assert_eq!(vec![(peer, 100)], state.bans.banned(50));
+
+        // bans are stored
+        state.bans.remove(&peer);
+        restore(&state).await.unwrap();
+        assert!(state.bans.is_banned(&peer, 50));
+
+        assert!(unban(&state, peer).await.unwrap());
+        assert!(!unban(&state, peer).await.unwrap());
+        restore(&state).await.unwrap();
+        assert!(!state.bans.is_banned(&peer, 50));
+    }
+}
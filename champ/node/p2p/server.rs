@@ -1,9 +1,12 @@
 #![allow(dead_code, unused_variables)]
 
+use std::collections::{HashMap, HashSet};
 use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use crate::consensus::{epoch_power, equivocation::SignedVote, network_power::NetworkPowerSnapshot, prime_delegates};
 use crate::p2p::metrics;
 use crate::p2p::types::Peer;
 use crate::state::ChampStateArc;
@@ -12,31 +15,88 @@ use anyhow::{anyhow, Result};
 use crypto::rand::seq::IteratorRandom;
 use crypto::signatures::ed25519::verify_signature;
 use dashmap::DashMap;
+#[cfg(test)]
+use libp2p::core::transport::MemoryTransport;
 use libp2p::core::ConnectedPoint;
 use libp2p::dns::TokioDnsConfig;
+#[cfg(test)]
+use libp2p::futures::{future::BoxFuture, stream::FuturesUnordered};
 use libp2p::identity::{self, ed25519};
 use libp2p::Multiaddr;
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot};
 
+use pog_proto::rpc::node_admin::Mode;
 use pog_proto::Message;
 
 use libp2p::{
     core::{upgrade, Transport},
     futures::StreamExt,
     noise::{self},
-    request_response::{RequestId, RequestResponseEvent, ResponseChannel},
-    swarm::{SwarmBuilder, SwarmEvent},
+    request_response::{OutboundFailure, RequestId, RequestResponseEvent, ResponseChannel},
+    swarm::{ConnectionError, ConnectionHandlerUpgrErr, SwarmBuilder, SwarmEvent},
     tcp::TokioTcpConfig,
     yamux::YamuxConfig,
     {PeerId, Swarm},
 };
 
+#[cfg(test)]
+use super::conditions::LinkConditions;
+use super::discovery::{Discovery, PeerSource};
+use super::gossip::{self, SeenMessages};
 use super::methods;
+use super::peer_store;
 use super::protocol::{PogBehavior, PogMessage, PogRequest, PogResponse};
-use super::types::{protocol, Event, Failure, NodeKeypair, Peers};
+use super::replay::{self, ReplayCache};
+use super::reputation::{self, Behavior};
+use super::types::{protocol, Command, Event, Failure, NodeKeypair, Peers};
 use super::types::{request_body, RequestBody, RequestBodyData, RequestHeader};
 use super::types::{ResponseBody, ResponseBodyData, ResponseHeader};
 
 const NR_OF_PEERS_SENT: usize = 10;
+// peers that have not sent a ping for this many seconds are offline
+const ONLINE_TIMEOUT: u64 = 20;
+const LISTEN_ADDRESS: &str = "/ip4/0.0.0.0/tcp/50052";
+// seconds between saves of the connected peers to the peer store
+const SAVE_INTERVAL: u64 = 60;
+
+/// The transport the p2p server connects to other nodes with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransportKind {
+    Tcp,
+    /// connects to nodes in the same process, for testing a network of nodes
+    #[cfg(test)]
+    Memory,
+}
+
+/// Why a request or response from a peer could not be decoded
+#[derive(Error, Debug)]
+pub enum MessageError {
+    #[error("invalid signature")]
+    InvalidSignature,
+    #[error("malformed message: {0}")]
+    Malformed(String),
+    #[error("request is not bound to the peer or a registered account")]
+    UnknownSigner,
+    #[error("request timestamp is outside of the request window")]
+    Expired,
+    #[error("request was already received")]
+    Replayed,
+}
+
+impl MessageError {
+    /// How the error changes the score of the peer that sent the message
+    ///
+    /// Honest peers can send expired requests if their clock is off, and the same request twice within a second, so
+    /// these don't change the score.
+    pub fn behavior(&self) -> Option<Behavior> {
+        match self {
+            MessageError::InvalidSignature | MessageError::UnknownSigner => Some(Behavior::InvalidSignature),
+            MessageError::Malformed(_) => Some(Behavior::Malformed),
+            MessageError::Expired | MessageError::Replayed => None,
+        }
+    }
+}
 
 pub fn timestamp() -> u64 {
     SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs() as u64
@@ -48,10 +108,34 @@ pub struct P2PServer {
     pub node_wallet: Wallet,
     swarm: Swarm<PogBehavior>,
     keypair: NodeKeypair,
+    rx: mpsc::Receiver<Command>,
+    #[cfg(test)]
+    pub conditions: Arc<LinkConditions>,
+    // messages from peers whose link is delayed or partitioned
+    #[cfg(test)]
+    held: FuturesUnordered<BoxFuture<'static, (PeerId, PogMessage)>>,
+    discovery: Discovery,
+    // requests sent for other parts of the node, which wait for the response
+    pending_requests: HashMap<RequestId, oneshot::Sender<Result<ResponseBodyData>>>,
+    pub seen_messages: SeenMessages,
+    pub replays: ReplayCache,
+    last_saved: u64,
+    // set while the prime delegates and voting power of a new epoch are calculated
+    starting_epoch: Arc<AtomicBool>,
 }
 
 impl P2PServer {
-    pub async fn new(state: ChampStateArc) -> Result<Self> {
+    pub async fn new(state: ChampStateArc, rx: mpsc::Receiver<Command>) -> Result<Self> {
+        Self::with_transport(state, rx, TransportKind::Tcp, LISTEN_ADDRESS.parse()?).await
+    }
+
+    /// Creates a server that listens on `listen_address` right away, so other nodes can dial it before it starts
+    pub async fn with_transport(
+        state: ChampStateArc,
+        rx: mpsc::Receiver<Command>,
+        transport: TransportKind,
+        listen_address: Multiaddr,
+    ) -> Result<Self> {
         let node_wallet = {
             let wallet_manager = state.wallet_manager.read().await;
             let wallet = wallet_manager.primary_wallet().await.ok_or_else(|| anyhow!("no primary wallet found"))?;
@@ -71,18 +155,28 @@ impl P2PServer {
         let pog_protocol = protocol::PogProtocol::new();
 
         let noise = noise::NoiseConfig::xx(dh_keys.clone()).into_authenticated();
-        let transp = TokioDnsConfig::system(TokioTcpConfig::new())?
-            .upgrade(upgrade::Version::V1)
-            .authenticate(noise)
-            .multiplex(YamuxConfig::default())
-            .timeout(Duration::from_secs(10))
-            .boxed();
-
-        let swarm = SwarmBuilder::new(transp, pog_protocol.behavior(), peer_id)
+        let transp = match transport {
+            TransportKind::Tcp => TokioDnsConfig::system(TokioTcpConfig::new())?
+                .upgrade(upgrade::Version::V1)
+                .authenticate(noise)
+                .multiplex(YamuxConfig::default())
+                .timeout(Duration::from_secs(10))
+                .boxed(),
+            #[cfg(test)]
+            TransportKind::Memory => MemoryTransport::default()
+                .upgrade(upgrade::Version::V1)
+                .authenticate(noise)
+                .multiplex(YamuxConfig::default())
+                .timeout(Duration::from_secs(10))
+                .boxed(),
+        };
+
+        let mut swarm = SwarmBuilder::new(transp, pog_protocol.behavior(), peer_id)
             .executor(Box::new(|f| {
                 tokio::task::spawn(f);
             }))
             .build();
+        swarm.listen_on(listen_address)?;
 
         let peers = Arc::new(DashMap::new());
 
@@ -92,17 +186,48 @@ impl P2PServer {
             keypair: dh_keys,
             peers,
             node_wallet,
+            rx,
+            #[cfg(test)]
+            conditions: Arc::new(LinkConditions::default()),
+            #[cfg(test)]
+            held: FuturesUnordered::new(),
+            discovery: Discovery::default(),
+            pending_requests: HashMap::new(),
+            seen_messages: SeenMessages::default(),
+            replays: ReplayCache::default(),
+            last_saved: timestamp(),
+            starting_epoch: Arc::new(AtomicBool::new(false)),
         })
     }
 
+    pub fn peer_id(&self) -> PeerId {
+        *self.swarm.local_peer_id()
+    }
+
     async fn connect_to_initial_peers(&mut self) {
-        let peers = &self.state.config.read().await.consensus.initial_peers;
-        for peer in peers {
+        let config = self.state.config.read().await;
+        let peers = config.consensus.initial_peers.iter().chain(config.chain_spec.bootstrap_peers.iter());
+        for peer in peers.clone() {
             if let Ok(addr) = peer.parse::<Multiaddr>() {
                 let peer_ = self.swarm.dial(addr);
             }
         }
-        tracing::debug!("{peers:?}");
+        tracing::debug!("{:?}", peers.collect::<Vec<_>>());
+
+        // peers from before the last restart, the best ones first
+        let stored = match peer_store::best(&self.state, config.consensus.target_peers, timestamp()).await {
+            Ok(stored) => stored,
+            Err(e) => {
+                tracing::error!("could not load stored peers: {e}");
+                return;
+            }
+        };
+        tracing::debug!("dialing {} stored peers", stored.len());
+        for address in stored {
+            if let Err(e) = self.swarm.dial(address.clone()) {
+                tracing::debug!("could not dial {address}: {e}");
+            }
+        }
     }
 
     fn get_random_peer_ids(&mut self, max_nr_of_peers: usize) -> Vec<PeerId> {
@@ -112,11 +237,13 @@ impl P2PServer {
 
     fn send_ping(&mut self, peer_id: PeerId) -> Result<()> {
         let peers = self.get_random_peer_ids(10).iter().map(|p| p.to_bytes()).collect();
+        let account = Some(methods::account_proof(self)?);
 
         self.send_request(
             &peer_id,
             RequestBodyData::Ping(request_body::Ping {
                 peers,
+                account,
             }),
         )
         .map(|_| ())
@@ -127,7 +254,7 @@ impl P2PServer {
             SwarmEvent::Behaviour(RequestResponseEvent::Message {
                 peer,
                 message,
-            }) => self.process_message(peer, message).await,
+            }) => self.handle_message(peer, message).await,
             SwarmEvent::Behaviour(RequestResponseEvent::ResponseSent {
                 ..
             }) => {}
@@ -140,13 +267,30 @@ impl P2PServer {
                 peer,
                 request_id,
                 error,
-            }) => tracing::error!("outbound message failure: {peer:?}: {error:?}: {request_id}"),
+            }) => {
+                tracing::error!("outbound message failure: {peer:?}: {error:?}: {request_id}");
+                if let Some(resp) = self.pending_requests.remove(&request_id) {
+                    let _ = resp.send(Err(anyhow!("request to {peer} failed: {error:?}")));
+                }
+                if let OutboundFailure::Timeout = error {
+                    self.report(peer, Behavior::Timeout).await;
+                }
+            }
             SwarmEvent::ConnectionEstablished {
                 peer_id,
                 endpoint,
                 num_established,
                 ..
             } => self.handle_new_connection(peer_id, endpoint, num_established),
+            // the peer sent data that could not be decoded, e.g. an invalid ADAD encoding
+            SwarmEvent::ConnectionClosed {
+                peer_id,
+                cause: Some(ConnectionError::Handler(ConnectionHandlerUpgrErr::Upgrade(error))),
+                ..
+            } => {
+                tracing::debug!("connection to {peer_id} closed: {error:?}");
+                self.report(peer_id, Behavior::Malformed).await;
+            }
             other => {
                 tracing::debug!("Unhandled {:?}", other);
             }
@@ -155,7 +299,22 @@ impl P2PServer {
 
     fn handle_new_connection(&mut self, peer_id: PeerId, endpoint: ConnectedPoint, num_established: NonZeroU32) {
         tracing::debug!("connection established: {peer_id}");
+        if self.state.bans.is_banned(&peer_id, timestamp()) {
+            tracing::debug!("disconnecting banned peer {peer_id}");
+            let _ = self.swarm.disconnect_peer_id(peer_id);
+            return;
+        }
+
         let addr = endpoint.get_remote_address();
+        let source = match &endpoint {
+            ConnectedPoint::Dialer {
+                address,
+                ..
+            } => self.discovery.connected(address),
+            ConnectedPoint::Listener {
+                ..
+            } => PeerSource::Inbound,
+        };
 
         if !self.peers.contains_key(&peer_id) {
             self.peers.insert(
@@ -163,8 +322,11 @@ impl P2PServer {
                 Peer {
                     id: peer_id,
                     ip: addr.clone(),
+                    account: None,
                     last_ping: None,
                     voting_power: None,
+                    source,
+                    score: 0,
                 },
             );
         }
@@ -175,8 +337,27 @@ impl P2PServer {
         }
     }
 
-    fn handle_tick(&mut self) {
+    async fn handle_tick(&mut self) {
+        // peers can also be banned over the admin api
+        let now = timestamp();
+        let banned: Vec<PeerId> =
+            self.peers.iter().filter(|peer| self.state.bans.is_banned(&peer.id, now)).map(|peer| peer.id).collect();
+        for peer_id in banned {
+            self.disconnect(peer_id);
+        }
+
         metrics::update(self.peers.clone());
+        self.state.equivocations.prune(now);
+        if now.saturating_sub(self.last_saved) >= SAVE_INTERVAL {
+            let peers: Vec<Peer> = self.peers.iter().map(|peer| peer.clone()).collect();
+            match peer_store::save(&self.state, &peers, now).await {
+                Ok(()) => self.last_saved = now,
+                Err(e) => tracing::error!("could not save peers: {e}"),
+            }
+        }
+
+        self.update_network_power().await;
+
         tracing::debug!("pinging all peers");
         let peers: Vec<PeerId> = self.peers.iter().map(|p| p.id).collect();
         for peer in peers {
@@ -186,21 +367,161 @@ impl P2PServer {
         }
     }
 
+    /// Elects the prime delegates once per epoch and calculates the network power from the ones that are online
+    ///
+    /// The voting power of every account is snapshotted first, so all votes during the epoch use it and the delegates
+    /// are elected from it, and again if blocks from before the epoch were synced after the snapshot was taken. The
+    /// snapshot goes through every account, so it runs in its own task and the network power of the new epoch is
+    /// calculated on the first tick after it is published.
+    async fn update_network_power(&mut self) {
+        let epoch = self.state.config.read().await.chain_spec.epoch(timestamp());
+        let outdated = self.state.prime_delegates.epoch() != Some(epoch)
+            || self.state.epoch_power.epoch() != Some(epoch)
+            || self.state.epoch_power.is_outdated();
+        if outdated && !self.starting_epoch.swap(true, Ordering::SeqCst) {
+            let (state, starting_epoch) = (self.state.clone(), self.starting_epoch.clone());
+            tokio::spawn(async move {
+                let started = async {
+                    let snapshot = epoch_power::snapshot(&state, epoch).await?;
+                    prime_delegates::elect(&state, &snapshot).await
+                };
+                if let Err(e) = started.await {
+                    tracing::error!("could not start epoch {epoch}: {e}");
+                }
+                starting_epoch.store(false, Ordering::SeqCst);
+            });
+        }
+
+        self.update_online_power().await;
+    }
+
+    /// Calculates the network power from the prime delegates that are online
+    ///
+    /// Delegates go on- and offline during an epoch, so this runs on every tick and whenever a peer proves its account.
+    pub async fn update_online_power(&self) {
+        let (epoch, power_epoch) = match (self.state.prime_delegates.epoch(), self.state.epoch_power.epoch()) {
+            (Some(epoch), Some(power_epoch)) => (epoch, power_epoch),
+            _ => return,
+        };
+        if epoch != power_epoch {
+            return;
+        }
+
+        let now = timestamp();
+        let mode = self.state.config.read().await.consensus.mode;
+        let own_account = self.node_wallet.account_address_bytes;
+        let delegates: Vec<(u64, bool)> = self
+            .state
+            .prime_delegates
+            .get()
+            .into_iter()
+            .map(|(account, _)| {
+                // this node only votes for its account if it runs as a prime delegate
+                let online = if account == own_account {
+                    mode == Mode::Prime
+                } else {
+                    // peers only have an account once they proved they control it
+                    self.peers.iter().any(|peer| {
+                        peer.account == Some(account)
+                            && peer.last_ping.map_or(false, |ping| ping + ONLINE_TIMEOUT > now)
+                    })
+                };
+                // quorum is measured with the same power the votes are counted with
+                (self.state.epoch_power.power(&account).unwrap_or_default(), online)
+            })
+            .collect();
+
+        let snapshot = NetworkPowerSnapshot::new(epoch, &delegates);
+        if self.state.network_power.get() != Some(snapshot) {
+            tracing::debug!("network power of epoch {epoch}: {snapshot:?}");
+            self.state.network_power.set(snapshot);
+        }
+    }
+
+    fn handle_command(&mut self, command: Command) {
+        match command {
+            Command::Broadcast {
+                request,
+            } => {
+                if let Err(e) = self.standard_send(request) {
+                    tracing::error!("broadcast failed: {e}");
+                }
+            }
+            Command::Request {
+                request,
+                resp,
+            } => {
+                let peer = match self.request_peer() {
+                    Some(peer) => peer,
+                    None => {
+                        let _ = resp.send(Err(anyhow!("not connected to any peers")));
+                        return;
+                    }
+                };
+
+                match self.send_request(&peer, request) {
+                    Ok(request_id) => {
+                        self.pending_requests.insert(request_id, resp);
+                    }
+                    Err(e) => {
+                        let _ = resp.send(Err(e));
+                    }
+                }
+            }
+        }
+    }
+
+    /// A random prime delegate, or any random peer if no prime delegate is connected
+    fn request_peer(&mut self) -> Option<PeerId> {
+        let mut r = crypto::rand::thread_rng();
+        let prime_delegates = self.get_prime_delegates();
+        match prime_delegates.into_iter().choose(&mut r) {
+            Some(peer) => Some(peer),
+            None => self.get_random_peer_ids(1).pop(),
+        }
+    }
+
     pub async fn start(&mut self) -> Result<()> {
-        self.swarm.listen_on("/ip4/0.0.0.0/tcp/50052".parse()?)?;
         self.connect_to_initial_peers().await;
         metrics::update(self.peers.clone());
 
         let mut interval = tokio::time::interval(Duration::from_secs(5));
 
         loop {
+            #[cfg(not(test))]
             tokio::select! {
                     event = self.swarm.select_next_some() => self.handle_event(event).await,
-                    _ = interval.tick() => self.handle_tick(),
+                    Some(command) = self.rx.recv() => self.handle_command(command),
+                    _ = interval.tick() => self.handle_tick().await,
+            }
+
+            // the test harness delays or partitions links by holding messages back
+            #[cfg(test)]
+            tokio::select! {
+                    event = self.swarm.select_next_some() => self.handle_event(event).await,
+                    Some(command) = self.rx.recv() => self.handle_command(command),
+                    _ = interval.tick() => self.handle_tick().await,
+                    Some((peer, message)) = self.held.next(), if !self.held.is_empty() => {
+                        self.process_message(peer, message).await
+                    }
             }
         }
     }
 
+    async fn handle_message(&mut self, peer: PeerId, message: PogMessage) {
+        #[cfg(test)]
+        if !self.conditions.is_open(&peer) {
+            let conditions = self.conditions.clone();
+            self.held.push(Box::pin(async move {
+                conditions.wait(peer).await;
+                (peer, message)
+            }));
+            return;
+        }
+
+        self.process_message(peer, message).await
+    }
+
     async fn process_message(&mut self, peer: PeerId, message: PogMessage) {
         let res = match message {
             protocol::RequestMessage {
@@ -211,7 +532,7 @@ impl P2PServer {
             protocol::ResponseMessage {
                 request_id,
                 response,
-            } => self.process_response(request_id, response, peer),
+            } => self.process_response(request_id, response, peer).await,
         };
 
         if let Err(err) = res {
@@ -228,47 +549,49 @@ impl P2PServer {
     ) -> Result<()> {
         tracing::debug!("processing request from {peer_id}");
 
-        let header = match RequestHeader::decode(&*request.header) {
-            Ok(header) => header,
-            Err(err) => {
-                self.send_response(channel, ResponseBodyData::Failure(Failure::MalformedRequest.into()))?;
-                return Err(err.into());
-            }
-        };
-
-        if let Err(e) = verify_signature(&request.data, &*header.public_key, &*header.signature) {
-            self.send_response(channel, ResponseBodyData::Failure(Failure::MalformedRequest.into()))?;
-            return Err(e.into());
-        }
-
-        let body = match RequestBody::decode(&*request.data) {
-            Ok(body) => body,
-            Err(err) => {
-                self.send_response(channel, ResponseBodyData::Failure(Failure::MalformedRequest.into()))?;
-                return Err(err.into());
-            }
-        };
-
-        let data = match body.data {
-            Some(d) => d,
-            None => {
+        let (header, data) = match self.verify_request(&request, peer_id).await {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                if let Some(behavior) = e.behavior() {
+                    self.report(peer_id, behavior).await;
+                }
                 self.send_response(channel, ResponseBodyData::Failure(Failure::MalformedRequest.into()))?;
-                return Err(anyhow!("data was none"));
+                return Err(e.into());
             }
         };
 
         tracing::trace!("got a request: {data:?}");
 
         let result = match data {
-            request_body::Data::FinalVote(data) => methods::process_final_vote(self, data, peer_id),
-            request_body::Data::VoteProposal(data) => methods::process_vote_proposal(self, data, peer_id).await,
-            request_body::Data::Forward(data) => methods::process_forward(self, *data, peer_id),
-            request_body::Data::Ping(data) => return methods::process_ping(self, data, channel, peer_id),
+            request_body::Data::FinalVote(data) => {
+                let signed_vote = SignedVote::from_request(&request.data, &header);
+                methods::process_final_vote(self, data, &header, signed_vote, peer_id).await
+            }
+            request_body::Data::VoteProposal(data) => {
+                let signed_vote = SignedVote::from_request(&request.data, &header);
+                methods::process_vote_proposal(self, data, &header, signed_vote, peer_id).await
+            }
+            request_body::Data::Forward(data) => methods::process_forward(self, *data, peer_id).await,
+            request_body::Data::Ping(data) => {
+                return methods::process_ping(self, data, &header, channel, peer_id).await
+            }
+            request_body::Data::GetLatestBlock(data) => {
+                return methods::process_get_latest_block(self, data, channel).await
+            }
+            request_body::Data::GetBlocks(data) => return methods::process_get_blocks(self, data, channel).await,
+            request_body::Data::GetChangedAccounts(data) => {
+                return methods::process_get_changed_accounts(self, data, channel).await
+            }
         };
 
         match result {
             Err(e) => {
                 tracing::error!("error while processing request: {e}");
+                let behavior =
+                    e.downcast_ref::<MessageError>().map_or(Some(Behavior::InvalidBlock), MessageError::behavior);
+                if let Some(behavior) = behavior {
+                    self.report(peer_id, behavior).await;
+                }
                 self.send_response(channel, ResponseBodyData::Failure(Failure::MalformedRequest.into()))
             }
             Ok(()) => {
@@ -277,37 +600,175 @@ impl P2PServer {
         }
     }
 
-    fn process_response(&self, request_id: RequestId, response: PogResponse, peer_id: PeerId) -> Result<()> {
+    async fn process_response(&mut self, request_id: RequestId, response: PogResponse, peer_id: PeerId) -> Result<()> {
         tracing::debug!("processing response from {peer_id}");
-        Ok(())
+        let data = Self::decode_response(&response);
+        match &data {
+            Err(MessageError::InvalidSignature) => self.report(peer_id, Behavior::InvalidSignature).await,
+            Err(_) => self.report(peer_id, Behavior::Malformed).await,
+            Ok(ResponseBodyData::Failure(_)) => {}
+            Ok(_) if self.pending_requests.contains_key(&request_id) => {
+                self.report(peer_id, Behavior::UsefulResponse).await
+            }
+            Ok(_) => {}
+        }
+
+        // responses to requests of other parts of the node are passed on, even if they are invalid
+        if let Some(resp) = self.pending_requests.remove(&request_id) {
+            let result = match data {
+                Ok(ResponseBodyData::Failure(failure)) => Err(anyhow!("request to {peer_id} failed: {failure:?}")),
+                result => result.map_err(|e| e.into()),
+            };
+            let _ = resp.send(result);
+            return Ok(());
+        }
+
+        match data? {
+            ResponseBodyData::Pong(pong) => methods::process_pong(self, pong, peer_id).await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Decodes a request a peer sent and checks that it was signed by the peer and not received before
+    async fn verify_request(
+        &mut self,
+        request: &PogRequest,
+        peer_id: PeerId,
+    ) -> Result<(RequestHeader, RequestBodyData), MessageError> {
+        let now = timestamp();
+        let window = self.state.config.read().await.consensus.request_window;
+        let (header, data) = decode_request(&request.data, &request.header, now, window)?;
+
+        let public_key = ed25519::PublicKey::decode(&header.public_key).map_err(|_| MessageError::UnknownSigner)?;
+        if PeerId::from(identity::PublicKey::Ed25519(public_key)) != peer_id {
+            return Err(MessageError::UnknownSigner);
+        }
+
+        if !self.replays.insert(&header.public_key, &header.signature, now, window) {
+            return Err(MessageError::Replayed);
+        }
+        Ok((header, data))
+    }
+
+    fn decode_response(response: &PogResponse) -> Result<ResponseBodyData, MessageError> {
+        let header = ResponseHeader::decode(&*response.header).map_err(|e| MessageError::Malformed(e.to_string()))?;
+        verify_signature(&response.data, &*header.public_key, &*header.signature)
+            .map_err(|_| MessageError::InvalidSignature)?;
+        let body = ResponseBody::decode(&*response.data).map_err(|e| MessageError::Malformed(e.to_string()))?;
+        body.data.ok_or_else(|| MessageError::Malformed("data was none".to_string()))
+    }
+
+    /// Updates the score of a peer and bans it for `consensus.ban_duration` seconds once the score drops to
+    /// `reputation::BAN_THRESHOLD`
+    pub async fn report(&mut self, peer_id: PeerId, behavior: Behavior) {
+        let score = match self.peers.get_mut(&peer_id) {
+            Some(mut peer) => {
+                peer.score = reputation::update_score(peer.score, behavior);
+                peer.score
+            }
+            None => return,
+        };
+        tracing::debug!("{peer_id}: {behavior:?}, score is now {score}");
+        if score > reputation::BAN_THRESHOLD {
+            return;
+        }
+
+        let ban_duration = self.state.config.read().await.consensus.ban_duration;
+        tracing::warn!("banning {peer_id} for {ban_duration} seconds");
+        if let Err(e) = reputation::ban(&self.state, peer_id, timestamp() + ban_duration).await {
+            tracing::error!("could not ban {peer_id}: {e}");
+        }
+        self.disconnect(peer_id);
+    }
+
+    fn disconnect(&mut self, peer_id: PeerId) {
+        self.peers.remove(&peer_id);
+        let _ = self.swarm.disconnect_peer_id(peer_id);
+    }
+
+    /// Dials peers learned from another peer, until this node is connected to `consensus.target_peers` peers
+    pub async fn discover_peers(&mut self, source: PeerId, addresses: &[Vec<u8>]) {
+        let target = self.state.config.read().await.consensus.target_peers;
+        let known: HashSet<Multiaddr> =
+            self.peers.iter().map(|peer| peer.ip.clone()).chain(self.swarm.listeners().cloned()).collect();
+
+        let candidates = self.discovery.candidates(source, addresses, &known, self.peers.len(), target, timestamp());
+        for address in candidates {
+            tracing::debug!("dialing {address}, learned from {source}");
+            if let Err(e) = self.swarm.dial(address.clone()) {
+                tracing::debug!("could not dial {address}: {e}");
+                self.discovery.failed(address);
+            }
+        }
     }
 
+    /// Connected peers that proved they control the account of an elected prime delegate
     fn get_prime_delegates(&self) -> Vec<PeerId> {
-        todo!("write a fn that gets all online prime delegates")
+        self.peers
+            .iter()
+            .filter(|peer| peer.account.map_or(false, |account| self.state.prime_delegates.contains(&account)))
+            .map(|peer| peer.id)
+            .collect()
     }
 }
 
+/// Decodes a signed request, verifies its signature and that it was signed within `window` seconds of `now`
+pub fn decode_request(
+    data: &[u8],
+    header: &[u8],
+    now: u64,
+    window: u64,
+) -> Result<(RequestHeader, RequestBodyData), MessageError> {
+    let header = RequestHeader::decode(header).map_err(|e| MessageError::Malformed(e.to_string()))?;
+    verify_signature(data, &*header.public_key, &*header.signature).map_err(|_| MessageError::InvalidSignature)?;
+    let body = RequestBody::decode(data).map_err(|e| MessageError::Malformed(e.to_string()))?;
+    if !replay::is_fresh(body.timestamp, now, window) {
+        return Err(MessageError::Expired);
+    }
+    Ok((header, body.data.ok_or_else(|| MessageError::Malformed("data was none".to_string()))?))
+}
+
 pub trait RequestResponse {
     fn standard_send(&mut self, request: RequestBodyData) -> Result<()>;
+    fn forward(&mut self, forward: request_body::Forward, from: Option<PeerId>) -> Result<()>;
+    fn sign_request(&mut self, request: RequestBodyData) -> Result<PogRequest>;
     fn send_request(&mut self, peer: &PeerId, request: RequestBodyData) -> Result<RequestId>;
     fn send_response(&mut self, channel: ResponseChannel<PogResponse>, response: ResponseBodyData) -> Result<()>;
 }
 
 impl RequestResponse for P2PServer {
-    // standard send means that a request is sent to all Prime Delegates + 10 random non Prime Delegates
+    // standard send means that a request is gossiped: it is sent to all Prime Delegates + 10 random non Prime
+    // Delegates, which forward it the same way until its TTL runs out
     fn standard_send(&mut self, request: RequestBodyData) -> Result<()> {
-        let prime_delegates = self.get_prime_delegates();
-        let random_peers = self.get_random_peer_ids(NR_OF_PEERS_SENT);
-        let all_peers = prime_delegates.iter().chain(random_peers.iter()).collect::<Vec<&PeerId>>();
+        let request = self.sign_request(request)?;
+        self.seen_messages.insert(&request.data);
+
+        let forward = request_body::Forward {
+            header: request.header,
+            data: request.data,
+            ttl: gossip::TTL,
+        };
+        self.forward(forward, None)
+    }
+
+    // forwards a request to all Prime Delegates + 10 random non Prime Delegates, except the peer it came from
+    fn forward(&mut self, forward: request_body::Forward, from: Option<PeerId>) -> Result<()> {
+        let mut peers: HashSet<PeerId> = self.get_prime_delegates().into_iter().collect();
+        peers.extend(self.get_random_peer_ids(NR_OF_PEERS_SENT));
 
-        for peers in all_peers {
-            let _ = self.send_request(peers, request.to_owned());
+        for peer in peers.iter().filter(|peer| Some(**peer) != from) {
+            let _ = self.send_request(peer, RequestBodyData::Forward(Box::new(forward.clone())));
         }
 
         Ok(())
     }
 
     fn send_request(&mut self, peer: &PeerId, request: RequestBodyData) -> Result<RequestId> {
+        let request = self.sign_request(request)?;
+        Ok(self.swarm.behaviour_mut().send_request(peer, request))
+    }
+
+    fn sign_request(&mut self, request: RequestBodyData) -> Result<PogRequest> {
         let request_body = RequestBody {
             data: Some(request),
             signature_type: 0,
@@ -320,12 +781,10 @@ impl RequestResponse for P2PServer {
             public_key: self.node_wallet.public_key()?.to_vec(),
         };
 
-        let request = PogRequest {
+        Ok(PogRequest {
             data: request_body,
             header: header.encode_to_vec(),
-        };
-
-        Ok(self.swarm.behaviour_mut().send_request(peer, request))
+        })
     }
 
     fn send_response(&mut self, channel: ResponseChannel<PogResponse>, response: ResponseBodyData) -> Result<()> {
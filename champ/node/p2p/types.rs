@@ -6,7 +6,9 @@ use libp2p::noise::AuthenticKeypair;
 use libp2p::request_response::RequestResponseEvent;
 use libp2p::swarm::{ConnectionHandlerUpgrErr, SwarmEvent};
 use libp2p::PeerId;
+use pog_proto::api::AccountID;
 pub use pog_proto::p2p::{request_body, response_body, Failure};
+use tokio::sync::oneshot;
 
 pub use pog_proto::p2p::{
     request_body::Data as RequestBodyData, response_body::Data as ResponseBodyData, RequestBody, RequestHeader,
@@ -14,13 +16,32 @@ pub use pog_proto::p2p::{
 };
 
 use self::protocol::{PogRequest, PogResponse};
+use super::discovery::PeerSource;
 
 #[derive(Clone)]
 pub struct Peer {
     pub id: PeerId,
     pub ip: libp2p::Multiaddr,
+    /// account that signed the last ping of the peer
+    pub account: Option<AccountID>,
     pub voting_power: Option<u64>,
     pub last_ping: Option<u64>,
+    pub source: PeerSource,
+    /// goes down when the peer misbehaves, see `reputation::Behavior`
+    pub score: i32,
+}
+
+/// Commands sent to the p2p server by other parts of the node
+#[derive(Debug)]
+pub enum Command {
+    Broadcast {
+        request: RequestBodyData,
+    },
+    /// sends a request to a single peer, preferably a prime delegate, and waits for its response
+    Request {
+        request: RequestBodyData,
+        resp: oneshot::Sender<anyhow::Result<ResponseBodyData>>,
+    },
 }
 
 pub type Event = SwarmEvent<RequestResponseEvent<PogRequest, PogResponse>, ConnectionHandlerUpgrErr<std::io::Error>>;
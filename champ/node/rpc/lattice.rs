@@ -1,9 +1,11 @@
 use std::convert::TryInto;
 
+use crate::blockpool::{get_status, set_status, BlockStatus};
 use crate::consensus::voting_power::{get_active_power, get_actual_power};
 use crate::state::ChampStateArc;
 use crate::storage;
-use crate::validation::block::validate;
+use crate::validation::block::{validate, BlockValidationError};
+use crate::validation::spam::{check_spam_index, get_spam_index};
 
 use pog_proto::api::{self, SignedBlock};
 use pog_proto::rpc::lattice::*;
@@ -195,7 +197,60 @@ impl Lattice for LatticeService {
         &self,
         _request: tonic::Request<Empty>,
     ) -> Result<tonic::Response<PendingBlockReply>, tonic::Status> {
-        unimplemented!()
+        debug!("getting pending blocks");
+
+        let blocks = self
+            .state
+            .blockpool_client
+            .get_pending_blocks()
+            .await
+            .map_err(|_| Status::new(tonic::Code::Internal, "could not get pending blocks"))?;
+
+        Ok(Response::new(PendingBlockReply {
+            blocks: blocks.into_iter().map(|b| b.into()).collect(),
+        }))
+    }
+
+    async fn get_block_status(
+        &self,
+        request: tonic::Request<BlockStatusRequest>,
+    ) -> Result<tonic::Response<BlockStatusReply>, tonic::Status> {
+        debug!("getting block status");
+
+        let block_id: api::BlockID = request
+            .into_inner()
+            .block_id
+            .try_into()
+            .map_err(|_| Status::new(tonic::Code::Internal, "couldn't parse block id"))?;
+
+        let status = get_status(&self.state, block_id)
+            .await
+            .map_err(|_e| Status::new(tonic::Code::Internal, "internal server error"))?
+            .ok_or_else(|| Status::new(tonic::Code::Internal, "block not found"))?;
+
+        let mut reply = BlockStatusReply::default();
+        match status {
+            BlockStatus::Received => reply.state = BlockState::Received as i32,
+            BlockStatus::Validating => reply.state = BlockState::Validating as i32,
+            BlockStatus::Orphaned => reply.state = BlockState::Orphaned as i32,
+            BlockStatus::Voting {
+                quorum,
+                final_quorum,
+            } => {
+                reply.state = BlockState::Voting as i32;
+                reply.quorum = quorum;
+                reply.final_quorum = final_quorum;
+            }
+            BlockStatus::Confirmed => reply.state = BlockState::Confirmed as i32,
+            BlockStatus::Rejected {
+                reason,
+            } => {
+                reply.state = BlockState::Rejected as i32;
+                reply.reason = reason;
+            }
+        }
+
+        Ok(Response::new(reply))
     }
 
     async fn get_unacknowledged_tx(
@@ -240,29 +295,67 @@ impl Lattice for LatticeService {
         &self,
         request: tonic::Request<pog_proto::api::RawBlock>,
     ) -> Result<tonic::Response<Empty>, tonic::Status> {
-        let block = request.into_inner();
-        let block: SignedBlock =
-            block.try_into().map_err(|_e| Status::new(tonic::Code::Internal, "invalid block: encoding"))?;
+        let raw_block = request.into_inner();
+        let block: SignedBlock = raw_block
+            .clone()
+            .try_into()
+            .map_err(|_e| Status::new(tonic::Code::Internal, "invalid block: encoding"))?;
+
+        match check_spam_index(&block, &self.state).await {
+            Ok(_) => (),
+            Err(BlockValidationError::Invalid(_)) => {
+                return Err(Status::new(tonic::Code::Internal, "invalid block: spam index too high"))
+            }
+            Err(_) => return Err(Status::new(tonic::Code::Internal, "internal server error")),
+        }
 
         let internal_config = { self.state.config.read().await.internal.clone() };
         if internal_config.debug_skip_consensus {
-            let mut db = self.state.db.lock().await;
-
             if !internal_config.debug_skip_block_validation && validate(&block, &self.state).await.is_err() {
                 return Err(Status::new(tonic::Code::Internal, "invalid block: validation"));
             }
 
-            let db_response = db.add_block(block).await;
+            let mut db = self.state.db.lock().await;
+            let db_response = db.add_block(block.clone()).await;
             let _ = db_response.map_err(|_e| Status::new(tonic::Code::Internal, "internal server error"))?;
+            self.state.voting_power_cache.invalidate(&block);
+            return Ok(Response::new(Empty {}));
         }
 
+        // the blockpool might still be busy with other blocks
+        set_status(&self.state, block.get_id(), BlockStatus::Received)
+            .await
+            .map_err(|_e| Status::new(tonic::Code::Internal, "internal server error"))?;
+
+        self.state.blockpool_client.submit_block(raw_block).await.map_err(|e| {
+            match e.downcast_ref::<BlockValidationError>() {
+                Some(BlockValidationError::Invalid(_)) => {
+                    Status::new(tonic::Code::Internal, "invalid block: validation")
+                }
+                _ => Status::new(tonic::Code::Internal, "internal server error"),
+            }
+        })?;
+
         Ok(Response::new(Empty {}))
     }
 
     async fn get_block_spam_index(
         &self,
-        _request: tonic::Request<pog_proto::api::RawBlock>,
+        request: tonic::Request<pog_proto::api::RawBlock>,
     ) -> Result<tonic::Response<BlockSpamIndexReply>, tonic::Status> {
-        unimplemented!()
+        debug!("getting block spam index");
+
+        let block: SignedBlock = request
+            .into_inner()
+            .try_into()
+            .map_err(|_e| Status::new(tonic::Code::Internal, "invalid block: encoding"))?;
+
+        let index = get_spam_index(&block, &self.state)
+            .await
+            .map_err(|_e| Status::new(tonic::Code::Internal, "internal server error"))?;
+
+        Ok(Response::new(BlockSpamIndexReply {
+            spam_index: index.value(),
+        }))
     }
 }
@@ -1,6 +1,12 @@
 use crate::auth::permissions::verify_perms;
+use crate::consensus::{equivocation, farming};
+use crate::p2p::{reputation, server::timestamp};
 use crate::state::ChampStateArc;
-use pog_proto::{api::Empty, rpc::node_admin::*};
+use libp2p::PeerId;
+use pog_proto::{
+    api::{AccountID, Empty},
+    rpc::node_admin::*,
+};
 use tonic::{Response, Status};
 
 pub use pog_proto::rpc::node_admin::node_admin_server::{NodeAdmin, NodeAdminServer};
@@ -51,9 +57,21 @@ impl NodeAdmin for NodeAdminService {
 
     async fn get_pending_blocks(
         &self,
-        _request: tonic::Request<Empty>,
+        request: tonic::Request<Empty>,
     ) -> Result<tonic::Response<GetPendingBlocksReply>, tonic::Status> {
-        unimplemented!()
+        debug!("getting pending blocks");
+
+        verify_perms(&request, "admin.read")?;
+        let blocks = self
+            .state
+            .blockpool_client
+            .get_pending_blocks()
+            .await
+            .map_err(|_| Status::new(tonic::Code::Internal, "could not get pending blocks"))?;
+
+        Ok(Response::new(GetPendingBlocksReply {
+            blocks: blocks.into_iter().map(|b| b.into()).collect(),
+        }))
     }
 
     async fn get_block_pool_size(
@@ -143,10 +161,201 @@ impl NodeAdmin for NodeAdminService {
         verify_perms(&request, "admin.read")?;
         let config = self.state.config.read().await;
         Ok(Response::new(GetChainReply {
-            current_chain: config.consensus.chain.clone(),
+            current_chain: config.chain_spec.chain_id.clone(),
+        }))
+    }
+
+    async fn get_network_power(
+        &self,
+        request: tonic::Request<Empty>,
+    ) -> Result<tonic::Response<GetNetworkPowerReply>, tonic::Status> {
+        debug!("getting network power");
+
+        verify_perms(&request, "admin.read")?;
+        let snapshot = self
+            .state
+            .network_power
+            .get()
+            .ok_or_else(|| Status::new(tonic::Code::Internal, "network power not calculated yet"))?;
+
+        Ok(Response::new(GetNetworkPowerReply {
+            epoch: snapshot.epoch,
+            total_power: snapshot.total_power,
+            online_power: snapshot.online_power,
         }))
     }
 
+    async fn get_farming_scores(
+        &self,
+        request: tonic::Request<GetFarmingScoresRequest>,
+    ) -> Result<tonic::Response<GetFarmingScoresReply>, tonic::Status> {
+        debug!("getting farming scores");
+
+        verify_perms(&request, "admin.read")?;
+        let account_id = request.into_inner().account_id;
+        let accounts: Vec<AccountID> = match account_id.is_empty() {
+            true => self
+                .state
+                .db
+                .lock()
+                .await
+                .get_accounts()
+                .await
+                .map_err(|_| Status::new(tonic::Code::Internal, "could not get accounts"))?,
+            false => vec![account_id
+                .try_into()
+                .map_err(|_| Status::new(tonic::Code::Internal, "couldn't parse address"))?],
+        };
+
+        let params = self.state.config.read().await.chain_spec.consensus.farming.clone();
+        let mut scores = Vec::with_capacity(accounts.len());
+        for account in accounts {
+            let score = farming::score(&self.state, account)
+                .await
+                .map_err(|_| Status::new(tonic::Code::Internal, "could not calculate the farming score"))?;
+
+            scores.push(AccountFarmingScore {
+                account_id: account.to_vec(),
+                uniform_transactions: score.uniform_transactions.to_f64(),
+                young_delegation: score.young_delegation.to_f64(),
+                single_funding_source: score.single_funding_source.to_f64(),
+                unchanged_delegate: score.unchanged_delegate.to_f64(),
+                total: score.total().to_f64(),
+                flagged: score.is_flagged(&params),
+            });
+        }
+
+        Ok(Response::new(GetFarmingScoresReply {
+            scores,
+        }))
+    }
+
+    async fn get_equivocation_evidence(
+        &self,
+        request: tonic::Request<Empty>,
+    ) -> Result<tonic::Response<GetEquivocationEvidenceReply>, tonic::Status> {
+        debug!("getting equivocation evidence");
+
+        verify_perms(&request, "admin.read")?;
+        let evidence = equivocation::get_evidence(&self.state)
+            .await
+            .map_err(|_| Status::new(tonic::Code::Internal, "could not get evidence"))?;
+
+        let signed_request = |vote: equivocation::SignedVote| SignedRequest {
+            data: vote.data,
+            public_key: vote.public_key,
+            signature: vote.signature,
+        };
+
+        Ok(Response::new(GetEquivocationEvidenceReply {
+            evidence: evidence
+                .into_iter()
+                .map(|evidence| EquivocationEvidence {
+                    voter: evidence.voter.to_vec(),
+                    account_id: evidence.account.to_vec(),
+                    height: evidence.height,
+                    final_vote: evidence.kind == equivocation::VoteKind::Final,
+                    excluded_until: self.state.equivocations.excluded_until(&evidence.voter).unwrap_or_default(),
+                    detected_at: evidence.detected_at,
+                    first: Some(signed_request(evidence.first)),
+                    second: Some(signed_request(evidence.second)),
+                })
+                .collect(),
+        }))
+    }
+
+    async fn get_power_snapshot(
+        &self,
+        request: tonic::Request<GetPowerSnapshotRequest>,
+    ) -> Result<tonic::Response<GetPowerSnapshotReply>, tonic::Status> {
+        debug!("getting power snapshot");
+
+        verify_perms(&request, "admin.read")?;
+        let epoch = request.into_inner().epoch;
+        let powers = self
+            .state
+            .db
+            .lock()
+            .await
+            .get_power_snapshot(epoch)
+            .await
+            .map_err(|_| Status::new(tonic::Code::Internal, "could not get power snapshot"))?
+            .ok_or_else(|| Status::new(tonic::Code::Internal, "no power snapshot for this epoch"))?;
+
+        Ok(Response::new(GetPowerSnapshotReply {
+            epoch,
+            total_power: powers.iter().fold(0u64, |sum, (_, power)| sum.saturating_add(*power)),
+            accounts: powers
+                .into_iter()
+                .map(|(account_id, power)| AccountPower {
+                    account_id: account_id.to_vec(),
+                    power,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn get_banned_peers(
+        &self,
+        request: tonic::Request<Empty>,
+    ) -> Result<tonic::Response<GetBannedPeersReply>, tonic::Status> {
+        debug!("getting banned peers");
+
+        verify_perms(&request, "admin.read")?;
+        Ok(Response::new(GetBannedPeersReply {
+            peers: self
+                .state
+                .bans
+                .banned(timestamp())
+                .into_iter()
+                .map(|(peer_id, until)| BannedPeer {
+                    peer_id: peer_id.to_string(),
+                    until,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn ban_peer(
+        &self,
+        request: tonic::Request<BanPeerRequest>,
+    ) -> Result<tonic::Response<Empty>, tonic::Status> {
+        debug!("banning peer");
+
+        verify_perms(&request, "admin.write")?;
+        let request = request.into_inner();
+        let peer_id: PeerId =
+            request.peer_id.parse().map_err(|_| Status::new(tonic::Code::Internal, "couldn't parse peer id"))?;
+        let duration = match request.duration {
+            0 => self.state.config.read().await.consensus.ban_duration,
+            duration => duration,
+        };
+
+        reputation::ban(&self.state, peer_id, timestamp().saturating_add(duration))
+            .await
+            .map_err(|_| Status::new(tonic::Code::Internal, "could not ban peer"))?;
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn unban_peer(
+        &self,
+        request: tonic::Request<UnbanPeerRequest>,
+    ) -> Result<tonic::Response<Empty>, tonic::Status> {
+        debug!("unbanning peer");
+
+        verify_perms(&request, "admin.write")?;
+        let peer_id: PeerId = request
+            .into_inner()
+            .peer_id
+            .parse()
+            .map_err(|_| Status::new(tonic::Code::Internal, "couldn't parse peer id"))?;
+
+        reputation::unban(&self.state, peer_id)
+            .await
+            .map_err(|_| Status::new(tonic::Code::Internal, "could not unban peer"))?;
+        Ok(Response::new(Empty {}))
+    }
+
     async fn get_logs(
         &self,
         request: tonic::Request<GetLogsRequest>,
@@ -1,6 +1,15 @@
+use crate::consensus::{
+    cache::VotingPowerCache, epoch_power::EpochPower, equivocation::Equivocations, network_power::NetworkPower,
+    prime_delegates::PrimeDelegates,
+};
 use crate::storage::Database;
 use crate::wallets::WalletManager;
-use crate::{blockpool::BlockpoolClient, config::Config};
+use crate::{
+    blockpool::{BlockStatuses, BlockpoolClient},
+    config::Config,
+    p2p::{client::P2PClient, reputation::Bans},
+    sync::SyncClient,
+};
 use std::sync::Arc;
 use tokio::sync::{Mutex, RwLock};
 
@@ -10,6 +19,15 @@ pub struct ChampState {
     pub config: RwLock<Config>,
     pub wallet_manager: RwLock<WalletManager>,
     pub blockpool_client: BlockpoolClient,
+    pub p2p_client: P2PClient,
+    pub sync_client: SyncClient,
+    pub voting_power_cache: VotingPowerCache,
+    pub network_power: NetworkPower,
+    pub epoch_power: EpochPower,
+    pub prime_delegates: PrimeDelegates,
+    pub equivocations: Equivocations,
+    pub block_statuses: BlockStatuses,
+    pub bans: Bans,
 }
 
 pub struct ChampStateArgs {
@@ -17,6 +35,8 @@ pub struct ChampStateArgs {
     pub config: RwLock<Config>,
     pub wallet_manager: RwLock<WalletManager>,
     pub blockpool_client: BlockpoolClient,
+    pub p2p_client: P2PClient,
+    pub sync_client: SyncClient,
 }
 
 impl ChampState {
@@ -26,15 +46,35 @@ impl ChampState {
             config: args.config,
             wallet_manager: args.wallet_manager,
             blockpool_client: args.blockpool_client,
+            p2p_client: args.p2p_client,
+            sync_client: args.sync_client,
+            voting_power_cache: VotingPowerCache::default(),
+            network_power: NetworkPower::default(),
+            epoch_power: EpochPower::default(),
+            prime_delegates: PrimeDelegates::default(),
+            equivocations: Equivocations::default(),
+            block_statuses: BlockStatuses::default(),
+            bans: Bans::default(),
         })
     }
 
     #[cfg(test)]
     pub async fn mock() -> ChampStateArc {
+        let (state, mut p2p_rx) = Self::mock_with_p2p().await;
+
+        // requests to the network are dropped
+        tokio::spawn(async move { while p2p_rx.recv().await.is_some() {} });
+        state
+    }
+
+    /// Mocks a state and returns the requests it sends to the network
+    #[cfg(test)]
+    pub async fn mock_with_p2p() -> (ChampStateArc, tokio::sync::mpsc::Receiver<crate::p2p::types::Command>) {
         use crate::{blockpool::Blockpool, storage};
 
         let mut pool = Blockpool::new();
         let blockpool_client = pool.get_client();
+        let (p2p_tx, p2p_rx) = tokio::sync::mpsc::channel(1000);
 
         let db = Mutex::new(
             storage::new(&storage::DatabaseConfig {
@@ -51,11 +91,21 @@ impl ChampState {
             config: RwLock::new(Config::default()),
             wallet_manager: RwLock::new(WalletManager::mock()),
             blockpool_client,
+            p2p_client: P2PClient::new(p2p_tx),
+            // accounts are not synced
+            sync_client: SyncClient::new(tokio::sync::mpsc::channel(1).0),
+            voting_power_cache: VotingPowerCache::default(),
+            network_power: NetworkPower::default(),
+            epoch_power: EpochPower::default(),
+            prime_delegates: PrimeDelegates::default(),
+            equivocations: Equivocations::default(),
+            block_statuses: BlockStatuses::default(),
+            bans: Bans::default(),
         });
 
         pool.add_state(state.clone());
         tokio::spawn(async move { pool.start().await });
-        state
+        (state, p2p_rx)
     }
 }
 
@@ -171,9 +171,78 @@ pub trait Database: Send + Sync {
     // Adds a new block to the database
     async fn add_block(&mut self, block: api::SignedBlock) -> Result<(), DatabaseError>;
 
+    // Removes the blocks of an account starting at a height, so a competing block can take their place.
+    // Returns the removed blocks, oldest first
+    async fn remove_blocks_from(
+        &mut self,
+        account_id: api::AccountID,
+        height: u64,
+    ) -> Result<Vec<api::SignedBlock>, DatabaseError>;
+
     // Get the transaction id claiming a send transaction
     async fn get_send_recipient(
         &self,
         send_transaction_id: api::TransactionID,
     ) -> Result<Option<api::TransactionID>, DatabaseError>;
+
+    // Lists all accounts that have at least one block
+    async fn get_accounts(&self) -> Result<Vec<api::AccountID>, DatabaseError>;
+
+    // Lists up to `limit` accounts whose latest block was signed after `since`, oldest change first, skipping `offset`
+    async fn get_changed_accounts(
+        &self,
+        since: u64,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<api::AccountID>, DatabaseError>;
+
+    // Gets the last elected prime delegates with their voting power and the epoch they were elected for
+    async fn get_prime_delegates(&self) -> Result<Option<(u64, Vec<(api::AccountID, u64)>)>, DatabaseError>;
+
+    // Stores evidence that a voter signed votes for competing blocks, replacing evidence with the same key
+    async fn add_evidence(&mut self, key: Vec<u8>, evidence: Vec<u8>) -> Result<(), DatabaseError>;
+
+    // Lists all stored evidence
+    async fn get_evidence(&self) -> Result<Vec<Vec<u8>>, DatabaseError>;
+
+    // Stores whether a block was confirmed or rejected, replacing its previous status
+    async fn set_block_status(&mut self, block_id: api::BlockID, status: Vec<u8>) -> Result<(), DatabaseError>;
+
+    // Gets the stored status of a block
+    async fn get_block_status(&self, block_id: api::BlockID) -> Result<Option<Vec<u8>>, DatabaseError>;
+
+    // Stores the active power of every account at the start of an epoch
+    async fn set_power_snapshot(
+        &mut self,
+        epoch: u64,
+        powers: Vec<(api::AccountID, u64)>,
+    ) -> Result<(), DatabaseError>;
+
+    // Gets the active power of every account at the start of an epoch, if a snapshot was taken
+    async fn get_power_snapshot(&self, epoch: u64) -> Result<Option<Vec<(api::AccountID, u64)>>, DatabaseError>;
+
+    // Replaces the elected prime delegates
+    async fn set_prime_delegates(
+        &mut self,
+        epoch: u64,
+        delegates: Vec<(api::AccountID, u64)>,
+    ) -> Result<(), DatabaseError>;
+
+    // Bans a peer until a timestamp, replacing an earlier ban of the peer
+    async fn add_ban(&mut self, peer_id: Vec<u8>, until: u64) -> Result<(), DatabaseError>;
+
+    // Removes the ban of a peer
+    async fn remove_ban(&mut self, peer_id: Vec<u8>) -> Result<(), DatabaseError>;
+
+    // Lists all banned peers with the timestamp their ban ends at
+    async fn get_bans(&self) -> Result<Vec<(Vec<u8>, u64)>, DatabaseError>;
+
+    // Stores a known peer, replacing the stored peer with the same id
+    async fn set_peer(&mut self, peer_id: Vec<u8>, peer: Vec<u8>) -> Result<(), DatabaseError>;
+
+    // Removes a stored peer
+    async fn remove_peer(&mut self, peer_id: Vec<u8>) -> Result<(), DatabaseError>;
+
+    // Lists all stored peers with their ids
+    async fn get_peers(&self) -> Result<Vec<(Vec<u8>, Vec<u8>)>, DatabaseError>;
 }
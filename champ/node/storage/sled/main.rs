@@ -16,7 +16,56 @@ pub struct SledDB {
     accounts: sled::Tree,
     transactions: sled::Tree,
     claims: sled::Tree,
-    // meta: sled::Tree,
+    meta: sled::Tree,
+}
+
+const PRIME_DELEGATES_KEY: &[u8] = b"prime_delegates";
+const EVIDENCE_PREFIX: &[u8] = b"evidence_";
+const STATUS_PREFIX: &[u8] = b"status_";
+const POWER_SNAPSHOT_PREFIX: &[u8] = b"power_";
+const BAN_PREFIX: &[u8] = b"ban_";
+const PEER_PREFIX: &[u8] = b"peer_";
+const CHANGED_PREFIX: &[u8] = b"changed_";
+
+// "changed_" + timestamp + account_id, so accounts are ordered by the time of their latest block
+fn changed_key(timestamp: u64, account_id: &[u8]) -> Vec<u8> {
+    let mut key = CHANGED_PREFIX.to_vec();
+    key.extend_from_slice(&timestamp.to_be_bytes());
+    key.extend_from_slice(account_id);
+    key
+}
+
+fn changed_at_key(account_id: &[u8]) -> Vec<u8> {
+    let mut key = account_id.to_vec();
+    key.extend_from_slice(b"_changed_at");
+    key
+}
+
+// (account_id + power) for each account
+fn encode_powers(powers: &[(api::AccountID, u64)]) -> Vec<u8> {
+    let mut value = Vec::with_capacity(powers.len() * 32);
+    for (account_id, power) in powers {
+        value.extend_from_slice(account_id);
+        value.extend_from_slice(&power.to_be_bytes());
+    }
+    value
+}
+
+fn decode_powers(value: &[u8], error: &str) -> Result<Vec<(api::AccountID, u64)>, DatabaseError> {
+    let invalid = || DatabaseError::Specific(error.to_string());
+
+    let mut result = vec![];
+    for entry in value.chunks(32) {
+        if entry.len() != 32 {
+            return Err(invalid());
+        }
+        let (account_id, power) = entry.split_at(24);
+        result.push((
+            account_id.try_into().map_err(|_| invalid())?,
+            u64::from_be_bytes(power.try_into().map_err(|_| invalid())?),
+        ));
+    }
+    Ok(result)
 }
 
 fn encode_block(block: api::SignedBlock) -> Vec<u8> {
@@ -71,6 +120,12 @@ impl SledDB {
         //
         // key: account_id + "_rep"
         // val: representative account_id
+        //
+        // key: account_id + "_changed_at"
+        // val: timestamp of the latest block
+        //
+        // key: "changed_" + timestamp of the latest block + account_id
+        // val: empty, an index of accounts by the time they last changed
 
         // claims provides some convenient pointers to data relevant to claim transactions
         let claims = db.open_tree("claims")?;
@@ -100,7 +155,27 @@ impl SledDB {
         // key: "by_blk_id_" + block_id + "block_index"
         // val: transaction proto
 
-        // let meta = db.open_tree("meta")?;
+        // meta provides data that is not part of an account chain
+        let meta = db.open_tree("meta")?;
+        // meta contains:
+        //
+        // key: "prime_delegates"
+        // val: epoch + (account_id + voting power) for each delegate
+        //
+        // key: "evidence_" + evidence key
+        // val: json encoded evidence of conflicting votes
+        //
+        // key: "status_" + block_id
+        // val: json encoded status of a confirmed or rejected block
+        //
+        // key: "power_" + epoch
+        // val: (account_id + active power) for each account
+        //
+        // key: "ban_" + peer_id
+        // val: timestamp the ban ends at
+        //
+        // key: "peer_" + peer_id
+        // val: json encoded address, last-seen time, score and voting power of a known peer
 
         Ok(Self {
             // db,
@@ -109,7 +184,7 @@ impl SledDB {
             accounts,
             transactions,
             claims,
-            // meta,
+            meta,
         })
     }
 }
@@ -241,6 +316,16 @@ impl Database for SledDB {
                     account_key.append(&mut b"_last_blk".to_vec());
                     accounts.insert(account_key, &block_id.clone())?;
 
+                    // Move the account to the time of its latest block
+                    if let Some(changed_at) =
+                        accounts.insert(changed_at_key(&account_id), &block.header.timestamp.to_be_bytes())?
+                    {
+                        let changed_at =
+                            changed_at.as_ref().try_into().map_err(|_| ConflictableTransactionError::Abort(()))?;
+                        accounts.remove(changed_key(u64::from_be_bytes(changed_at), &account_id))?;
+                    }
+                    accounts.insert(changed_key(block.header.timestamp, &account_id), b"")?;
+
                     // Add Block
                     blocks.insert(block_key, encode_block(block.clone()))?;
                     blocks.insert(block_by_acc_key, block_id.to_vec())?;
@@ -296,6 +381,113 @@ impl Database for SledDB {
         res.map_err(|_| DatabaseError::DBInsertFailed)
     }
 
+    async fn remove_blocks_from(
+        &mut self,
+        account_id: api::AccountID,
+        height: u64,
+    ) -> Result<Vec<api::SignedBlock>, DatabaseError> {
+        let mut removed = vec![];
+        let mut next_height = height;
+        while let Some(block) = self.get_block_by_height(account_id, &next_height).await? {
+            removed.push(block);
+            next_height += 1;
+        }
+
+        let previous_block = match height {
+            0 => None,
+            _ => self.get_block_by_height(account_id, &(height - 1)).await?,
+        };
+
+        // the representative is the one of the latest delegation that is left
+        let mut representative = None;
+        for previous_height in (0..height).rev() {
+            let block = match self.get_block_by_height(account_id, &previous_height).await? {
+                Some(block) => block,
+                None => break,
+            };
+            representative = block.data.transactions.iter().rev().find_map(|tx| match &tx.data {
+                Some(api::transaction::Data::TxDelegate(tx)) => Some(tx.representative.clone()),
+                _ => None,
+            });
+            if representative.is_some() {
+                break;
+            }
+        }
+
+        let res: sled::transaction::TransactionResult<()> =
+            (&self.accounts, &self.blocks, &self.transactions, &self.claims).transaction(
+                |(accounts, blocks, transactions, claims)| {
+                    for block in &removed {
+                        let block_id = block.get_id();
+
+                        let mut block_key = b"by_id_".to_vec();
+                        block_key.append(&mut block_id.to_vec());
+                        blocks.remove(block_key)?;
+
+                        let mut block_by_acc_key = b"by_acc_".to_vec();
+                        block_by_acc_key.append(&mut account_id.to_vec());
+                        block_by_acc_key.append(&mut b"_".to_vec());
+                        block_by_acc_key.append(&mut block.data.height.to_be_bytes().to_vec());
+                        blocks.remove(block_by_acc_key)?;
+
+                        for (i, tx) in block.data.transactions.iter().enumerate() {
+                            let transaction_id = match api::Transaction::get_id(block_id, i as u32) {
+                                Ok(x) => x,
+                                Err(_) => return sled::transaction::abort(()),
+                            };
+
+                            // the send can be claimed again
+                            if let Some(api::transaction::Data::TxClaim(tx)) = &tx.data {
+                                claims.remove(tx.send_transaction_id.clone())?;
+                            }
+
+                            let mut tx_key = b"by_id_".to_vec();
+                            tx_key.append(&mut transaction_id.into());
+                            transactions.remove(tx_key)?;
+
+                            let mut tx_key = b"blk_by_id_".to_vec();
+                            tx_key.append(&mut transaction_id.into());
+                            transactions.remove(tx_key)?;
+
+                            let mut tx_key = b"by_blk_id_".to_vec();
+                            tx_key.append(&mut block_id.into());
+                            tx_key.append(&mut i.to_be_bytes().into());
+                            transactions.remove(tx_key)?;
+                        }
+                    }
+
+                    let mut account_key = account_id.to_vec();
+                    account_key.append(&mut b"_last_blk".to_vec());
+                    match &previous_block {
+                        Some(block) => accounts.insert(account_key, &block.get_id())?,
+                        None => accounts.remove(account_key)?,
+                    };
+
+                    if let Some(changed_at) = accounts.remove(changed_at_key(&account_id))? {
+                        let changed_at =
+                            changed_at.as_ref().try_into().map_err(|_| ConflictableTransactionError::Abort(()))?;
+                        accounts.remove(changed_key(u64::from_be_bytes(changed_at), &account_id))?;
+                    }
+                    if let Some(block) = &previous_block {
+                        accounts.insert(changed_at_key(&account_id), &block.header.timestamp.to_be_bytes())?;
+                        accounts.insert(changed_key(block.header.timestamp, &account_id), b"")?;
+                    }
+
+                    let mut account_rep_key = b"rep_".to_vec();
+                    account_rep_key.append(&mut account_id.to_vec());
+                    match &representative {
+                        Some(representative) => accounts.insert(account_rep_key, representative.clone())?,
+                        None => accounts.remove(account_rep_key)?,
+                    };
+
+                    Ok(())
+                },
+            );
+
+        res.map_err(|_| DatabaseError::DBInsertFailed)?;
+        Ok(removed)
+    }
+
     async fn get_block_by_height(
         &self,
         account_id: api::AccountID,
@@ -461,4 +653,166 @@ impl Database for SledDB {
             })
             .ok_or(DatabaseError::Unknown)
     }
+
+    async fn get_accounts(&self) -> Result<Vec<api::AccountID>, DatabaseError> {
+        let mut accounts = vec![];
+        for key in self.accounts.iter().keys() {
+            let key = key?;
+            if let Some(account_id) = key.strip_suffix(b"_last_blk") {
+                accounts.push(
+                    account_id.try_into().map_err(|_| DatabaseError::Specific("invalid account id".to_string()))?,
+                );
+            }
+        }
+        Ok(accounts)
+    }
+
+    async fn get_changed_accounts(
+        &self,
+        since: u64,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<api::AccountID>, DatabaseError> {
+        let start = match since.checked_add(1) {
+            Some(start) => changed_key(start, &[]),
+            None => return Ok(vec![]),
+        };
+
+        let keys = self.accounts.range(start..).keys();
+        let mut accounts = vec![];
+        for key in keys.take_while(|key| key.as_ref().map_or(true, |key| key.starts_with(CHANGED_PREFIX))).skip(offset)
+        {
+            if accounts.len() >= limit {
+                break;
+            }
+            let key = key?;
+            let account_id = key.get(CHANGED_PREFIX.len() + 8..).unwrap_or_default();
+            accounts
+                .push(account_id.try_into().map_err(|_| DatabaseError::Specific("invalid account id".to_string()))?);
+        }
+        Ok(accounts)
+    }
+
+    async fn add_evidence(&mut self, key: Vec<u8>, evidence: Vec<u8>) -> Result<(), DatabaseError> {
+        let mut evidence_key = EVIDENCE_PREFIX.to_vec();
+        evidence_key.extend_from_slice(&key);
+        self.meta.insert(evidence_key, evidence)?;
+        Ok(())
+    }
+
+    async fn get_evidence(&self) -> Result<Vec<Vec<u8>>, DatabaseError> {
+        let mut evidence = vec![];
+        for entry in self.meta.scan_prefix(EVIDENCE_PREFIX).values() {
+            evidence.push(entry?.to_vec());
+        }
+        Ok(evidence)
+    }
+
+    async fn set_block_status(&mut self, block_id: api::BlockID, status: Vec<u8>) -> Result<(), DatabaseError> {
+        let mut status_key = STATUS_PREFIX.to_vec();
+        status_key.extend_from_slice(&block_id);
+        self.meta.insert(status_key, status)?;
+        Ok(())
+    }
+
+    async fn get_block_status(&self, block_id: api::BlockID) -> Result<Option<Vec<u8>>, DatabaseError> {
+        let mut status_key = STATUS_PREFIX.to_vec();
+        status_key.extend_from_slice(&block_id);
+        Ok(self.meta.get(status_key)?.map(|status| status.to_vec()))
+    }
+
+    async fn get_prime_delegates(&self) -> Result<Option<(u64, Vec<(api::AccountID, u64)>)>, DatabaseError> {
+        let value = match self.meta.get(PRIME_DELEGATES_KEY)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        if value.len() < 8 {
+            return Err(DatabaseError::Specific("invalid prime delegates".to_string()));
+        }
+        let (epoch, delegates) = value.split_at(8);
+        let epoch = u64::from_be_bytes(epoch.try_into().expect("epoch has 8 bytes"));
+
+        Ok(Some((epoch, decode_powers(delegates, "invalid prime delegates")?)))
+    }
+
+    async fn set_power_snapshot(
+        &mut self,
+        epoch: u64,
+        powers: Vec<(api::AccountID, u64)>,
+    ) -> Result<(), DatabaseError> {
+        let mut key = POWER_SNAPSHOT_PREFIX.to_vec();
+        key.extend_from_slice(&epoch.to_be_bytes());
+        self.meta.insert(key, encode_powers(&powers))?;
+        Ok(())
+    }
+
+    async fn get_power_snapshot(&self, epoch: u64) -> Result<Option<Vec<(api::AccountID, u64)>>, DatabaseError> {
+        let mut key = POWER_SNAPSHOT_PREFIX.to_vec();
+        key.extend_from_slice(&epoch.to_be_bytes());
+        match self.meta.get(key)? {
+            Some(value) => Ok(Some(decode_powers(&value, "invalid power snapshot")?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn set_prime_delegates(
+        &mut self,
+        epoch: u64,
+        delegates: Vec<(api::AccountID, u64)>,
+    ) -> Result<(), DatabaseError> {
+        let mut value = epoch.to_be_bytes().to_vec();
+        value.extend(encode_powers(&delegates));
+
+        self.meta.insert(PRIME_DELEGATES_KEY, value)?;
+        Ok(())
+    }
+
+    async fn add_ban(&mut self, peer_id: Vec<u8>, until: u64) -> Result<(), DatabaseError> {
+        let mut key = BAN_PREFIX.to_vec();
+        key.extend_from_slice(&peer_id);
+        self.meta.insert(key, &until.to_be_bytes())?;
+        Ok(())
+    }
+
+    async fn remove_ban(&mut self, peer_id: Vec<u8>) -> Result<(), DatabaseError> {
+        let mut key = BAN_PREFIX.to_vec();
+        key.extend_from_slice(&peer_id);
+        self.meta.remove(key)?;
+        Ok(())
+    }
+
+    async fn get_bans(&self) -> Result<Vec<(Vec<u8>, u64)>, DatabaseError> {
+        let mut bans = vec![];
+        for entry in self.meta.scan_prefix(BAN_PREFIX) {
+            let (key, value) = entry?;
+            let until: [u8; 8] =
+                value.as_ref().try_into().map_err(|_| DatabaseError::Specific("invalid ban".to_string()))?;
+            bans.push((key[BAN_PREFIX.len()..].to_vec(), u64::from_be_bytes(until)));
+        }
+        Ok(bans)
+    }
+
+    async fn set_peer(&mut self, peer_id: Vec<u8>, peer: Vec<u8>) -> Result<(), DatabaseError> {
+        let mut key = PEER_PREFIX.to_vec();
+        key.extend_from_slice(&peer_id);
+        self.meta.insert(key, peer)?;
+        Ok(())
+    }
+
+    async fn remove_peer(&mut self, peer_id: Vec<u8>) -> Result<(), DatabaseError> {
+        let mut key = PEER_PREFIX.to_vec();
+        key.extend_from_slice(&peer_id);
+        self.meta.remove(key)?;
+        Ok(())
+    }
+
+    async fn get_peers(&self) -> Result<Vec<(Vec<u8>, Vec<u8>)>, DatabaseError> {
+        let mut peers = vec![];
+        for entry in self.meta.scan_prefix(PEER_PREFIX) {
+            let (key, value) = entry?;
+            peers.push((key[PEER_PREFIX.len()..].to_vec(), value.to_vec()));
+        }
+        Ok(peers)
+    }
 }
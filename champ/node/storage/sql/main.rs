@@ -22,6 +22,11 @@ use entity::transaction::{self, Entity as Transaction};
 use entity::tx_claim::{self, Entity as TxClaim};
 use prost::Message;
 
+// methods that were added after the sql schema and have no tables yet
+fn unsupported(method: &str) -> DatabaseError {
+    DatabaseError::Specific(format!("{method} is not supported by the sql backend yet"))
+}
+
 #[derive(Debug)]
 pub struct Sql {
     db: DatabaseConnection,
@@ -319,4 +324,97 @@ impl Database for Sql {
             None => Ok(None),
         }
     }
+
+    async fn remove_blocks_from(
+        &mut self,
+        _account_id: api::AccountID,
+        _height: u64,
+    ) -> Result<Vec<api::SignedBlock>, DatabaseError> {
+        Err(unsupported("remove_blocks_from"))
+    }
+
+    async fn get_accounts(&self) -> Result<Vec<api::AccountID>, DatabaseError> {
+        let accounts = Account::find().select_only().column(account::Column::AccountIdV1).all(&self.db).await?;
+
+        accounts
+            .into_iter()
+            .map(|a| {
+                api::AccountID::try_from(a.account_id_v1)
+                    .map_err(|_| DatabaseError::Specific("invalid account id".to_string()))
+            })
+            .collect()
+    }
+
+    async fn get_changed_accounts(
+        &self,
+        _since: u64,
+        _offset: usize,
+        _limit: usize,
+    ) -> Result<Vec<api::AccountID>, DatabaseError> {
+        Err(unsupported("get_changed_accounts"))
+    }
+
+    async fn add_evidence(&mut self, _key: Vec<u8>, _evidence: Vec<u8>) -> Result<(), DatabaseError> {
+        Err(unsupported("add_evidence"))
+    }
+
+    async fn get_evidence(&self) -> Result<Vec<Vec<u8>>, DatabaseError> {
+        Err(unsupported("get_evidence"))
+    }
+
+    async fn set_block_status(&mut self, _block_id: api::BlockID, _status: Vec<u8>) -> Result<(), DatabaseError> {
+        Err(unsupported("set_block_status"))
+    }
+
+    async fn get_block_status(&self, _block_id: api::BlockID) -> Result<Option<Vec<u8>>, DatabaseError> {
+        Err(unsupported("get_block_status"))
+    }
+
+    async fn set_power_snapshot(
+        &mut self,
+        _epoch: u64,
+        _powers: Vec<(api::AccountID, u64)>,
+    ) -> Result<(), DatabaseError> {
+        Err(unsupported("set_power_snapshot"))
+    }
+
+    async fn get_power_snapshot(&self, _epoch: u64) -> Result<Option<Vec<(api::AccountID, u64)>>, DatabaseError> {
+        Err(unsupported("get_power_snapshot"))
+    }
+
+    async fn get_prime_delegates(&self) -> Result<Option<(u64, Vec<(api::AccountID, u64)>)>, DatabaseError> {
+        Err(unsupported("get_prime_delegates"))
+    }
+
+    async fn set_prime_delegates(
+        &mut self,
+        _epoch: u64,
+        _delegates: Vec<(api::AccountID, u64)>,
+    ) -> Result<(), DatabaseError> {
+        Err(unsupported("set_prime_delegates"))
+    }
+
+    async fn add_ban(&mut self, _peer_id: Vec<u8>, _until: u64) -> Result<(), DatabaseError> {
+        Err(unsupported("add_ban"))
+    }
+
+    async fn remove_ban(&mut self, _peer_id: Vec<u8>) -> Result<(), DatabaseError> {
+        Err(unsupported("remove_ban"))
+    }
+
+    async fn get_bans(&self) -> Result<Vec<(Vec<u8>, u64)>, DatabaseError> {
+        Err(unsupported("get_bans"))
+    }
+
+    async fn set_peer(&mut self, _peer_id: Vec<u8>, _peer: Vec<u8>) -> Result<(), DatabaseError> {
+        Err(unsupported("set_peer"))
+    }
+
+    async fn remove_peer(&mut self, _peer_id: Vec<u8>) -> Result<(), DatabaseError> {
+        Err(unsupported("remove_peer"))
+    }
+
+    async fn get_peers(&self) -> Result<Vec<(Vec<u8>, Vec<u8>)>, DatabaseError> {
+        Err(unsupported("get_peers"))
+    }
 }
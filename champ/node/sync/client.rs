@@ -0,0 +1,26 @@
+use pog_proto::api::AccountID;
+use tokio::sync::mpsc;
+
+/// Asks the sync service to fetch missing blocks
+#[derive(Debug, Clone)]
+pub struct SyncClient {
+    tx: mpsc::Sender<AccountID>,
+}
+
+impl SyncClient {
+    pub fn new(tx: mpsc::Sender<AccountID>) -> Self {
+        Self {
+            tx,
+        }
+    }
+
+    /// Syncs the chain of an account in the background
+    ///
+    /// Requests are dropped while the sync service is busy, since the account is synced again with all other
+    /// changed accounts later on.
+    pub fn sync_account(&self, account_id: AccountID) {
+        if let Err(e) = self.tx.try_send(account_id) {
+            tracing::debug!("could not request a sync of account {account_id:?}: {e}");
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod client;
+pub mod service;
+
+pub use client::SyncClient;
+pub use service::{SyncService, MAX_ACCOUNTS, MAX_BLOCKS};
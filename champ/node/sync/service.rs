@@ -0,0 +1,192 @@
+use std::convert::TryInto;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use encoding::account::generate_account_address;
+use pog_proto::api::{AccountID, SignedBlock};
+use pog_proto::p2p::request_body;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use super::client::SyncClient;
+use crate::p2p::server::timestamp;
+use crate::p2p::types::{RequestBodyData, ResponseBodyData};
+use crate::state::ChampStateArc;
+
+/// blocks a peer sends in a single response
+pub const MAX_BLOCKS: u64 = 100;
+/// accounts a peer sends in a single response
+pub const MAX_ACCOUNTS: usize = 1000;
+
+// seconds between syncs of the accounts that changed since the last sync
+const SYNC_INTERVAL: u64 = 300;
+// seconds between attempts while the node has not synced yet, e.g. because it has no peers
+const RETRY_INTERVAL: u64 = 10;
+// blocks can be confirmed a while after they were signed, so accounts are synced again for this many seconds
+const CHECKPOINT_MARGIN: u64 = 3600;
+
+/// Brings the database up to date with the chains of other nodes
+///
+/// The accounts that changed since the last sync are synced at startup and then every few minutes.
+/// Single accounts are synced as soon as the blockpool notices that a block is missing.
+pub struct SyncService {
+    state: Option<ChampStateArc>,
+    tx: mpsc::Sender<AccountID>,
+    rx: mpsc::Receiver<AccountID>,
+    // blocks signed before this timestamp are already synced
+    checkpoint: Option<u64>,
+    last_sync: Option<u64>,
+}
+
+impl Default for SyncService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyncService {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel(1000);
+        Self {
+            state: None,
+            tx,
+            rx,
+            checkpoint: None,
+            last_sync: None,
+        }
+    }
+
+    pub fn add_state(&mut self, state: ChampStateArc) {
+        self.state = Some(state);
+    }
+
+    pub fn get_client(&self) -> SyncClient {
+        SyncClient::new(self.tx.clone())
+    }
+
+    pub async fn start(&mut self) -> Result<()> {
+        let state = self.state.clone().ok_or_else(|| anyhow!("add_state has to be called first"))?;
+        info!("sync service started");
+
+        let mut interval = tokio::time::interval(Duration::from_secs(RETRY_INTERVAL));
+        loop {
+            tokio::select! {
+                Some(account_id) = self.rx.recv() => {
+                    if let Err(e) = sync_account(&state, account_id).await {
+                        warn!("could not sync account {account_id:?}: {e}");
+                    }
+                }
+                _ = interval.tick() => {
+                    let now = timestamp();
+                    if self.last_sync.map_or(false, |last_sync| last_sync + SYNC_INTERVAL > now) {
+                        continue;
+                    }
+
+                    match self.sync(&state, now).await {
+                        Ok(()) => self.last_sync = Some(now),
+                        Err(e) => warn!("could not sync: {e}"),
+                    }
+                }
+            }
+        }
+    }
+
+    async fn sync(&mut self, state: &ChampStateArc, now: u64) -> Result<()> {
+        let since = match self.checkpoint {
+            Some(checkpoint) => checkpoint,
+            // after a restart, everything after the newest block this node has is synced
+            None => match state.db.lock().await.get_blocks(true, 1, 0, None).await?.first() {
+                Some(block) => block.header.timestamp.saturating_sub(CHECKPOINT_MARGIN),
+                None => 0,
+            },
+        };
+
+        sync_changed_accounts(state, since).await?;
+        self.checkpoint = Some(now.saturating_sub(CHECKPOINT_MARGIN));
+        Ok(())
+    }
+}
+
+/// Syncs every account with a block signed after `since`
+pub async fn sync_changed_accounts(state: &ChampStateArc, since: u64) -> Result<()> {
+    let mut offset = 0;
+    loop {
+        let request = RequestBodyData::GetChangedAccounts(request_body::GetChangedAccounts {
+            since,
+            offset,
+        });
+        let accounts = match state.p2p_client.request(request).await? {
+            ResponseBodyData::ChangedAccounts(changed) => changed.accounts,
+            other => return Err(anyhow!("unexpected response: {other:?}")),
+        };
+        debug!("syncing {} accounts that changed since {since}", accounts.len());
+
+        let count = accounts.len();
+        for account_id in accounts {
+            let account_id: AccountID = account_id.try_into().map_err(|_| anyhow!("invalid account id"))?;
+            if let Err(e) = sync_account(state, account_id).await {
+                warn!("could not sync account {account_id:?}: {e}");
+            }
+        }
+
+        if count < MAX_ACCOUNTS {
+            return Ok(());
+        }
+        offset += count as u32;
+    }
+}
+
+/// Fetches the blocks of an account this node is missing and adds them to its chain
+///
+/// The blocks come from a single peer and are validated, so a peer can't forge blocks, but it can send a block that
+/// never reached quorum, e.g. one of two blocks a wallet signed for the same height. Synced blocks therefore go
+/// through the blockpool: blocks at a height that is being voted on are left to the vote, and a synced block is
+/// rolled back if a competing block reaches final quorum. If the chain of the peer forks this node's chain, syncing
+/// stops at the fork.
+pub async fn sync_account(state: &ChampStateArc, account_id: AccountID) -> Result<()> {
+    let request = RequestBodyData::GetLatestBlock(request_body::GetLatestBlock {
+        account_id: account_id.to_vec(),
+    });
+    let latest = match state.p2p_client.request(request).await? {
+        ResponseBodyData::LatestBlock(latest) => latest,
+        other => return Err(anyhow!("unexpected response: {other:?}")),
+    };
+    if latest.block.is_none() {
+        return Ok(());
+    }
+
+    let mut next_height = match state.db.lock().await.get_latest_block_by_account(account_id).await {
+        Ok(block) => block.data.height + 1,
+        Err(_) => 0,
+    };
+
+    while next_height <= latest.height {
+        let request = RequestBodyData::GetBlocks(request_body::GetBlocks {
+            account_id: account_id.to_vec(),
+            from_height: next_height,
+            to_height: latest.height,
+        });
+        let blocks = match state.p2p_client.request(request).await? {
+            ResponseBodyData::Blocks(blocks) => blocks.blocks,
+            other => return Err(anyhow!("unexpected response: {other:?}")),
+        };
+        if blocks.is_empty() {
+            return Err(anyhow!("peer has no block at height {next_height}"));
+        }
+
+        for raw_block in blocks.into_iter().take(MAX_BLOCKS as usize) {
+            let block: SignedBlock = raw_block.try_into()?;
+            if generate_account_address(block.header.public_key.clone())? != account_id
+                || block.data.height != next_height
+            {
+                return Err(anyhow!("peer sent a block of another account or height"));
+            }
+
+            state.blockpool_client.add_synced_block(block).await?;
+            next_height += 1;
+        }
+    }
+
+    debug!("synced account {account_id:?} up to height {}", latest.height);
+    Ok(())
+}
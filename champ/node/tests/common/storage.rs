@@ -10,8 +10,8 @@ pub struct TestStorage {
 }
 
 pub struct TestAccount {
-    private_key: [u8; 32],
-    public_key: [u8; 32],
+    pub private_key: [u8; 32],
+    pub public_key: [u8; 32],
 }
 
 pub const GENESIS_ID: &[u8; 32] = b"00000000000000000000000000000000";
@@ -80,7 +80,10 @@ impl TestStorage {
     }
 
     pub fn mock_simple_signed_block() -> SignedBlock {
-        let account = TestStorage::mock_account();
+        TestStorage::mock_simple_signed_block_for(&TestStorage::mock_account())
+    }
+
+    pub fn mock_simple_signed_block_for(account: &TestAccount) -> SignedBlock {
         let block_data = TestStorage::mock_blockdata(
             100u64,
             0,
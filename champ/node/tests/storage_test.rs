@@ -1,5 +1,7 @@
 mod common;
 use common::storage::TestStorage;
+use encoding::account::generate_account_address;
+use pog_proto::Message;
 
 #[tokio::test]
 async fn test_mock() {
@@ -21,3 +23,166 @@ async fn test_add_block() {
 // async fn test_get_send_recipient() {
 //     let mut db = TestStorage::new().await.db;
 // }
+
+#[tokio::test]
+async fn test_get_accounts() {
+    let db = TestStorage::new_mock().await.db;
+    let accounts = db.get_accounts().await.expect("should return accounts");
+    assert_eq!(10, accounts.len());
+}
+
+#[tokio::test]
+async fn test_get_changed_accounts() {
+    let mut db = TestStorage::new().await.db;
+    let (account, other) = (TestStorage::mock_account(), TestStorage::mock_account());
+    let account_id = generate_account_address(account.public_key.to_vec()).expect("should generate account id");
+    let other_id = generate_account_address(other.public_key.to_vec()).expect("should generate account id");
+
+    let genesis = TestStorage::mock_simple_signed_block_for(&account);
+    let data = TestStorage::mock_blockdata(100, 1, &genesis.get_id(), vec![]);
+    let latest = TestStorage::mock_sign_data(&data.encode_to_vec(), 3, &account.public_key, &account.private_key);
+    for block in [genesis.clone(), TestStorage::mock_simple_signed_block_for(&other), latest] {
+        db.add_block(block).await.expect("should add block");
+    }
+
+    // accounts are ordered by the time of their latest block
+    let since = genesis.header.timestamp;
+    assert_eq!(vec![other_id, account_id], db.get_changed_accounts(0, 0, 10).await.expect("should return accounts"));
+    assert_eq!(vec![other_id], db.get_changed_accounts(0, 0, 1).await.expect("should return accounts"));
+    assert_eq!(vec![account_id], db.get_changed_accounts(0, 1, 10).await.expect("should return accounts"));
+    assert_eq!(vec![account_id], db.get_changed_accounts(since, 0, 10).await.expect("should return accounts"));
+
+    // removed blocks no longer count as changes
+    db.remove_blocks_from(account_id, 1).await.expect("should remove blocks");
+    assert!(db.get_changed_accounts(since, 0, 10).await.expect("should return accounts").is_empty());
+    assert_eq!(2, db.get_changed_accounts(0, 0, 10).await.expect("should return accounts").len());
+}
+
+#[tokio::test]
+async fn test_prime_delegates() {
+    let mut db = TestStorage::new().await.db;
+    assert_eq!(None, db.get_prime_delegates().await.expect("should return prime delegates"));
+
+    let delegates = vec![([1; 24], 300), ([2; 24], 200)];
+    db.set_prime_delegates(7, delegates.clone()).await.expect("should set prime delegates");
+    assert_eq!(Some((7, delegates)), db.get_prime_delegates().await.expect("should return prime delegates"));
+
+    db.set_prime_delegates(8, vec![]).await.expect("should set prime delegates");
+    assert_eq!(Some((8, vec![])), db.get_prime_delegates().await.expect("should return prime delegates"));
+}
+
+#[tokio::test]
+async fn test_remove_blocks_from() {
+    let mut db = TestStorage::new().await.db;
+    let account = TestStorage::mock_account();
+    let account_id = generate_account_address(account.public_key.to_vec()).expect("should generate account id");
+
+    let mut chain = vec![TestStorage::mock_simple_signed_block_for(&account)];
+    for height in 1..3 {
+        let data = TestStorage::mock_blockdata(100, height, &chain.last().unwrap().get_id(), vec![]);
+        chain.push(TestStorage::mock_sign_data(
+            &data.encode_to_vec(),
+            height,
+            &account.public_key,
+            &account.private_key,
+        ));
+    }
+    for block in &chain {
+        db.add_block(block.clone()).await.expect("should add block");
+    }
+
+    let removed = db.remove_blocks_from(account_id, 1).await.expect("should remove blocks");
+    assert_eq!(chain[1..].to_vec(), removed);
+    assert_eq!(chain[0], db.get_latest_block_by_account(account_id).await.expect("should return latest block"));
+    assert_eq!(None, db.get_block_by_height(account_id, &1).await.expect("should look up block"));
+    db.get_block_by_id(chain[2].get_id()).await.expect_err("block should be removed");
+
+    // a competing block can take the place of the removed ones
+    let data = TestStorage::mock_blockdata(50, 1, &chain[0].get_id(), vec![]);
+    let fork = TestStorage::mock_sign_data(&data.encode_to_vec(), 5, &account.public_key, &account.private_key);
+    db.add_block(fork.clone()).await.expect("should add block");
+    assert_eq!(fork, db.get_latest_block_by_account(account_id).await.expect("should return latest block"));
+
+    assert!(db.remove_blocks_from(account_id, 5).await.expect("should remove nothing").is_empty());
+}
+
+#[tokio::test]
+async fn test_evidence() {
+    let mut db = TestStorage::new().await.db;
+    assert!(db.get_evidence().await.expect("should return evidence").is_empty());
+
+    db.add_evidence(b"a".to_vec(), b"first".to_vec()).await.expect("should add evidence");
+    db.add_evidence(b"b".to_vec(), b"second".to_vec()).await.expect("should add evidence");
+    db.add_evidence(b"a".to_vec(), b"replaced".to_vec()).await.expect("should add evidence");
+    db.set_prime_delegates(1, vec![]).await.expect("should set prime delegates");
+
+    assert_eq!(
+        vec![b"replaced".to_vec(), b"second".to_vec()],
+        db.get_evidence().await.expect("should return evidence")
+    );
+}
+
+#[tokio::test]
+async fn test_block_status() {
+    let mut db = TestStorage::new().await.db;
+    assert_eq!(None, db.get_block_status([1; 32]).await.expect("should return status"));
+
+    db.set_block_status([1; 32], b"rejected".to_vec()).await.expect("should set status");
+    db.set_block_status([1; 32], b"confirmed".to_vec()).await.expect("should set status");
+    db.add_evidence([1; 32].to_vec(), b"evidence".to_vec()).await.expect("should add evidence");
+
+    assert_eq!(Some(b"confirmed".to_vec()), db.get_block_status([1; 32]).await.expect("should return status"));
+    assert_eq!(None, db.get_block_status([2; 32]).await.expect("should return status"));
+    assert_eq!(vec![b"evidence".to_vec()], db.get_evidence().await.expect("should return evidence"));
+}
+
+#[tokio::test]
+async fn test_power_snapshots() {
+    let mut db = TestStorage::new().await.db;
+    assert_eq!(None, db.get_power_snapshot(3).await.expect("should return snapshot"));
+
+    let powers = vec![([1; 24], 100), ([2; 24], 0)];
+    db.set_power_snapshot(3, powers.clone()).await.expect("should set snapshot");
+    db.set_power_snapshot(4, vec![]).await.expect("should set snapshot");
+    db.set_prime_delegates(4, vec![([1; 24], 100)]).await.expect("should set prime delegates");
+
+    assert_eq!(Some(powers), db.get_power_snapshot(3).await.expect("should return snapshot"));
+    assert_eq!(Some(vec![]), db.get_power_snapshot(4).await.expect("should return snapshot"));
+    assert_eq!(None, db.get_power_snapshot(5).await.expect("should return snapshot"));
+}
+
+#[tokio::test]
+async fn test_bans() {
+    let mut db = TestStorage::new().await.db;
+    assert!(db.get_bans().await.expect("should return bans").is_empty());
+
+    db.add_ban(b"first".to_vec(), 100).await.expect("should add ban");
+    db.add_ban(b"second".to_vec(), 200).await.expect("should add ban");
+    db.add_ban(b"first".to_vec(), 300).await.expect("should add ban");
+    db.add_evidence(b"first".to_vec(), b"evidence".to_vec()).await.expect("should add evidence");
+    assert_eq!(
+        vec![(b"first".to_vec(), 300), (b"second".to_vec(), 200)],
+        db.get_bans().await.expect("should return bans")
+    );
+
+    db.remove_ban(b"first".to_vec()).await.expect("should remove ban");
+    assert_eq!(vec![(b"second".to_vec(), 200)], db.get_bans().await.expect("should return bans"));
+}
+
+#[tokio::test]
+async fn test_peers() {
+    let mut db = TestStorage::new().await.db;
+    assert!(db.get_peers().await.expect("should return peers").is_empty());
+
+    db.set_peer(b"first".to_vec(), b"a".to_vec()).await.expect("should set peer");
+    db.set_peer(b"second".to_vec(), b"b".to_vec()).await.expect("should set peer");
+    db.set_peer(b"first".to_vec(), b"replaced".to_vec()).await.expect("should set peer");
+    db.add_ban(b"first".to_vec(), 100).await.expect("should add ban");
+    assert_eq!(
+        vec![(b"first".to_vec(), b"replaced".to_vec()), (b"second".to_vec(), b"b".to_vec())],
+        db.get_peers().await.expect("should return peers")
+    );
+
+    db.remove_peer(b"first".to_vec()).await.expect("should remove peer");
+    assert_eq!(vec![(b"second".to_vec(), b"b".to_vec())], db.get_peers().await.expect("should return peers"));
+}
@@ -1,7 +1,12 @@
+use std::collections::HashSet;
+
 use crate::storage;
 use crate::{state::ChampStateArc, storage::DatabaseError};
 
-use crypto::{self, signatures::ed25519::verify_signature};
+use crypto::{
+    self,
+    signatures::ed25519::{verify_signature, verify_signatures},
+};
 use encoding::account::{generate_account_address, validate_account_address};
 
 use pog_proto::api::{
@@ -31,10 +36,16 @@ pub enum Validation {
     DuplicatedTx,
     #[error("too many transactions")]
     TooManyTransactions,
+    #[error("block too large")]
+    BlockTooLarge,
     #[error("send receiver cannot be block account")]
     ReceiverAccountError,
     #[error("block already exists")]
     BlockDuplicate,
+    #[error("spam index too high")]
+    SpamIndexExceeded,
+    #[error("block belongs to a different account")]
+    AccountMismatch,
 }
 
 #[derive(Error, Debug)]
@@ -68,6 +79,23 @@ pub enum BlockValidationError {
     Error(#[from] Node),
 }
 
+#[derive(Error, Debug)]
+#[error("block {index} of the segment is invalid: {error}")]
+pub struct SegmentValidationError {
+    /// index of the first invalid block in the segment
+    pub index: usize,
+    pub error: BlockValidationError,
+}
+
+impl SegmentValidationError {
+    fn new(index: usize, error: impl Into<BlockValidationError>) -> Self {
+        Self {
+            index,
+            error: error.into(),
+        }
+    }
+}
+
 // Validate block
 #[allow(dead_code)]
 #[tracing::instrument]
@@ -77,25 +105,33 @@ pub async fn validate(block: &SignedBlock, state: &ChampStateArc) -> Result<(),
     let public_key = &block.header.public_key;
     let signature = &block.header.signature;
 
-    let db = &state.db.lock().await;
     let account_id = generate_account_address(public_key.to_vec()).map_err(|_| Node::CryptoError)?;
 
-    let response = db.get_latest_block_by_account(account_id).await;
+    // the lock has to be released before verifying the transactions, which access the database again
+    let latest_block = {
+        let db = state.db.lock().await;
 
-    let latest_block = match response {
-        Ok(block) => block,
-        Err(storage::DatabaseError::NoLastBlock) => return verify_account_genesis_block(block),
-        _ => return Err(Node::BlockNotFound.into()),
-    };
+        match db.get_block_by_id(block.get_id()).await {
+            Ok(_) => return Err(Validation::BlockDuplicate.into()),
+            Err(storage::DatabaseError::BlockNotFound) => (),
+            Err(e) => return Err(Node::DBError(e).into()),
+        }
 
-    match db.get_block_by_id(block.get_id()).await {
-        Ok(_) => return Err(Validation::BlockDuplicate.into()),
-        Err(storage::DatabaseError::BlockNotFound) => (),
-        Err(e) => return Err(Node::DBError(e).into()),
-    }
+        match db.get_latest_block_by_account(account_id).await {
+            Ok(block) => Some(block),
+            Err(DatabaseError::NoLastBlock) | Err(DatabaseError::BlockNotFound) => None,
+            Err(e) => return Err(Node::DBError(e).into()),
+        }
+    };
 
     // signature
     verify_signature(&block.data.encode_to_vec(), public_key, signature).map_err(|_| Node::CryptoError)?;
+
+    let latest_block = match latest_block {
+        Some(latest_block) => latest_block,
+        None => return verify_account_genesis_block(block, state).await,
+    };
+
     // height / previous block
     verify_previous_block(block, &latest_block)?;
     // transactions / balance
@@ -106,23 +142,182 @@ pub async fn validate(block: &SignedBlock, state: &ChampStateArc) -> Result<(),
     Ok(())
 }
 
+/// Validates a block that competes with a block the database already has at the same height
+///
+/// The block has to continue the block before it, like the block it competes with. Sends that were claimed by
+/// the competing block are still claimed, so a fork can not claim them again.
+#[tracing::instrument]
+pub async fn validate_fork(block: &SignedBlock, state: &ChampStateArc) -> Result<(), BlockValidationError> {
+    debug!("validating a fork");
+
+    let account_id = generate_account_address(block.header.public_key.to_vec()).map_err(|_| Node::CryptoError)?;
+    let height = block.data.height;
+
+    let prev_block = {
+        let db = state.db.lock().await;
+
+        match db.get_block_by_id(block.get_id()).await {
+            Ok(_) => return Err(Validation::BlockDuplicate.into()),
+            Err(storage::DatabaseError::BlockNotFound) => (),
+            Err(e) => return Err(Node::DBError(e).into()),
+        }
+
+        // only blocks at a height that is already taken are forks
+        if db.get_block_by_height(account_id, &height).await.map_err(Node::DBError)?.is_none() {
+            return Err(Validation::BlockHeightError.into());
+        }
+
+        match height {
+            0 => None,
+            _ => {
+                let prev_block = db.get_block_by_height(account_id, &(height - 1)).await.map_err(Node::DBError)?;
+                Some(prev_block.ok_or(Validation::PreviousBlockError)?)
+            }
+        }
+    };
+
+    verify_signature(&block.data.encode_to_vec(), &block.header.public_key, &block.header.signature)
+        .map_err(|_| Node::CryptoError)?;
+
+    let prev_block = match prev_block {
+        Some(prev_block) => prev_block,
+        None => return verify_account_genesis_block(block, state).await,
+    };
+
+    verify_previous_block(block, &prev_block)?;
+    verify_transactions(block, &prev_block, state).await
+}
+
+/// Validates a segment of consecutive blocks of a single account chain
+///
+/// The segment has to continue the latest block in the database (or start with the account's genesis block).
+/// Links between the blocks are checked in memory and all signatures are verified in a single batch.
+/// Claims are checked against the database and against earlier blocks of the segment.
+#[tracing::instrument(skip(blocks))]
+pub async fn validate_segment(blocks: &[SignedBlock], state: &ChampStateArc) -> Result<(), SegmentValidationError> {
+    debug!("validating a segment of {} blocks", blocks.len());
+
+    let first_block = match blocks.first() {
+        Some(block) => block,
+        None => return Ok(()),
+    };
+
+    let account_id = generate_account_address(first_block.header.public_key.to_vec())
+        .map_err(|_| SegmentValidationError::new(0, Node::CryptoError))?;
+
+    let latest_block = {
+        let db = state.db.lock().await;
+        match db.get_latest_block_by_account(account_id).await {
+            Ok(block) => Some(block),
+            Err(DatabaseError::NoLastBlock) | Err(DatabaseError::BlockNotFound) => None,
+            Err(e) => return Err(SegmentValidationError::new(0, Node::DBError(e))),
+        }
+    };
+
+    // signatures
+    let first_invalid_signature = {
+        let data: Vec<Vec<u8>> = blocks.iter().map(|block| block.data.encode_to_vec()).collect();
+        let items: Vec<(&[u8], &[u8], &[u8])> = blocks
+            .iter()
+            .zip(data.iter())
+            .map(|(block, data)| (&data[..], &block.header.public_key[..], &block.header.signature[..]))
+            .collect();
+
+        match verify_signatures(&items) {
+            Ok(_) => None,
+            // the batch only tells us that some signature is invalid, so we have to look for the first one
+            Err(_) => items
+                .iter()
+                .position(|(data, public_key, signature)| verify_signature(data, public_key, signature).is_err()),
+        }
+    };
+
+    let mut claimed_sends: HashSet<Vec<u8>> = HashSet::new();
+
+    for (index, block) in blocks.iter().enumerate() {
+        if first_invalid_signature == Some(index) {
+            return Err(SegmentValidationError::new(index, Node::CryptoError));
+        }
+
+        if index > 0 {
+            let block_account_id = generate_account_address(block.header.public_key.to_vec())
+                .map_err(|_| SegmentValidationError::new(index, Node::CryptoError))?;
+            if block_account_id != account_id {
+                return Err(SegmentValidationError::new(index, Validation::AccountMismatch));
+            }
+        }
+
+        let prev_block = match index {
+            0 => latest_block.as_ref(),
+            _ => blocks.get(index - 1),
+        };
+
+        let prev_block = match prev_block {
+            Some(prev_block) => prev_block,
+            None => {
+                verify_account_genesis_block(block, state).await.map_err(|e| SegmentValidationError::new(index, e))?;
+                continue;
+            }
+        };
+
+        // height / previous block
+        verify_previous_block(block, prev_block).map_err(|e| SegmentValidationError::new(index, e))?;
+
+        // claims of earlier blocks in this segment are not in the database yet
+        for transaction in &block.data.transactions {
+            if let Some(Data::TxClaim(claim)) = &transaction.data {
+                if !claimed_sends.insert(claim.send_transaction_id.clone()) {
+                    return Err(SegmentValidationError::new(index, Validation::DuplicatedTx));
+                }
+            }
+        }
+
+        // transactions / balance
+        verify_transactions(block, prev_block, state).await.map_err(|e| SegmentValidationError::new(index, e))?;
+    }
+
+    trace!("segment successfully validated");
+    Ok(())
+}
+
 // TODO: add error handling so validation error go to voting
 // Verifies the transactions and balances
 async fn verify_transactions(
     new_block: &SignedBlock,
     prev_block: &SignedBlock,
     state: &ChampStateArc,
+) -> Result<(), BlockValidationError> {
+    verify_balance(new_block, prev_block.data.balance, state).await
+}
+
+// Verifies the block against the limits of the chain spec
+async fn verify_limits(block: &SignedBlock, state: &ChampStateArc) -> Result<(), BlockValidationError> {
+    let limits = state.config.read().await.chain_spec.limits.clone();
+
+    if block.data.transactions.len() > limits.max_transactions {
+        return Err(Validation::TooManyTransactions.into());
+    }
+    if block.data.encoded_len() > limits.max_block_size {
+        return Err(Validation::BlockTooLarge.into());
+    }
+
+    Ok(())
+}
+
+// Verifies the transactions of a block starting from the balance of its previous block
+async fn verify_balance(
+    new_block: &SignedBlock,
+    prev_balance: u64,
+    state: &ChampStateArc,
 ) -> Result<(), BlockValidationError> {
     debug!("verify transactions");
     // go through all tx in the block and do math to see new balance
     // check against block balance
     let mut transaction_ids: Vec<[u8; 32]> = vec![];
 
-    if new_block.data.transactions.len() > 255 {
-        return Err(Validation::TooManyTransactions.into());
-    }
+    verify_limits(new_block, state).await?;
 
-    let mut new_balance: i128 = prev_block.data.balance as i128;
+    let mut new_balance: i128 = prev_balance as i128;
     let mut tokio_tasks: Vec<JoinHandle<Result<i128, BlockValidationError>>> = vec![];
 
     for (i, transaction) in new_block.data.transactions.iter().enumerate() {
@@ -177,7 +372,7 @@ async fn tx_verification(
 fn verify_previous_block(new_block: &SignedBlock, prev_block: &SignedBlock) -> Result<(), BlockValidationError> {
     debug!("verify previous block");
 
-    if new_block.data.height - 1 != prev_block.data.height {
+    if new_block.data.height != prev_block.data.height + 1 {
         return Err(Validation::BlockHeightError.into());
     }
     if new_block.data.previous != prev_block.get_id().to_vec() {
@@ -187,11 +382,19 @@ fn verify_previous_block(new_block: &SignedBlock, prev_block: &SignedBlock) -> R
     Ok(())
 }
 
-// Verifies the block height and previous block
-fn verify_account_genesis_block(block: &SignedBlock) -> Result<(), BlockValidationError> {
-    match block.data.height {
-        0 => Ok(()),
-        _ => Err(Validation::BlockHeightError.into()),
+// Verifies the first block of an account against the genesis accounts of the chain spec
+async fn verify_account_genesis_block(block: &SignedBlock, state: &ChampStateArc) -> Result<(), BlockValidationError> {
+    if block.data.height != 0 {
+        return Err(Validation::BlockHeightError.into());
+    }
+
+    let account_id = generate_account_address(block.header.public_key.to_vec()).map_err(|_| Node::AccountError)?;
+    let genesis_balance = state.config.read().await.chain_spec.genesis_balance(&account_id);
+
+    match genesis_balance {
+        Some(balance) => verify_balance(block, balance, state).await,
+        // chains without genesis accounts accept any starting balance
+        None => verify_limits(block, state).await,
     }
 }
 
@@ -247,16 +450,55 @@ async fn validate_collect(
 
 #[cfg(test)]
 mod tests {
-    use crate::validation::block::{verify_previous_block, verify_transactions};
+    use crate::chain::ChainSpec;
+    use crate::validation::block::{
+        validate, validate_fork, validate_segment, verify_previous_block, verify_transactions,
+    };
     use crate::ChampState;
     use anyhow::Result;
-    use encoding::zbase32::FromZbase;
+    use crypto::signatures::ed25519::{create_public_key, create_signature, generate_private_key};
+    use encoding::account::generate_account_address;
+    use encoding::zbase32::{FromZbase, ToZbase};
     use pog_proto::api::transaction::TxClaim;
     use pog_proto::api::BlockHeader;
     use pog_proto::api::{
         transaction::{Data, TxSend},
         BlockData, SignedBlock, Transaction,
     };
+    use prost::Message;
+
+    fn signed_block(private_key: &[u8], height: u64, previous: Vec<u8>) -> SignedBlock {
+        signed_block_with_balance(private_key, height, previous, 0)
+    }
+
+    fn signed_block_with_balance(private_key: &[u8], height: u64, previous: Vec<u8>, balance: u64) -> SignedBlock {
+        let data = BlockData {
+            version: 0,
+            signature_type: 0,
+            balance,
+            height,
+            previous,
+            transactions: vec![],
+        };
+
+        SignedBlock::new(
+            BlockHeader {
+                signature: create_signature(&data.encode_to_vec(), private_key).expect("should sign").to_vec(),
+                public_key: create_public_key(private_key).expect("should create public key").to_vec(),
+                timestamp: 1,
+            },
+            data,
+        )
+    }
+
+    fn signed_segment(private_key: &[u8], length: u64) -> Vec<SignedBlock> {
+        let mut blocks: Vec<SignedBlock> = vec![];
+        for height in 0..length {
+            let previous = blocks.last().map_or(b"genesis".to_vec(), |b| b.get_id().to_vec());
+            blocks.push(signed_block(private_key, height, previous));
+        }
+        blocks
+    }
 
     #[test]
     fn test_verify_previous_block() -> Result<()> {
@@ -296,6 +538,110 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_validate_segment() -> Result<()> {
+        let state = ChampState::mock().await;
+        let private_key = generate_private_key()?;
+
+        let blocks = signed_segment(&private_key, 5);
+        validate_segment(&blocks, &state).await.expect("segment should be valid");
+
+        let mut invalid_signature = blocks.clone();
+        invalid_signature[3].header.signature = vec![0; 64];
+        let err = validate_segment(&invalid_signature, &state).await.expect_err("signature should be invalid");
+        assert_eq!(3, err.index);
+
+        let mut invalid_link = blocks.clone();
+        invalid_link.remove(2);
+        let err = validate_segment(&invalid_link, &state).await.expect_err("link should be invalid");
+        assert_eq!(2, err.index);
+
+        let other_account = signed_block(&generate_private_key()?, 5, blocks[4].get_id().to_vec());
+        let mut mixed_accounts = blocks.clone();
+        mixed_accounts.push(other_account);
+        let err = validate_segment(&mixed_accounts, &state).await.expect_err("account should be invalid");
+        assert_eq!(5, err.index);
+
+        // continue a chain that is already in the database
+        state.db.lock().await.add_block(blocks[0].clone()).await.expect("block should be added");
+        validate_segment(&blocks[1..], &state).await.expect("segment should continue the database");
+        let err = validate_segment(&blocks[2..], &state).await.expect_err("segment should not skip a block");
+        assert_eq!(0, err.index);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_validate_fork() -> Result<()> {
+        let state = ChampState::mock().await;
+        let private_key = generate_private_key()?;
+        let blocks = signed_segment(&private_key, 3);
+        for block in &blocks[..2] {
+            state.db.lock().await.add_block(block.clone()).await.expect("block should be added");
+        }
+
+        // a different block at the height of the latest block
+        let data = BlockData {
+            version: 1,
+            ..blocks[1].data.clone()
+        };
+        let fork = SignedBlock::new(
+            BlockHeader {
+                signature: create_signature(&data.encode_to_vec(), &private_key).expect("should sign").to_vec(),
+                ..blocks[1].header.clone()
+            },
+            data,
+        );
+        validate(&fork, &state).await.expect_err("fork should not continue the chain");
+        validate_fork(&fork, &state).await.expect("fork should be valid");
+
+        validate_fork(&blocks[1], &state).await.expect_err("block is already in the chain");
+        validate_fork(&blocks[2], &state).await.expect_err("block is not a fork");
+        validate_fork(&signed_block(&private_key, 1, b"unknown".to_vec()), &state)
+            .await
+            .expect_err("fork should continue the previous block");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_validate_genesis_block() -> Result<()> {
+        let state = ChampState::mock().await;
+        let private_key = generate_private_key()?;
+
+        // the dev chain has no genesis accounts, so any starting balance is accepted
+        validate(&signed_block_with_balance(&private_key, 0, b"genesis".to_vec(), 100), &state)
+            .await
+            .expect("dev chain should accept any starting balance");
+
+        let account_id = generate_account_address(create_public_key(&private_key)?.to_vec())?;
+        state.config.write().await.chain_spec = ChainSpec::from_toml(&format!(
+            r#"
+            chain_id = "test"
+
+            [[genesis]]
+            address = "pog-{}"
+            balance = 100
+            "#,
+            account_id.encode_zbase()?
+        ))?;
+
+        validate(&signed_block_with_balance(&private_key, 0, b"genesis".to_vec(), 100), &state)
+            .await
+            .expect("genesis balance should be accepted");
+        validate(&signed_block_with_balance(&private_key, 0, b"genesis".to_vec(), 1000), &state)
+            .await
+            .expect_err("balance above the genesis balance should be rejected");
+        validate(&signed_block_with_balance(&generate_private_key()?, 0, b"genesis".to_vec(), 100), &state)
+            .await
+            .expect_err("accounts without a genesis balance should start at 0");
+        validate(&signed_block_with_balance(&private_key, 1, b"genesis".to_vec(), 100), &state)
+            .await
+            .expect_err("first block should have height 0");
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_verify_transactions() -> Result<()> {
         let prev_block = SignedBlock::new(
@@ -0,0 +1,171 @@
+use crate::consensus::graphs::{age_graph, balance_graph};
+use crate::consensus::parameters::GraphParameters;
+use crate::state::ChampStateArc;
+use crate::storage::DatabaseError;
+use crate::validation::block::{BlockValidationError, Node, Validation};
+
+use encoding::account::generate_account_address;
+use pog_proto::api::{BlockID, SignedBlock};
+use tracing::{debug, trace};
+
+// Blocks of an account that are looked at to determine its recent block rate
+const RATE_LOOKBACK_BLOCKS: u32 = 20;
+// Time window for the recent block rate (1 minute)
+const RATE_WINDOW: u64 = 60;
+// Blocks per window an account can send before the rate starts doubling the spam index
+const RATE_LIMIT: f64 = 10.0;
+// Every 4 leading zero bits in the block id halve the spam index
+const WORK_BITS_PER_HALVING: f64 = 4.0;
+
+/// The components a spam index is made of
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpamIndex {
+    /// leading zero bits of the block id
+    pub work: u32,
+    /// trust an account has gained through its age and balance
+    pub trust: f64,
+    /// blocks the account has sent within `RATE_WINDOW` before this block
+    pub recent_blocks: u32,
+}
+
+impl SpamIndex {
+    /// Combines all components into a single value.
+    ///
+    /// A block of a new, empty account without any proof of work and no recent blocks has an index of 1.0.
+    /// Sending more blocks increases the index, while account trust and work over the block id decrease it.
+    pub fn value(&self) -> f64 {
+        let rate = 1.0 + self.recent_blocks as f64 / RATE_LIMIT;
+        let account = 1.0 / (1.0 + self.trust);
+        let work = 0.5_f64.powf(self.work as f64 / WORK_BITS_PER_HALVING);
+        rate * account * work
+    }
+}
+
+/// Counts the leading zero bits of a block id
+pub fn block_work(block_id: &BlockID) -> u32 {
+    let mut bits = 0;
+    for byte in block_id {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// Trust gained by an account, based on the age and balance graphs
+fn account_trust(account_age: u64, balance: u64, params: &GraphParameters) -> f64 {
+    // age_graph starts negative, so it is shifted to start at 0 for new accounts
+    // the spam index is only checked locally, so it does not need to be deterministic
+    let age = (age_graph(account_age, params) - age_graph(0, params)).to_f64().max(0.0);
+    let balance = (1.0 + balance_graph(balance, params).to_f64()).log10();
+    age + balance
+}
+
+/// Calculates the spam index of a block
+#[tracing::instrument]
+pub async fn get_spam_index(block: &SignedBlock, state: &ChampStateArc) -> Result<SpamIndex, BlockValidationError> {
+    debug!("calculating spam index");
+
+    let account_id = generate_account_address(block.header.public_key.to_vec()).map_err(|_| Node::AccountError)?;
+    let params = state.config.read().await.chain_spec.consensus.graphs.clone();
+    let db = state.db.lock().await;
+
+    let latest_block = match db.get_latest_block_by_account(account_id).await {
+        Ok(block) => Some(block),
+        Err(DatabaseError::NoLastBlock) | Err(DatabaseError::BlockNotFound) => None,
+        Err(e) => return Err(Node::DBError(e).into()),
+    };
+
+    let (trust, recent_blocks) = match latest_block {
+        // accounts without any blocks have no trust and no recent blocks
+        None => (0.0, 0),
+        Some(latest_block) => {
+            let first_block = db.get_block_by_height(account_id, &0).await.map_err(Node::DBError)?;
+            let first_timestamp = first_block.map_or(latest_block.header.timestamp, |b| b.header.timestamp);
+            let account_age = block.header.timestamp.saturating_sub(first_timestamp);
+
+            let recent_blocks = db
+                .get_blocks(true, RATE_LOOKBACK_BLOCKS, 0, Some(account_id))
+                .await
+                .map_err(Node::DBError)?
+                .iter()
+                .filter(|b| b.header.timestamp + RATE_WINDOW >= block.header.timestamp)
+                .count() as u32;
+
+            (account_trust(account_age, latest_block.data.balance, &params), recent_blocks)
+        }
+    };
+
+    let index = SpamIndex {
+        work: block_work(&block.get_id()),
+        trust,
+        recent_blocks,
+    };
+
+    trace!("spam index: {:?} = {}", index, index.value());
+    Ok(index)
+}
+
+/// Rejects blocks with a spam index above the configured `consensus.max_spam_index`
+pub async fn check_spam_index(block: &SignedBlock, state: &ChampStateArc) -> Result<(), BlockValidationError> {
+    let max_spam_index = state.config.read().await.consensus.max_spam_index;
+    let index = get_spam_index(block, state).await?;
+
+    if index.value() > max_spam_index {
+        return Err(Validation::SpamIndexExceeded.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_work() {
+        assert_eq!(0, block_work(&[255; 32]));
+        assert_eq!(256, block_work(&[0; 32]));
+
+        let mut block_id = [0; 32];
+        block_id[1] = 0b0001_0000;
+        assert_eq!(11, block_work(&block_id));
+    }
+
+    #[test]
+    fn test_spam_index() {
+        let new_account = SpamIndex {
+            work: 0,
+            trust: 0.0,
+            recent_blocks: 0,
+        };
+        assert_eq!(1.0, new_account.value());
+
+        let busy_account = SpamIndex {
+            recent_blocks: 10,
+            ..new_account.clone()
+        };
+        assert_eq!(2.0, busy_account.value());
+
+        let busy_account_with_work = SpamIndex {
+            work: 8,
+            ..busy_account.clone()
+        };
+        assert_eq!(0.5, busy_account_with_work.value());
+
+        let trusted_account = SpamIndex {
+            trust: 1.0,
+            ..busy_account
+        };
+        assert_eq!(1.0, trusted_account.value());
+    }
+
+    #[test]
+    fn test_account_trust() {
+        let params = GraphParameters::default();
+        assert_eq!(0.0, account_trust(0, 0, &params));
+        assert!(account_trust(60 * 60 * 24 * 365, 0, &params) > account_trust(60 * 60 * 24 * 7, 0, &params));
+        assert!(account_trust(0, 1_000_000, &params) > account_trust(0, 1_000, &params));
+    }
+}
@@ -291,6 +291,19 @@ impl WalletManager {
     pub fn add_state(&mut self, state: ChampStateArc) {
         self.state = Some(state);
     }
+
+    /// adds an unlocked wallet that is only kept in memory
+    #[cfg(test)]
+    pub fn add_unlocked_wallet(
+        &mut self,
+        account_address: &str,
+        private_key: [u8; 32],
+    ) -> Result<(), WalletManagerError> {
+        let mut wallet = Wallet::new(account_address)?;
+        wallet.set_private_key(private_key);
+        self.wallets.insert(account_address.to_string(), wallet);
+        Ok(())
+    }
 }
 
 pub trait WalletInterface {